@@ -0,0 +1,63 @@
+#![no_main]
+
+use std::sync::LazyLock;
+use libfuzzer_sys::fuzz_target;
+use twoparty_fuzz::decode_each_version;
+use twoparty_ed25519::{keygen, rotate, sign};
+use twoparty_ed25519::generic::share::Ed25519Share;
+
+// the share of party2 that sign & rotate run against
+static PARTY2_SHARE: LazyLock<Ed25519Share> = LazyLock::new(|| {
+    let (party1_msg1, assets1) = keygen::party1::party1_step1();
+    let (party2_msg1, assets2) = keygen::party2::party2_step1();
+    let (party1_msg2, _pending_share1) = keygen::party1::party1_step2(party2_msg1, assets1).unwrap();
+    let (_party2_msg2, share2) = keygen::party2::party2_step2(party1_msg2, party1_msg1, assets2).unwrap();
+    share2
+});
+
+// the first byte picks the step message, the rest is its msg_detail.
+// mpc-server runs its step on the party1 messages of a session, the party2 messages are decoded by mpc-client
+fuzz_target!(|data: &[u8]| {
+    if data.is_empty() {
+        return;
+    }
+    let detail = &data[1..];
+    match data[0] % 9 {
+        0 => {
+            for (msg1, msg2) in decode_each_version::<(keygen::party1::Party1KeygenMsg1, keygen::party1::Party1KeygenMsg2)>(detail) {
+                let (_party2_msg1, assets2) = keygen::party2::party2_step1();
+                let _ = keygen::party2::party2_step2(msg2, msg1, assets2);
+            }
+        }
+        1 => {
+            decode_each_version::<keygen::party2::Party2KeygenMsg1>(detail);
+        }
+        2 => {
+            decode_each_version::<keygen::party2::Party2KeygenMsg2>(detail);
+        }
+        3 => {
+            for (msg1, msg2) in decode_each_version::<(sign::party1::Party1SignMsg1, sign::party1::Party1SignMsg2)>(detail) {
+                let (_party2_msg1, eph_keypair) = sign::party2::party2_step1(msg1.clone(), &PARTY2_SHARE);
+                let _ = sign::party2::party2_step2(msg2, msg1, eph_keypair, &PARTY2_SHARE);
+            }
+        }
+        4 => {
+            decode_each_version::<sign::party2::Party2SignMsg1>(detail);
+        }
+        5 => {
+            decode_each_version::<sign::party2::Party2SignMsg2>(detail);
+        }
+        6 => {
+            for (msg1, msg2) in decode_each_version::<(rotate::party1::Party1RotateMsg1, rotate::party1::Party1RotateMsg2)>(detail) {
+                let (_party2_msg1, delta_keypair) = rotate::party2::party2_step1();
+                let _ = rotate::party2::party2_step2(msg2, msg1, delta_keypair, &PARTY2_SHARE);
+            }
+        }
+        7 => {
+            decode_each_version::<rotate::party2::Party2RotateMsg1>(detail);
+        }
+        _ => {
+            decode_each_version::<rotate::party2::Party2RotateMsg2>(detail);
+        }
+    }
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use common::socketmsg::types::Mpc22Msg;
+use twoparty_fuzz::decode_each_version;
+
+fuzz_target!(|data: &[u8]| {
+    for mpc22_msg in decode_each_version::<Mpc22Msg>(data) {
+        // mpc-server decodes the unsigned payload at sign step1
+        if let Some(sign_payload) = &mpc22_msg.sign_payload {
+            let _ = sign_payload.decode();
+        }
+    }
+});
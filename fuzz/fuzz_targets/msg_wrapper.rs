@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use common::socketmsg::MsgWrapper;
+use common::socketmsg::codec::decode_detail;
+use common::socketmsg::types::Mpc22Msg;
+
+// what mpc-server does with a ws frame before dispatching it
+fuzz_target!(|data: &[u8]| {
+    if let Ok(msg_wrapper) = MsgWrapper::from_bytes(data) {
+        let _ = decode_detail::<Mpc22Msg>(msg_wrapper.version, &msg_wrapper.body);
+        // whatever decodes encodes back
+        msg_wrapper.to_bytes().unwrap();
+    }
+});
@@ -0,0 +1,71 @@
+#![no_main]
+
+use std::sync::LazyLock;
+use libfuzzer_sys::fuzz_target;
+use twoparty_fuzz::decode_each_version;
+use twoparty_secp256k1::{export, keygen, rotate, sign};
+use twoparty_secp256k1::generic::share::Party2Share;
+
+// the share of party2 that sign, rotate & export run against
+static PARTY2_SHARE: LazyLock<Party2Share> = LazyLock::new(|| {
+    let (party1_msg1, witness, party1_keypair) = keygen::party1::party1_step1();
+    let (party2_msg1, party2_keypair) = keygen::party2::party2_step1();
+    let (party1_msg2, _party1_share) = keygen::party1::party1_step2(party2_msg1, witness, party1_keypair).unwrap();
+    keygen::party2::party2_step2(party1_msg2, party1_msg1, party2_keypair).unwrap()
+});
+
+// the first byte picks the step message, the rest is its msg_detail.
+// mpc-server runs its step on the party1 messages of a session, the party2 messages are decoded by mpc-client
+fuzz_target!(|data: &[u8]| {
+    if data.is_empty() {
+        return;
+    }
+    let detail = &data[1..];
+    match data[0] % 11 {
+        0 => {
+            for (msg1, msg2) in decode_each_version::<(keygen::party1::Party1KeyGenMsg1, keygen::party1::Party1KeygenMsg2)>(detail) {
+                let (_party2_msg1, party2_keypair) = keygen::party2::party2_step1();
+                let _ = keygen::party2::party2_step2(msg2, msg1, party2_keypair);
+            }
+        }
+        1 => {
+            decode_each_version::<keygen::party2::Party2KeyGenMsg1>(detail);
+        }
+        2 => {
+            for (msg1, msg2) in decode_each_version::<(sign::party1::Party1SignMsg1, sign::party1::Party1SignMsg2)>(detail) {
+                let (_party2_msg1, eph_keypair) = sign::party2::party2_step1();
+                let _ = sign::party2::party2_step2(msg2, msg1, &PARTY2_SHARE, eph_keypair);
+            }
+        }
+        3 => {
+            decode_each_version::<sign::party2::Party2SignMsg1>(detail);
+        }
+        4 => {
+            decode_each_version::<sign::party2::Party2SignMsg2>(detail);
+        }
+        5 => {
+            for (msg1, msg2) in decode_each_version::<(rotate::party1::Party1RotateMsg1, rotate::party1::Party1RotateMsg2)>(detail) {
+                let (_party2_msg1, seed_keypair) = rotate::party2::party2_step1();
+                let _ = rotate::party2::party2_step2(msg2, msg1, seed_keypair, &PARTY2_SHARE);
+            }
+        }
+        6 => {
+            decode_each_version::<rotate::party2::Party2RotateMsg1>(detail);
+        }
+        7 => {
+            decode_each_version::<rotate::party2::Party2RotateMsg2>(detail);
+        }
+        8 => {
+            for msg2 in decode_each_version::<export::party1::Party1ExportMsg2>(detail) {
+                let party2_msg1 = export::party2::party2_step1();
+                let _ = export::party2::party2_step2(msg2, &party2_msg1.challenge, &PARTY2_SHARE);
+            }
+        }
+        9 => {
+            decode_each_version::<export::party2::Party2ExportMsg1>(detail);
+        }
+        _ => {
+            decode_each_version::<export::party2::Party2ExportMsg2>(detail);
+        }
+    }
+});
@@ -0,0 +1,10 @@
+use serde::de::DeserializeOwned;
+use common::socketmsg::MSG_VERSIONS;
+use common::socketmsg::codec::decode_detail;
+
+// decode data as a msg_detail of every msg version, malformed data must come back as an error
+pub fn decode_each_version<T: DeserializeOwned>(data: &[u8]) -> Vec<T> {
+    MSG_VERSIONS.iter()
+        .filter_map(|msg_version| decode_detail::<T>(*msg_version, data).ok())
+        .collect()
+}
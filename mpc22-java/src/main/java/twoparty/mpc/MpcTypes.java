@@ -3,14 +3,20 @@ package twoparty.mpc;
 
 public class MpcTypes {
 
-    private static final int MPC_SCOPE_SECP256K1ECDSA = 1;
-    private static final int MPC_SCOPE_ED25519EDDSA = 2;
+    public static final int MPC_SCOPE_SECP256K1ECDSA = 1;
+    public static final int MPC_SCOPE_ED25519EDDSA = 2;
 
     public static class SavedShare {
+        public int schema_version;
         public String identity_id;
         public String share_id;
         public int scope;
         public int party;
+        // 0 after keygen, increased by every rotation
+        public long epoch;
+        // millis
+        public long created_at;
+        public long rotated_at;
         // secp256k1 uncompressed_pub's type is [u8;65], explicitly [4,x,y]
         // ed25519 uncompressed_pub's type is [u8;32], explicitly [y] with the x is negative at last bit
         public byte[] uncompressed_pub;
@@ -26,6 +32,119 @@ public class MpcTypes {
         public int v;
     }
 
+    public static final int PAYLOAD_FORMAT_ETH_TX = 1;
+    public static final int PAYLOAD_FORMAT_EIP712 = 2;
+    public static final int PAYLOAD_FORMAT_PSBT = 3;
+    public static final int PAYLOAD_FORMAT_SUI_INTENT = 4;
+
+    public static class SignPayload {
+        public int format;
+        public byte[] payload;
+        // psbt only, the input to sign
+        public int input_index;
+    }
+
+    public static final int SHARE_STATUS_NOT_FOUND = 0;
+    public static final int SHARE_STATUS_ACTIVE = 1;
+    public static final int SHARE_STATUS_PENDING = 2;
+    public static final int SHARE_STATUS_RETIRED = 3;
+
+    public static class ShareStatus {
+        public String share_id;
+        public int status;
+        public long epoch;
+        public String active_share_id;
+        public String pending_share_id;
+    }
+
+    public static final int NOTICE_ROTATION_REQUIRED = 1;
+    public static final int NOTICE_SHARE_FROZEN = 2;
+    public static final int NOTICE_POLICY_CHANGED = 3;
+
+    public static class Notice {
+        // empty if the notice is not about a single share
+        public String share_id;
+        public String message;
+        // millis
+        public long created_at;
+    }
+
+    public static class NoticeEvent {
+        public String notice_id;
+        public int notice_type;
+        public Notice notice;
+    }
+
+    // fields left null take the defaults of mpc-client
+    public static class MpcClientConfig {
+        public String url;
+        public String identity_id;
+        public String access_token;
+        // fetch /ws-config & /capabilities before connecting, operations the server does not advertise are refused
+        public Boolean auto_config;
+        // overrides the ws_client_interval of the server
+        public Integer heartbeat_sec;
+        public Long request_timeout_ms;
+        public Integer outbound_queue;
+        public Long max_message_size;
+        public ReconnectPolicy reconnect;
+        public StepRetryPolicy step_retry;
+        // offer the binary wire encoding, false keeps json on the wire for debugging
+        public Boolean binary_encoding;
+    }
+
+    public static class ReconnectPolicy {
+        public boolean enabled = true;
+        public long initial_backoff_ms = 500;
+        public long max_backoff_ms = 30000;
+        // 0 means retry forever
+        public int max_attempts;
+    }
+
+    public static class StepRetryPolicy {
+        public int max_retries = 2;
+        public long resume_timeout_ms = 30000;
+    }
+
     // the sig type of ed25519 is [u8;64] as described in RFC8032
+
+    // codes of MpcErrorKind, stable across releases
+    public static final int ERROR_INTERNAL = 1000;
+    public static final int ERROR_MALFORMED_MESSAGE = 1001;
+    public static final int ERROR_PROOF_VERIFICATION_FAILED = 1002;
+    public static final int ERROR_TRANSPORT_TIMEOUT = 1003;
+    public static final int ERROR_DISCONNECTED = 1004;
+    public static final int ERROR_SHARE_NOT_FOUND = 1005;
+    public static final int ERROR_POLICY_DENIED = 1006;
+    public static final int ERROR_VERSION_MISMATCH = 1007;
+    public static final int ERROR_UNAUTHORIZED = 1008;
+    public static final int ERROR_FORBIDDEN = 1009;
+    public static final int ERROR_RATE_LIMITED = 1010;
+    public static final int ERROR_INVALID_REQUEST = 1011;
+    public static final int ERROR_NOT_FOUND = 1012;
+    public static final int ERROR_UNSUPPORTED = 1013;
+    public static final int ERROR_UNAVAILABLE = 1014;
+
+    // a failed NativeMpc call returns [data, error message, error code]
+    public static class MpcException extends RuntimeException {
+        public final int code;
+
+        public MpcException(int code, String message) {
+            super(message);
+            this.code = code;
+        }
+
+        public static boolean isFailed(byte[][] result) {
+            return result.length > 1;
+        }
+
+        public static MpcException of(byte[][] result) {
+            int code = ERROR_INTERNAL;
+            if (result.length > 2) {
+                code = Integer.parseInt(new String(result[2]));
+            }
+            return new MpcException(code, new String(result[1]));
+        }
+    }
 }
 
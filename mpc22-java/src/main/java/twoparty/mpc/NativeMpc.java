@@ -2,19 +2,79 @@ package twoparty.mpc;
 
 import java.util.Arrays;
 
+// every call returns [data] on success, [data, error message, error code] on failure,
+// see MpcTypes.MpcException & MpcTypes.ERROR_*
+// access_token is the bearer jwt of the websocket upgrade, null or empty when the server disables authentication
 public class NativeMpc {
 
-    public static native byte[][] secp256k1Keygen(String identity_id, String ws_url);
+    public static native byte[][] secp256k1Keygen(String identity_id, String ws_url, String access_token);
 
-    public static native byte[][] secp256k1Sign(String ws_url, byte[] saved_share, byte[] message_digest);
+    public static native byte[][] secp256k1Sign(String ws_url, String access_token, byte[] saved_share, byte[] message_digest);
 
-    public static native byte[][] secp256k1Rotate(String ws_url, byte[] saved_share);
+    // sign_payload is json of MpcTypes.SignPayload, the message_digest is computed from it
+    public static native byte[][] secp256k1SignPayload(String ws_url, String access_token, byte[] saved_share, byte[] sign_payload);
 
-    public static native byte[][] secp256k1Export(String ws_url, byte[] saved_share);
+    public static native byte[][] secp256k1Rotate(String ws_url, String access_token, byte[] saved_share);
 
-    public static native byte[][] ed25519Keygen(String identity_id, String ws_url);
+    public static native byte[][] secp256k1Export(String ws_url, String access_token, byte[] saved_share);
 
-    public static native byte[][] ed25519Sign(String ws_url, byte[] saved_share, byte[] message_digest);
+    public static native byte[][] ed25519Keygen(String identity_id, String ws_url, String access_token);
 
-    public static native byte[][] ed25519Rotate(String ws_url, byte[] saved_share);
+    public static native byte[][] ed25519Sign(String ws_url, String access_token, byte[] saved_share, byte[] message_digest);
+
+    public static native byte[][] ed25519SignPayload(String ws_url, String access_token, byte[] saved_share, byte[] sign_payload);
+
+    public static native byte[][] ed25519Rotate(String ws_url, String access_token, byte[] saved_share);
+
+    // the rotated share stays pending at server until committed, persist it before commit
+    public static native byte[][] rotateCommit(String ws_url, String access_token, byte[] old_share, byte[] new_share);
+
+    public static native byte[][] shareStatus(String ws_url, String access_token, byte[] saved_share);
+
+    // kms_key is the 32 bytes aes-256-gcm key which encrypts identity-secret & share
+    public static native byte[][] identityBind(String ws_url, String access_token, byte[] saved_share, byte[] kms_key);
+
+    // the result is a json array of SavedShare
+    public static native byte[][] identityRecover(String identity_id, String ws_url, String access_token, byte[] kms_key);
+
+    public interface NoticeListener {
+        // notice_event is json of MpcTypes.NoticeEvent, called on a native thread,
+        // the notice is acknowledged after it returns and may be delivered again if the ack is lost
+        void onNotice(byte[] notice_event);
+    }
+
+    // the result is the json of the subscription handle, unacknowledged notices are delivered first
+    public static native byte[][] subscribeNotice(String identity_id, String ws_url, String access_token, NoticeListener listener);
+
+    public static native byte[][] unsubscribeNotice(long handle);
+
+    // config is json of MpcTypes.MpcClientConfig, the result is the json of the client handle.
+    // the client keeps one connection & the loaded shares until mpcClientClose, its calls run one at a time
+    public static native byte[][] mpcClientConnect(byte[] config);
+
+    public static native byte[][] mpcClientClose(long handle);
+
+    // only shares of the identity_id of the config can be loaded
+    public static native byte[][] mpcClientLoadShare(long handle, byte[] saved_share);
+
+    public static native byte[][] mpcClientUnloadShare(long handle, String share_id);
+
+    // scope is MpcTypes.MPC_SCOPE_*, the new share is loaded
+    public static native byte[][] mpcClientKeygen(long handle, int scope);
+
+    // the result is json of MpcTypes.Secp256k1Sig, or the 64 bytes sig of ed25519
+    public static native byte[][] mpcClientSign(long handle, String share_id, byte[] message_digest);
+
+    public static native byte[][] mpcClientSignPayload(long handle, String share_id, byte[] sign_payload);
+
+    // the new share is not loaded until mpcClientRotateCommit, persist it before commit
+    public static native byte[][] mpcClientRotate(long handle, String share_id);
+
+    // kms_key, the LocalKms key of the recovery binding, binds the new share in place of the old one, null does not bind
+    public static native byte[][] mpcClientRotateCommit(long handle, String share_id, byte[] new_share, byte[] kms_key);
+
+    // secp256k1 shares only
+    public static native byte[][] mpcClientExport(long handle, String share_id);
+
+    public static native byte[][] mpcClientShareStatus(long handle, String share_id);
 }
@@ -17,9 +17,9 @@ public class Ed25519Test {
         // keygen
         String identity_id = "wangcy";
         String ws_url = "ws://localhost:8822/ws";
-        byte[][] keygen_result = NativeMpc.ed25519Keygen(identity_id, ws_url);
-        if (keygen_result.length == 2) {
-            throw new RuntimeException(new String(keygen_result[1]));
+        byte[][] keygen_result = NativeMpc.ed25519Keygen(identity_id, ws_url, null);
+        if (MpcTypes.MpcException.isFailed(keygen_result)) {
+            throw MpcTypes.MpcException.of(keygen_result);
         }
         byte[] share_bytes = keygen_result[0];
         MpcTypes.SavedShare savedShare = JSON.parseObject(share_bytes, MpcTypes.SavedShare.class);
@@ -28,9 +28,9 @@ public class Ed25519Test {
 
         // sign
         byte[] message_digest = "hello ed25519".getBytes();
-        byte[][] sign_result = NativeMpc.ed25519Sign(ws_url, share_bytes, message_digest);
-        if (sign_result.length == 2) {
-            throw new RuntimeException(new String(sign_result[1]));
+        byte[][] sign_result = NativeMpc.ed25519Sign(ws_url, null, share_bytes, message_digest);
+        if (MpcTypes.MpcException.isFailed(sign_result)) {
+            throw MpcTypes.MpcException.of(sign_result);
         }
         byte[] sig_bytes = sign_result[0];
         System.out.println("sig bytes=" + sig_bytes.length);
@@ -39,19 +39,25 @@ public class Ed25519Test {
         }
 
         // rotate
-        byte[][] rotate_result = NativeMpc.ed25519Rotate(ws_url, share_bytes);
-        if (rotate_result.length == 2) {
-            throw new RuntimeException(new String(rotate_result[1]));
+        byte[][] rotate_result = NativeMpc.ed25519Rotate(ws_url, null, share_bytes);
+        if (MpcTypes.MpcException.isFailed(rotate_result)) {
+            throw MpcTypes.MpcException.of(rotate_result);
         }
         byte[] new_share_bytes = rotate_result[0];
         MpcTypes.SavedShare new_share = JSON.parseObject(new_share_bytes, MpcTypes.SavedShare.class);
         System.out.printf("identity_id=%s, party=%d, scope=%d, share_id=%s, public_key_length=%d \n",
                 new_share.identity_id, new_share.party, new_share.scope, new_share.share_id, new_share.uncompressed_pub.length);
 
+        // commit the rotated share after it is persisted
+        byte[][] commit_result = NativeMpc.rotateCommit(ws_url, null, share_bytes, new_share_bytes);
+        if (MpcTypes.MpcException.isFailed(commit_result)) {
+            throw MpcTypes.MpcException.of(commit_result);
+        }
+
         // sign with rotated_share
-        byte[][] rotate_sign_result = NativeMpc.ed25519Sign(ws_url, new_share_bytes, message_digest);
-        if (rotate_sign_result.length == 2) {
-            throw new RuntimeException(new String(rotate_sign_result[1]));
+        byte[][] rotate_sign_result = NativeMpc.ed25519Sign(ws_url, null, new_share_bytes, message_digest);
+        if (MpcTypes.MpcException.isFailed(rotate_sign_result)) {
+            throw MpcTypes.MpcException.of(rotate_sign_result);
         }
         byte[] rotate_sig_bytes = sign_result[0];
         System.out.println("rotate sig bytes=" + rotate_sig_bytes.length);
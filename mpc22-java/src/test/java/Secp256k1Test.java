@@ -18,9 +18,9 @@ public class Secp256k1Test {
         // keygen
         String identity_id = "wangcy";
         String ws_url = "ws://localhost:8822/ws";
-        byte[][] keygen_result = NativeMpc.secp256k1Keygen(identity_id, ws_url);
-        if (keygen_result.length == 2) {
-            throw new RuntimeException(new String(keygen_result[1]));
+        byte[][] keygen_result = NativeMpc.secp256k1Keygen(identity_id, ws_url, null);
+        if (MpcTypes.MpcException.isFailed(keygen_result)) {
+            throw MpcTypes.MpcException.of(keygen_result);
         }
         byte[] share_bytes = keygen_result[0];
         MpcTypes.SavedShare savedShare = JSON.parseObject(share_bytes, MpcTypes.SavedShare.class);
@@ -30,33 +30,39 @@ public class Secp256k1Test {
 
         // sign
         byte[] message_digest = "hello secp256k1".getBytes();
-        byte[][] sign_result = NativeMpc.secp256k1Sign(ws_url, share_bytes, message_digest);
-        if (sign_result.length == 2) {
-            throw new RuntimeException(new String(sign_result[1]));
+        byte[][] sign_result = NativeMpc.secp256k1Sign(ws_url, null, share_bytes, message_digest);
+        if (MpcTypes.MpcException.isFailed(sign_result)) {
+            throw MpcTypes.MpcException.of(sign_result);
         }
         byte[] sig_bytes = sign_result[0];
         MpcTypes.Secp256k1Sig sig = JSON.parseObject(sig_bytes, MpcTypes.Secp256k1Sig.class);
         System.out.println(JSON.toJSONString(sig));
 
         // rotate
-        byte[][] rotate_result = NativeMpc.secp256k1Rotate(ws_url, share_bytes);
-        if (rotate_result.length == 2) {
-            throw new RuntimeException(new String(rotate_result[1]));
+        byte[][] rotate_result = NativeMpc.secp256k1Rotate(ws_url, null, share_bytes);
+        if (MpcTypes.MpcException.isFailed(rotate_result)) {
+            throw MpcTypes.MpcException.of(rotate_result);
         }
         byte[] new_share_bytes = rotate_result[0];
         MpcTypes.SavedShare new_share = JSON.parseObject(new_share_bytes, MpcTypes.SavedShare.class);
         System.out.printf("identity_id=%s, party=%d, scope=%d, share_id=%s, public_key_length=%d \n",
                 new_share.identity_id, new_share.party, new_share.scope, new_share.share_id, new_share.uncompressed_pub.length);
 
+        // commit the rotated share after it is persisted
+        byte[][] commit_result = NativeMpc.rotateCommit(ws_url, null, share_bytes, new_share_bytes);
+        if (MpcTypes.MpcException.isFailed(commit_result)) {
+            throw MpcTypes.MpcException.of(commit_result);
+        }
+
         // check public_key consistence, no need to do this, library has checked
         if (!Arrays.equals(savedShare.uncompressed_pub, new_share.uncompressed_pub)) {
             throw new RuntimeException("public_key not consistent");
         }
 
         // export
-        byte[][] export_result = NativeMpc.secp256k1Export(ws_url, new_share_bytes);
-        if (export_result.length == 2) {
-            throw new RuntimeException(new String(export_result[1]));
+        byte[][] export_result = NativeMpc.secp256k1Export(ws_url, null, new_share_bytes);
+        if (MpcTypes.MpcException.isFailed(export_result)) {
+            throw MpcTypes.MpcException.of(export_result);
         }
         byte[] x_bytes = export_result[0];
         String hex_encoded_x = new String(x_bytes);
@@ -3,4 +3,26 @@ use std::env;
 fn main() {
     // Set the CARGO environment variable to the Cargo executable
     env::set_var("CARGO", "cargo");
-}
\ No newline at end of file
+
+    // generate the header of the c api, see src/capi
+    if env::var("CARGO_FEATURE_NATIVE").is_err() {
+        return;
+    }
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src/capi/mod.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=TWOPARTY_GENERATE_HEADER");
+    // only src/capi is parsed, the jni functions of src/ffi stay out of the header
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir)).unwrap();
+    let bindings = cbindgen::Builder::new()
+        .with_src(format!("{}/src/capi/mod.rs", crate_dir))
+        .with_config(config)
+        .generate()
+        .expect("fail to generate twoparty_client.h");
+    // a build never touches the source tree, e.g. when twoparty-python builds this crate as a dependency.
+    // TWOPARTY_GENERATE_HEADER=1 refreshes the committed include/twoparty_client.h, see `make check-header`
+    bindings.write_to_file(format!("{}/twoparty_client.h", env::var("OUT_DIR").unwrap()));
+    if env::var("TWOPARTY_GENERATE_HEADER").is_ok() {
+        bindings.write_to_file(format!("{}/include/twoparty_client.h", crate_dir));
+    }
+}
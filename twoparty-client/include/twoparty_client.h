@@ -0,0 +1,140 @@
+#ifndef TWOPARTY_CLIENT_H
+#define TWOPARTY_CLIENT_H
+
+/* generated by build.rs with cbindgen, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define TWOPARTY_SCOPE_SECP256K1ECDSA 1
+
+#define TWOPARTY_SCOPE_ED25519EDDSA 2
+
+#define TWOPARTY_OK 0
+
+#define TWOPARTY_ERROR_INTERNAL 1000
+
+#define TWOPARTY_ERROR_MALFORMED_MESSAGE 1001
+
+#define TWOPARTY_ERROR_PROOF_VERIFICATION_FAILED 1002
+
+#define TWOPARTY_ERROR_TRANSPORT_TIMEOUT 1003
+
+#define TWOPARTY_ERROR_DISCONNECTED 1004
+
+#define TWOPARTY_ERROR_SHARE_NOT_FOUND 1005
+
+#define TWOPARTY_ERROR_POLICY_DENIED 1006
+
+#define TWOPARTY_ERROR_VERSION_MISMATCH 1007
+
+#define TWOPARTY_ERROR_UNAUTHORIZED 1008
+
+#define TWOPARTY_ERROR_FORBIDDEN 1009
+
+#define TWOPARTY_ERROR_RATE_LIMITED 1010
+
+#define TWOPARTY_ERROR_INVALID_REQUEST 1011
+
+#define TWOPARTY_ERROR_NOT_FOUND 1012
+
+#define TWOPARTY_ERROR_UNSUPPORTED 1013
+
+#define TWOPARTY_ERROR_UNAVAILABLE 1014
+
+typedef struct TwopartyClient TwopartyClient;
+
+typedef struct TwopartyBuffer {
+  uint8_t *data;
+  size_t len;
+} TwopartyBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+void twoparty_buffer_free(struct TwopartyBuffer buffer);
+
+int32_t twoparty_client_connect(const uint8_t *config,
+                                size_t config_len,
+                                struct TwopartyClient **out_client,
+                                struct TwopartyBuffer *out_err);
+
+void twoparty_client_free(struct TwopartyClient *client);
+
+int32_t twoparty_client_load_share(const struct TwopartyClient *client,
+                                   const uint8_t *share,
+                                   size_t share_len,
+                                   struct TwopartyBuffer *out_err);
+
+int32_t twoparty_client_unload_share(const struct TwopartyClient *client,
+                                     const char *share_id,
+                                     struct TwopartyBuffer *out_err);
+
+int32_t twoparty_client_keygen(const struct TwopartyClient *client,
+                               uint8_t scope,
+                               struct TwopartyBuffer *out,
+                               struct TwopartyBuffer *out_err);
+
+int32_t twoparty_client_sign(const struct TwopartyClient *client,
+                             const char *share_id,
+                             const uint8_t *message_digest,
+                             size_t digest_len,
+                             struct TwopartyBuffer *out,
+                             struct TwopartyBuffer *out_err);
+
+int32_t twoparty_client_sign_payload(const struct TwopartyClient *client,
+                                     const char *share_id,
+                                     const uint8_t *sign_payload,
+                                     size_t payload_len,
+                                     struct TwopartyBuffer *out,
+                                     struct TwopartyBuffer *out_err);
+
+int32_t twoparty_client_rotate(const struct TwopartyClient *client,
+                               const char *share_id,
+                               struct TwopartyBuffer *out,
+                               struct TwopartyBuffer *out_err);
+
+int32_t twoparty_client_rotate_commit(const struct TwopartyClient *client,
+                                      const char *share_id,
+                                      const uint8_t *new_share,
+                                      size_t new_share_len,
+                                      const uint8_t *kms_key,
+                                      size_t kms_key_len,
+                                      struct TwopartyBuffer *out_err);
+
+int32_t twoparty_client_identity_bind(const struct TwopartyClient *client,
+                                      const char *share_id,
+                                      const uint8_t *kms_key,
+                                      size_t kms_key_len,
+                                      struct TwopartyBuffer *out_err);
+
+int32_t twoparty_client_identity_recover(const struct TwopartyClient *client,
+                                         const uint8_t *kms_key,
+                                         size_t kms_key_len,
+                                         struct TwopartyBuffer *out,
+                                         struct TwopartyBuffer *out_err);
+
+int32_t twoparty_client_export(const struct TwopartyClient *client,
+                               const char *share_id,
+                               struct TwopartyBuffer *out,
+                               struct TwopartyBuffer *out_err);
+
+int32_t twoparty_client_share_status(const struct TwopartyClient *client,
+                                     const char *share_id,
+                                     struct TwopartyBuffer *out,
+                                     struct TwopartyBuffer *out_err);
+
+int32_t twoparty_share_address(const uint8_t *share,
+                               size_t share_len,
+                               struct TwopartyBuffer *out,
+                               struct TwopartyBuffer *out_err);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* TWOPARTY_CLIENT_H */
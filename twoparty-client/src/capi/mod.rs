@@ -0,0 +1,378 @@
+// The C interface for native hosts without a JVM, e.g. iOS & cgo. include/twoparty_client.h is generated by build.rs.
+//
+// every call returns TWOPARTY_OK or the code of an MpcErrorKind. results & error messages are written to
+// caller-provided TwopartyBuffer, which must be released with twoparty_buffer_free. strings are utf-8 without
+// a trailing nul, except the share_id inputs which are nul terminated.
+//
+// pointers are checked for null before use, the rest of the contract is on the C side, see the # Safety of each
+// function.
+
+use std::ffi::{c_char, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use tokio::runtime::Runtime;
+use common::address::share_address;
+use common::errors::{MpcError, MpcErrorKind};
+use common::payload::SignPayload;
+use common::socketmsg::types::SavedShare;
+use crate::ffi::signature_bytes;
+use crate::kms::{Kms, LocalKms};
+use crate::mpc::client::{MpcClient, MpcClientConfig};
+
+#[cfg(test)]
+mod test;
+
+// MPC_SCOPE_*, literals so cbindgen can emit them
+pub const TWOPARTY_SCOPE_SECP256K1ECDSA: u8 = 1;
+pub const TWOPARTY_SCOPE_ED25519EDDSA: u8 = 2;
+
+pub const TWOPARTY_OK: i32 = 0;
+// the codes of MpcErrorKind
+pub const TWOPARTY_ERROR_INTERNAL: i32 = 1000;
+pub const TWOPARTY_ERROR_MALFORMED_MESSAGE: i32 = 1001;
+pub const TWOPARTY_ERROR_PROOF_VERIFICATION_FAILED: i32 = 1002;
+pub const TWOPARTY_ERROR_TRANSPORT_TIMEOUT: i32 = 1003;
+pub const TWOPARTY_ERROR_DISCONNECTED: i32 = 1004;
+pub const TWOPARTY_ERROR_SHARE_NOT_FOUND: i32 = 1005;
+pub const TWOPARTY_ERROR_POLICY_DENIED: i32 = 1006;
+pub const TWOPARTY_ERROR_VERSION_MISMATCH: i32 = 1007;
+pub const TWOPARTY_ERROR_UNAUTHORIZED: i32 = 1008;
+pub const TWOPARTY_ERROR_FORBIDDEN: i32 = 1009;
+pub const TWOPARTY_ERROR_RATE_LIMITED: i32 = 1010;
+pub const TWOPARTY_ERROR_INVALID_REQUEST: i32 = 1011;
+pub const TWOPARTY_ERROR_NOT_FOUND: i32 = 1012;
+pub const TWOPARTY_ERROR_UNSUPPORTED: i32 = 1013;
+pub const TWOPARTY_ERROR_UNAVAILABLE: i32 = 1014;
+
+// bytes owned by the library, data is null if len is 0
+#[repr(C)]
+pub struct TwopartyBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl TwopartyBuffer {
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        if bytes.is_empty() {
+            return TwopartyBuffer { data: ptr::null_mut(), len: 0 };
+        }
+        let boxed = bytes.into_boxed_slice();
+        let len = boxed.len();
+        TwopartyBuffer { data: Box::into_raw(boxed) as *mut u8, len }
+    }
+}
+
+// opaque to C, one connection to party2 with the loaded shares, see MpcClient
+pub struct TwopartyClient {
+    rt: Runtime,
+    client: MpcClient,
+}
+
+/// # Safety
+/// buffer must be zeroed or filled by this library, and released only once.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_buffer_free(buffer: TwopartyBuffer) {
+    if buffer.data.is_null() || buffer.len == 0 {
+        return;
+    }
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)));
+}
+
+/// config is json of MpcClientConfig, the client must be released with twoparty_client_free
+///
+/// # Safety
+/// config must point to config_len readable bytes. out_client must be writable, out_err writable or null.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_client_connect(config: *const u8, config_len: usize, out_client: *mut *mut TwopartyClient, out_err: *mut TwopartyBuffer) -> i32 {
+    if out_client.is_null() {
+        return write_err(out_err, invalid_arg("out_client"));
+    }
+    let result = guard(|| {
+        let config_bytes = bytes_arg(config, config_len, "config")?;
+        let config = serde_json::from_slice::<MpcClientConfig>(config_bytes)
+            .map_err(|e| MpcError::new(MpcErrorKind::MalformedMessage, format!("fail to parse config:{}", e)))?;
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .map_err(|e| MpcError::new(MpcErrorKind::Internal, e.to_string()))?;
+        let client = rt.block_on(MpcClient::connect(config))?;
+        Ok(Box::new(TwopartyClient { rt, client }))
+    });
+    match result {
+        Ok(client) => {
+            *out_client = Box::into_raw(client);
+            TWOPARTY_OK
+        }
+        Err(err) => write_err(out_err, err),
+    }
+}
+
+/// the connection is closed, the client must not be used afterwards
+///
+/// # Safety
+/// client must be null or returned by twoparty_client_connect, and not be freed twice.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_client_free(client: *mut TwopartyClient) {
+    if client.is_null() {
+        return;
+    }
+    drop(Box::from_raw(client));
+}
+
+/// share is json of SavedShare, only shares of the identity_id of the config can be loaded
+///
+/// # Safety
+/// client must be null or a live client, share must point to share_len readable bytes and out_err be writable or null.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_client_load_share(client: *const TwopartyClient, share: *const u8, share_len: usize, out_err: *mut TwopartyBuffer) -> i32 {
+    client_unit_call(client, out_err, |client| {
+        let saved_share = share_arg(share, share_len)?;
+        client.client.load_share(saved_share)
+    })
+}
+
+/// # Safety
+/// client must be null or a live client, share_id null or nul terminated and out_err writable or null.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_client_unload_share(client: *const TwopartyClient, share_id: *const c_char, out_err: *mut TwopartyBuffer) -> i32 {
+    client_unit_call(client, out_err, |client| {
+        client.client.unload_share(str_arg(share_id, "share_id")?);
+        Ok(())
+    })
+}
+
+/// scope is TWOPARTY_SCOPE_*, out is json of the new SavedShare, which is loaded
+///
+/// # Safety
+/// client must be null or a live client, out & out_err writable or null.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_client_keygen(client: *const TwopartyClient, scope: u8, out: *mut TwopartyBuffer, out_err: *mut TwopartyBuffer) -> i32 {
+    client_bytes_call(client, out, out_err, |client| {
+        let saved_share = client.rt.block_on(client.client.keygen(scope))?;
+        Ok(serde_json::to_vec(&saved_share).unwrap())
+    })
+}
+
+/// out is json of Secp256k1Sig, or the 64 bytes R || s of ed25519
+///
+/// # Safety
+/// client must be null or a live client, share_id null or nul terminated, message_digest must point to digest_len
+/// readable bytes, out & out_err writable or null.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_client_sign(client: *const TwopartyClient, share_id: *const c_char, message_digest: *const u8, digest_len: usize, out: *mut TwopartyBuffer, out_err: *mut TwopartyBuffer) -> i32 {
+    client_bytes_call(client, out, out_err, |client| {
+        let share_id = str_arg(share_id, "share_id")?;
+        let message_digest = bytes_arg(message_digest, digest_len, "message_digest")?.to_vec();
+        let sig = client.rt.block_on(client.client.sign(share_id, message_digest))?;
+        Ok(signature_bytes(sig))
+    })
+}
+
+/// sign_payload is json of SignPayload, party2 checks it and the digest is computed from it
+///
+/// # Safety
+/// client must be null or a live client, share_id null or nul terminated, sign_payload must point to payload_len
+/// readable bytes, out & out_err writable or null.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_client_sign_payload(client: *const TwopartyClient, share_id: *const c_char, sign_payload: *const u8, payload_len: usize, out: *mut TwopartyBuffer, out_err: *mut TwopartyBuffer) -> i32 {
+    client_bytes_call(client, out, out_err, |client| {
+        let share_id = str_arg(share_id, "share_id")?;
+        let payload_bytes = bytes_arg(sign_payload, payload_len, "sign_payload")?;
+        let sign_payload = serde_json::from_slice::<SignPayload>(payload_bytes)
+            .map_err(|e| MpcError::new(MpcErrorKind::MalformedMessage, format!("fail to parse sign_payload:{}", e)))?;
+        let sig = client.rt.block_on(client.client.sign_payload(share_id, sign_payload))?;
+        Ok(signature_bytes(sig))
+    })
+}
+
+/// out is json of the new SavedShare, it stays pending at server until twoparty_client_rotate_commit,
+/// persist it before commit
+///
+/// # Safety
+/// client must be null or a live client, share_id null or nul terminated, out & out_err writable or null.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_client_rotate(client: *const TwopartyClient, share_id: *const c_char, out: *mut TwopartyBuffer, out_err: *mut TwopartyBuffer) -> i32 {
+    client_bytes_call(client, out, out_err, |client| {
+        let new_share = client.rt.block_on(client.client.rotate(str_arg(share_id, "share_id")?))?;
+        Ok(serde_json::to_vec(&new_share).unwrap())
+    })
+}
+
+/// the old share is replaced by new_share, json of SavedShare. the commit drops the recovery binding of the old share,
+/// kms_key, the 32 bytes LocalKms key of the binding, binds new_share in its place. null kms_key does not bind
+///
+/// # Safety
+/// client must be null or a live client, share_id null or nul terminated, new_share must point to new_share_len
+/// readable bytes, kms_key be null or point to kms_key_len readable bytes and out_err be writable or null.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_client_rotate_commit(client: *const TwopartyClient, share_id: *const c_char, new_share: *const u8, new_share_len: usize, kms_key: *const u8, kms_key_len: usize, out_err: *mut TwopartyBuffer) -> i32 {
+    client_unit_call(client, out_err, |client| {
+        let share_id = str_arg(share_id, "share_id")?;
+        let new_share = share_arg(new_share, new_share_len)?;
+        let option_kms = if kms_key.is_null() { None } else { Some(kms_arg(kms_key, kms_key_len)?) };
+        client.rt.block_on(client.client.rotate_commit(share_id, new_share, option_kms.as_ref().map(|kms| kms as &dyn Kms)))
+    })
+}
+
+/// bind a loaded share for recovery, encrypted with kms_key, the 32 bytes LocalKms key
+///
+/// # Safety
+/// client must be null or a live client, share_id null or nul terminated, kms_key must point to kms_key_len
+/// readable bytes and out_err be writable or null.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_client_identity_bind(client: *const TwopartyClient, share_id: *const c_char, kms_key: *const u8, kms_key_len: usize, out_err: *mut TwopartyBuffer) -> i32 {
+    client_unit_call(client, out_err, |client| {
+        let share_id = str_arg(share_id, "share_id")?;
+        let kms = kms_arg(kms_key, kms_key_len)?;
+        client.rt.block_on(client.client.identity_bind(share_id, &kms))
+    })
+}
+
+/// out is json of the recovered SavedShare array, they are loaded into the client
+///
+/// # Safety
+/// client must be null or a live client, kms_key must point to kms_key_len readable bytes, out & out_err writable or null.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_client_identity_recover(client: *const TwopartyClient, kms_key: *const u8, kms_key_len: usize, out: *mut TwopartyBuffer, out_err: *mut TwopartyBuffer) -> i32 {
+    client_bytes_call(client, out, out_err, |client| {
+        let kms = kms_arg(kms_key, kms_key_len)?;
+        let saved_shares = client.rt.block_on(client.client.identity_recover(&kms))?;
+        Ok(serde_json::to_vec(&saved_shares).unwrap())
+    })
+}
+
+/// out is the hex encoded private key, secp256k1 shares only
+///
+/// # Safety
+/// client must be null or a live client, share_id null or nul terminated, out & out_err writable or null.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_client_export(client: *const TwopartyClient, share_id: *const c_char, out: *mut TwopartyBuffer, out_err: *mut TwopartyBuffer) -> i32 {
+    client_bytes_call(client, out, out_err, |client| {
+        let x = client.rt.block_on(client.client.export(str_arg(share_id, "share_id")?))?;
+        Ok(x.into_bytes())
+    })
+}
+
+/// out is json of ShareStatusMsg
+///
+/// # Safety
+/// client must be null or a live client, share_id null or nul terminated, out & out_err writable or null.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_client_share_status(client: *const TwopartyClient, share_id: *const c_char, out: *mut TwopartyBuffer, out_err: *mut TwopartyBuffer) -> i32 {
+    client_bytes_call(client, out, out_err, |client| {
+        let status = client.rt.block_on(client.client.share_status(str_arg(share_id, "share_id")?))?;
+        Ok(serde_json::to_vec(&status).unwrap())
+    })
+}
+
+/// share is json of SavedShare, out is the eth address of a secp256k1 share or the sui address of an ed25519 one,
+/// no connection is needed
+///
+/// # Safety
+/// share must point to share_len readable bytes, out & out_err writable or null.
+#[no_mangle]
+pub unsafe extern "C" fn twoparty_share_address(share: *const u8, share_len: usize, out: *mut TwopartyBuffer, out_err: *mut TwopartyBuffer) -> i32 {
+    if out.is_null() {
+        return write_err(out_err, invalid_arg("out"));
+    }
+    let result = guard(|| {
+        let saved_share = share_arg(share, share_len)?;
+        let address = share_address(&saved_share)
+            .map_err(|e| MpcError::new(MpcErrorKind::Unsupported, e))?;
+        Ok(address.into_bytes())
+    });
+    bytes_result(result, out, out_err)
+}
+
+// a panic must not unwind into the host
+fn guard<T, F>(f: F) -> Result<T, MpcError>
+    where F: FnOnce() -> Result<T, MpcError>
+{
+    catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(MpcError::new(MpcErrorKind::Internal, "panic in twoparty-client")))
+}
+
+fn invalid_arg(name: &str) -> MpcError {
+    MpcError::new(MpcErrorKind::InvalidRequest, format!("{} is null", name))
+}
+
+unsafe fn client_arg<'a>(client: *const TwopartyClient) -> Result<&'a TwopartyClient, MpcError> {
+    client.as_ref().ok_or_else(|| invalid_arg("client"))
+}
+
+unsafe fn bytes_arg<'a>(data: *const u8, len: usize, name: &str) -> Result<&'a [u8], MpcError> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if data.is_null() {
+        return Err(invalid_arg(name));
+    }
+    Ok(std::slice::from_raw_parts(data, len))
+}
+
+unsafe fn str_arg<'a>(s: *const c_char, name: &str) -> Result<&'a str, MpcError> {
+    if s.is_null() {
+        return Err(invalid_arg(name));
+    }
+    CStr::from_ptr(s).to_str()
+        .map_err(|_| MpcError::new(MpcErrorKind::InvalidRequest, format!("{} is not utf-8", name)))
+}
+
+unsafe fn share_arg(share: *const u8, share_len: usize) -> Result<SavedShare, MpcError> {
+    let share_bytes = bytes_arg(share, share_len, "share")?;
+    SavedShare::from_json(share_bytes)
+        .map_err(|e| MpcError::new(MpcErrorKind::MalformedMessage, format!("fail to parse share:{}", e)))
+}
+
+unsafe fn kms_arg(kms_key: *const u8, kms_key_len: usize) -> Result<LocalKms, MpcError> {
+    let key_bytes = bytes_arg(kms_key, kms_key_len, "kms_key")?;
+    LocalKms::from_slice(key_bytes).map_err(|e| MpcError::new(MpcErrorKind::InvalidRequest, e))
+}
+
+// out_err may be null if the caller does not want the message
+unsafe fn write_err(out_err: *mut TwopartyBuffer, err: MpcError) -> i32 {
+    if !out_err.is_null() {
+        *out_err = TwopartyBuffer::from_vec(err.message.into_bytes());
+    }
+    err.code() as i32
+}
+
+// client is checked before f runs
+unsafe fn client_unit_call<F>(client: *const TwopartyClient, out_err: *mut TwopartyBuffer, f: F) -> i32
+    where F: FnOnce(&TwopartyClient) -> Result<(), MpcError>
+{
+    let client = match client_arg(client) {
+        Ok(client) => client,
+        Err(err) => return write_err(out_err, err),
+    };
+    match guard(|| f(client)) {
+        Ok(()) => TWOPARTY_OK,
+        Err(err) => write_err(out_err, err),
+    }
+}
+
+// out & client are checked before f runs, a null out must not throw away a keygen or a rotate
+unsafe fn client_bytes_call<F>(client: *const TwopartyClient, out: *mut TwopartyBuffer, out_err: *mut TwopartyBuffer, f: F) -> i32
+    where F: FnOnce(&TwopartyClient) -> Result<Vec<u8>, MpcError>
+{
+    if out.is_null() {
+        return write_err(out_err, invalid_arg("out"));
+    }
+    let client = match client_arg(client) {
+        Ok(client) => client,
+        Err(err) => return write_err(out_err, err),
+    };
+    bytes_result(guard(|| f(client)), out, out_err)
+}
+
+// out is checked by the caller
+unsafe fn bytes_result(result: Result<Vec<u8>, MpcError>, out: *mut TwopartyBuffer, out_err: *mut TwopartyBuffer) -> i32 {
+    match result {
+        Ok(bytes) => {
+            *out = TwopartyBuffer::from_vec(bytes);
+            TWOPARTY_OK
+        }
+        Err(err) => write_err(out_err, err),
+    }
+}
@@ -0,0 +1,101 @@
+use std::ptr;
+use common::errors::MpcErrorKind;
+use common::socketmsg::types::{MPC_SCOPE_ED25519EDDSA, MPC_SCOPE_SECP256K1ECDSA, SAVED_SHARE_SCHEMA_VERSION, SavedShare};
+use crate::capi::*;
+
+fn empty_buffer() -> TwopartyBuffer {
+    TwopartyBuffer { data: ptr::null_mut(), len: 0 }
+}
+
+fn buffer_string(buffer: TwopartyBuffer) -> String {
+    let s = String::from_utf8(unsafe { std::slice::from_raw_parts(buffer.data, buffer.len) }.to_vec()).unwrap();
+    unsafe { twoparty_buffer_free(buffer) };
+    s
+}
+
+#[test]
+fn test_error_codes() {
+    let kinds = [
+        (MpcErrorKind::Internal, TWOPARTY_ERROR_INTERNAL),
+        (MpcErrorKind::MalformedMessage, TWOPARTY_ERROR_MALFORMED_MESSAGE),
+        (MpcErrorKind::ProofVerificationFailed, TWOPARTY_ERROR_PROOF_VERIFICATION_FAILED),
+        (MpcErrorKind::TransportTimeout, TWOPARTY_ERROR_TRANSPORT_TIMEOUT),
+        (MpcErrorKind::Disconnected, TWOPARTY_ERROR_DISCONNECTED),
+        (MpcErrorKind::ShareNotFound, TWOPARTY_ERROR_SHARE_NOT_FOUND),
+        (MpcErrorKind::PolicyDenied, TWOPARTY_ERROR_POLICY_DENIED),
+        (MpcErrorKind::VersionMismatch, TWOPARTY_ERROR_VERSION_MISMATCH),
+        (MpcErrorKind::Unauthorized, TWOPARTY_ERROR_UNAUTHORIZED),
+        (MpcErrorKind::Forbidden, TWOPARTY_ERROR_FORBIDDEN),
+        (MpcErrorKind::RateLimited, TWOPARTY_ERROR_RATE_LIMITED),
+        (MpcErrorKind::InvalidRequest, TWOPARTY_ERROR_INVALID_REQUEST),
+        (MpcErrorKind::NotFound, TWOPARTY_ERROR_NOT_FOUND),
+        (MpcErrorKind::Unsupported, TWOPARTY_ERROR_UNSUPPORTED),
+        (MpcErrorKind::Unavailable, TWOPARTY_ERROR_UNAVAILABLE),
+    ];
+    for (kind, code) in kinds {
+        assert_eq!(kind.code() as i32, code);
+    }
+    assert_eq!(TWOPARTY_SCOPE_SECP256K1ECDSA, MPC_SCOPE_SECP256K1ECDSA);
+    assert_eq!(TWOPARTY_SCOPE_ED25519EDDSA, MPC_SCOPE_ED25519EDDSA);
+}
+
+#[test]
+fn test_null_args() {
+    let mut err = empty_buffer();
+    let code = unsafe { twoparty_client_connect(ptr::null(), 0, ptr::null_mut(), &mut err) };
+    assert_eq!(code, TWOPARTY_ERROR_INVALID_REQUEST);
+    assert_eq!(buffer_string(err), "out_client is null");
+
+    let mut out = empty_buffer();
+    let mut err = empty_buffer();
+    let code = unsafe { twoparty_client_keygen(ptr::null(), TWOPARTY_SCOPE_SECP256K1ECDSA, &mut out, &mut err) };
+    assert_eq!(code, TWOPARTY_ERROR_INVALID_REQUEST);
+    assert!(out.data.is_null());
+    assert_eq!(buffer_string(err), "client is null");
+
+    // out is checked before anything runs
+    let mut err = empty_buffer();
+    let code = unsafe { twoparty_client_keygen(ptr::null(), TWOPARTY_SCOPE_SECP256K1ECDSA, ptr::null_mut(), &mut err) };
+    assert_eq!(code, TWOPARTY_ERROR_INVALID_REQUEST);
+    assert_eq!(buffer_string(err), "out is null");
+
+    // out_err is optional
+    let code = unsafe { twoparty_client_unload_share(ptr::null(), ptr::null(), ptr::null_mut()) };
+    assert_eq!(code, TWOPARTY_ERROR_INVALID_REQUEST);
+
+    unsafe {
+        twoparty_client_free(ptr::null_mut());
+        twoparty_buffer_free(empty_buffer());
+    }
+}
+
+#[test]
+fn test_share_address() {
+    let saved_share = SavedShare {
+        schema_version: SAVED_SHARE_SCHEMA_VERSION,
+        identity_id: "wangcy".to_string(),
+        share_id: "share".to_string(),
+        scope: MPC_SCOPE_SECP256K1ECDSA,
+        party: 1,
+        epoch: 0,
+        created_at: 0,
+        rotated_at: 0,
+        // public key of the private key 1
+        uncompressed_pub: hex::decode("0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8").unwrap(),
+        share_detail: vec![],
+    };
+    let share_json = serde_json::to_vec(&saved_share).unwrap();
+
+    let mut out = empty_buffer();
+    let mut err = empty_buffer();
+    let code = unsafe { twoparty_share_address(share_json.as_ptr(), share_json.len(), &mut out, &mut err) };
+    assert_eq!(code, TWOPARTY_OK);
+    assert!(err.data.is_null());
+    assert_eq!(buffer_string(out), "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf");
+
+    let mut out = empty_buffer();
+    let mut err = empty_buffer();
+    let code = unsafe { twoparty_share_address(b"{}".as_ptr(), 2, &mut out, &mut err) };
+    assert_eq!(code, TWOPARTY_ERROR_MALFORMED_MESSAGE);
+    assert!(!buffer_string(err).is_empty());
+}
@@ -5,22 +5,34 @@ use jni::JNIEnv;
 // These objects are what you should use as arguments to your native
 // function. They carry extra lifetime information to prevent them escaping
 // this context and getting used after being GC'd.
-use jni::objects::{JClass, JObjectArray, JString, JObject, JByteArray};
+use jni::objects::{JClass, JObjectArray, JString, JObject, JByteArray, JValue};
+use jni::sys::{jint, jlong};
 
 // This is just a pointer. We'll be returning it from our function. We
 // can't return one of the objects with lifetime information because the
 // lifetime checker won't let us.
 
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::sync::atomic::{AtomicI64, Ordering};
 use tokio::runtime::Runtime;
+use tokio::sync::oneshot;
+use common::errors::{MpcError, MpcErrorKind};
 use common::socketmsg::types::{MPC_SCOPE_ED25519EDDSA, MPC_SCOPE_SECP256K1ECDSA, SavedShare};
-use crate::mpc::ed25519::{ed25519_keygen, ed25519_rotate, ed25519_sign};
-use crate::mpc::secp256k1::{secp256k1_export, secp256k1_keygen, secp256k1_rotate, secp256k1_sign};
+use crate::kms::{Kms, LocalKms};
+use crate::mpc::{rotate_commit, share_status};
+use crate::mpc::client::{MpcClient, MpcClientConfig, MpcSignature};
+use crate::mpc::identity::{identity_bind, identity_recover};
+use common::payload::SignPayload;
+use crate::mpc::ed25519::{ed25519_keygen, ed25519_rotate, ed25519_sign, ed25519_sign_payload};
+use crate::websocket::{NoticeCallback, NoticeEvent, SyncClient};
+use crate::mpc::secp256k1::{secp256k1_export, secp256k1_keygen, secp256k1_rotate, secp256k1_sign, secp256k1_sign_payload};
 
 // #[cfg(target_os="android")]
 // This keeps Rust from "mangling" the name and making it unique for this crate.
 #[no_mangle]
 pub extern "system" fn Java_twoparty_mpc_NativeMpc_secp256k1Keygen<'local>
-(mut env: JNIEnv<'local>, _class: JClass<'local>, j_identity_id: JString, j_ws_url: JString) -> JObjectArray<'local> {
+(mut env: JNIEnv<'local>, _class: JClass<'local>, j_identity_id: JString, j_ws_url: JString, j_access_token: JString) -> JObjectArray<'local> {
     let identity_id: String = env
         .get_string(&j_identity_id)
         .expect("Couldn't get java string!")
@@ -30,10 +42,11 @@ pub extern "system" fn Java_twoparty_mpc_NativeMpc_secp256k1Keygen<'local>
         .get_string(&j_ws_url)
         .expect("Couldn't get java string!")
         .into();
+    let access_token = access_token_arg(&mut env, &j_access_token);
 
     let rt = get_runtime();
     let result = rt.block_on(async move {
-       secp256k1_keygen(identity_id, ws_url).await
+       secp256k1_keygen(identity_id, ws_url, access_token).await
     });
 
     return if let Ok(share) = result {
@@ -47,18 +60,51 @@ pub extern "system" fn Java_twoparty_mpc_NativeMpc_secp256k1Keygen<'local>
 
 #[no_mangle]
 pub extern "system" fn Java_twoparty_mpc_NativeMpc_secp256k1Sign<'local>
-(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_share: JByteArray, j_message_digest: JByteArray) -> JObjectArray<'local> {
+(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_access_token: JString, j_share: JByteArray, j_message_digest: JByteArray) -> JObjectArray<'local> {
     let ws_url: String = env
         .get_string(&j_ws_url)
         .expect("Couldn't get java string!")
         .into();
+    let access_token = access_token_arg(&mut env, &j_access_token);
     let share_bytes = env.convert_byte_array(&j_share).expect("fail to get java bytes");
     let message_digest = env.convert_byte_array(&j_message_digest).expect("fail to get java bytes");
 
     let rt = get_runtime();
     let result = rt.block_on(async move {
         let saved_share = parse_share(share_bytes, MPC_SCOPE_SECP256K1ECDSA)?;
-        secp256k1_sign(ws_url, &saved_share, message_digest).await
+        secp256k1_sign(ws_url, access_token, &saved_share, message_digest).await
+    });
+
+    return if let Ok(sig) = result {
+        let sig_bytes = serde_json::to_vec(&sig).unwrap();
+        fill_j_obj_arr(env, sig_bytes, None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_secp256k1SignPayload<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_access_token: JString, j_share: JByteArray, j_sign_payload: JByteArray) -> JObjectArray<'local> {
+    let ws_url: String = env
+        .get_string(&j_ws_url)
+        .expect("Couldn't get java string!")
+        .into();
+    let access_token = access_token_arg(&mut env, &j_access_token);
+    let share_bytes = env.convert_byte_array(&j_share).expect("fail to get java bytes");
+    let sign_payload_bytes = env.convert_byte_array(&j_sign_payload).expect("fail to get java bytes");
+
+    let rt = get_runtime();
+    let result = rt.block_on(async move {
+        let saved_share = parse_share(share_bytes, MPC_SCOPE_SECP256K1ECDSA)?;
+        let sign_payload_result = serde_json::from_slice::<SignPayload>(&sign_payload_bytes);
+        if sign_payload_result.is_err() {
+            let err = format!("fail to parse sign_payload:{}", sign_payload_result.err().unwrap());
+            return Err(MpcError::new(MpcErrorKind::MalformedMessage, err));
+        }
+        let sign_payload = sign_payload_result.unwrap();
+        secp256k1_sign_payload(ws_url, access_token, &saved_share, sign_payload).await
     });
 
     return if let Ok(sig) = result {
@@ -72,17 +118,18 @@ pub extern "system" fn Java_twoparty_mpc_NativeMpc_secp256k1Sign<'local>
 
 #[no_mangle]
 pub extern "system" fn Java_twoparty_mpc_NativeMpc_secp256k1Rotate<'local>
-(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_share: JByteArray) -> JObjectArray<'local> {
+(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_access_token: JString, j_share: JByteArray) -> JObjectArray<'local> {
     let ws_url: String = env
         .get_string(&j_ws_url)
         .expect("Couldn't get java string!")
         .into();
+    let access_token = access_token_arg(&mut env, &j_access_token);
     let share_bytes = env.convert_byte_array(&j_share).expect("fail to get java bytes");
 
     let rt = get_runtime();
     let result = rt.block_on(async move {
         let saved_share = parse_share(share_bytes, MPC_SCOPE_SECP256K1ECDSA)?;
-        secp256k1_rotate(ws_url, &saved_share).await
+        secp256k1_rotate(ws_url, access_token, &saved_share).await
     });
 
     return if let Ok(new_share) = result {
@@ -96,17 +143,18 @@ pub extern "system" fn Java_twoparty_mpc_NativeMpc_secp256k1Rotate<'local>
 
 #[no_mangle]
 pub extern "system" fn Java_twoparty_mpc_NativeMpc_secp256k1Export<'local>
-(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_share: JByteArray) -> JObjectArray<'local> {
+(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_access_token: JString, j_share: JByteArray) -> JObjectArray<'local> {
     let ws_url: String = env
         .get_string(&j_ws_url)
         .expect("Couldn't get java string!")
         .into();
+    let access_token = access_token_arg(&mut env, &j_access_token);
     let share_bytes = env.convert_byte_array(&j_share).expect("fail to get java bytes");
 
     let rt = get_runtime();
     let result = rt.block_on(async move {
         let saved_share = parse_share(share_bytes, MPC_SCOPE_SECP256K1ECDSA)?;
-        secp256k1_export(ws_url, &saved_share).await
+        secp256k1_export(ws_url, access_token, &saved_share).await
     });
 
     return if let Ok(x) = result {
@@ -119,7 +167,7 @@ pub extern "system" fn Java_twoparty_mpc_NativeMpc_secp256k1Export<'local>
 
 #[no_mangle]
 pub extern "system" fn Java_twoparty_mpc_NativeMpc_ed25519Keygen<'local>
-(mut env: JNIEnv<'local>, _class: JClass, j_identity_id: JString, j_ws_url: JString) -> JObjectArray<'local> {
+(mut env: JNIEnv<'local>, _class: JClass, j_identity_id: JString, j_ws_url: JString, j_access_token: JString) -> JObjectArray<'local> {
     let identity_id: String = env
         .get_string(&j_identity_id)
         .expect("Couldn't get java string!")
@@ -129,10 +177,11 @@ pub extern "system" fn Java_twoparty_mpc_NativeMpc_ed25519Keygen<'local>
         .get_string(&j_ws_url)
         .expect("Couldn't get java string!")
         .into();
+    let access_token = access_token_arg(&mut env, &j_access_token);
 
     let rt = get_runtime();
     let result = rt.block_on(async move {
-        ed25519_keygen(identity_id, ws_url).await
+        ed25519_keygen(identity_id, ws_url, access_token).await
     });
 
     return if let Ok(share) = result {
@@ -147,18 +196,50 @@ pub extern "system" fn Java_twoparty_mpc_NativeMpc_ed25519Keygen<'local>
 
 #[no_mangle]
 pub extern "system" fn Java_twoparty_mpc_NativeMpc_ed25519Sign<'local>
-(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_share: JByteArray, j_message_digest: JByteArray) -> JObjectArray<'local> {
+(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_access_token: JString, j_share: JByteArray, j_message_digest: JByteArray) -> JObjectArray<'local> {
     let ws_url: String = env
         .get_string(&j_ws_url)
         .expect("Couldn't get java string!")
         .into();
+    let access_token = access_token_arg(&mut env, &j_access_token);
     let share_bytes = env.convert_byte_array(&j_share).expect("fail to get java bytes");
     let message_digest = env.convert_byte_array(&j_message_digest).expect("fail to get java bytes");
 
     let rt = get_runtime();
     let result = rt.block_on(async move {
         let saved_share = parse_share(share_bytes, MPC_SCOPE_ED25519EDDSA)?;
-        ed25519_sign(ws_url, &saved_share, message_digest).await
+        ed25519_sign(ws_url, access_token, &saved_share, message_digest).await
+    });
+
+    return if let Ok(sig) = result {
+        fill_j_obj_arr(env, sig, None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_ed25519SignPayload<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_access_token: JString, j_share: JByteArray, j_sign_payload: JByteArray) -> JObjectArray<'local> {
+    let ws_url: String = env
+        .get_string(&j_ws_url)
+        .expect("Couldn't get java string!")
+        .into();
+    let access_token = access_token_arg(&mut env, &j_access_token);
+    let share_bytes = env.convert_byte_array(&j_share).expect("fail to get java bytes");
+    let sign_payload_bytes = env.convert_byte_array(&j_sign_payload).expect("fail to get java bytes");
+
+    let rt = get_runtime();
+    let result = rt.block_on(async move {
+        let saved_share = parse_share(share_bytes, MPC_SCOPE_ED25519EDDSA)?;
+        let sign_payload_result = serde_json::from_slice::<SignPayload>(&sign_payload_bytes);
+        if sign_payload_result.is_err() {
+            let err = format!("fail to parse sign_payload:{}", sign_payload_result.err().unwrap());
+            return Err(MpcError::new(MpcErrorKind::MalformedMessage, err));
+        }
+        let sign_payload = sign_payload_result.unwrap();
+        ed25519_sign_payload(ws_url, access_token, &saved_share, sign_payload).await
     });
 
     return if let Ok(sig) = result {
@@ -171,17 +252,18 @@ pub extern "system" fn Java_twoparty_mpc_NativeMpc_ed25519Sign<'local>
 
 #[no_mangle]
 pub extern "system" fn Java_twoparty_mpc_NativeMpc_ed25519Rotate<'local>
-(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_share: JByteArray) -> JObjectArray<'local> {
+(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_access_token: JString, j_share: JByteArray) -> JObjectArray<'local> {
     let ws_url: String = env
         .get_string(&j_ws_url)
         .expect("Couldn't get java string!")
         .into();
+    let access_token = access_token_arg(&mut env, &j_access_token);
     let share_bytes = env.convert_byte_array(&j_share).expect("fail to get java bytes");
 
     let rt = get_runtime();
     let result = rt.block_on(async move {
         let saved_share = parse_share(share_bytes, MPC_SCOPE_ED25519EDDSA)?;
-        ed25519_rotate(ws_url, &saved_share).await
+        ed25519_rotate(ws_url, access_token, &saved_share).await
     });
 
     return if let Ok(new_share) = result {
@@ -193,10 +275,135 @@ pub extern "system" fn Java_twoparty_mpc_NativeMpc_ed25519Rotate<'local>
     };
 }
 
-fn fill_j_obj_arr(mut env: JNIEnv, data: Vec<u8>, option_err: Option<String>) -> JObjectArray {
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_rotateCommit<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_access_token: JString, j_old_share: JByteArray, j_new_share: JByteArray) -> JObjectArray<'local> {
+    let ws_url: String = env
+        .get_string(&j_ws_url)
+        .expect("Couldn't get java string!")
+        .into();
+    let access_token = access_token_arg(&mut env, &j_access_token);
+    let old_share_bytes = env.convert_byte_array(&j_old_share).expect("fail to get java bytes");
+    let new_share_bytes = env.convert_byte_array(&j_new_share).expect("fail to get java bytes");
+
+    let rt = get_runtime();
+    let result = rt.block_on(async move {
+        let old_share: SavedShare = parse_share_any_scope(old_share_bytes)?;
+        let new_share = parse_share(new_share_bytes, old_share.scope)?;
+        rotate_commit(ws_url, access_token, &old_share, &new_share).await
+    });
+
+    return if result.is_ok() {
+        fill_j_obj_arr(env, vec![], None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_shareStatus<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_access_token: JString, j_share: JByteArray) -> JObjectArray<'local> {
+    let ws_url: String = env
+        .get_string(&j_ws_url)
+        .expect("Couldn't get java string!")
+        .into();
+    let access_token = access_token_arg(&mut env, &j_access_token);
+    let share_bytes = env.convert_byte_array(&j_share).expect("fail to get java bytes");
+
+    let rt = get_runtime();
+    let result = rt.block_on(async move {
+        let saved_share = parse_share_any_scope(share_bytes)?;
+        share_status(ws_url, access_token, &saved_share).await
+    });
+
+    return if let Ok(status) = result {
+        let status_bytes = serde_json::to_vec(&status).unwrap();
+        fill_j_obj_arr(env, status_bytes, None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+// kms_key is the 32 bytes aes-256-gcm key of LocalKms
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_identityBind<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_ws_url: JString, j_access_token: JString, j_share: JByteArray, j_kms_key: JByteArray) -> JObjectArray<'local> {
+    let ws_url: String = env
+        .get_string(&j_ws_url)
+        .expect("Couldn't get java string!")
+        .into();
+    let access_token = access_token_arg(&mut env, &j_access_token);
+    let share_bytes = env.convert_byte_array(&j_share).expect("fail to get java bytes");
+    let kms_key = env.convert_byte_array(&j_kms_key).expect("fail to get java bytes");
+
+    let rt = get_runtime();
+    let result = rt.block_on(async move {
+        let saved_share = parse_share_any_scope(share_bytes)?;
+        let kms = LocalKms::from_slice(&kms_key)?;
+        identity_bind(ws_url, access_token, &saved_share, &kms).await
+    });
+
+    return if result.is_ok() {
+        fill_j_obj_arr(env, vec![], None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+// return the json array of recovered shares
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_identityRecover<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_identity_id: JString, j_ws_url: JString, j_access_token: JString, j_kms_key: JByteArray) -> JObjectArray<'local> {
+    let identity_id: String = env
+        .get_string(&j_identity_id)
+        .expect("Couldn't get java string!")
+        .into();
+    let ws_url: String = env
+        .get_string(&j_ws_url)
+        .expect("Couldn't get java string!")
+        .into();
+    let access_token = access_token_arg(&mut env, &j_access_token);
+    let kms_key = env.convert_byte_array(&j_kms_key).expect("fail to get java bytes");
+
+    let rt = get_runtime();
+    let result = rt.block_on(async move {
+        let kms = LocalKms::from_slice(&kms_key)?;
+        identity_recover(identity_id, ws_url, access_token, &kms).await
+    });
+
+    return if let Ok(shares) = result {
+        let shares_bytes = serde_json::to_vec(&shares).unwrap();
+        fill_j_obj_arr(env, shares_bytes, None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+// a null or empty access_token connects without authentication
+fn access_token_arg(env: &mut JNIEnv, j_access_token: &JString) -> Option<String> {
+    if j_access_token.is_null() {
+        return None;
+    }
+    let access_token: String = env
+        .get_string(j_access_token)
+        .expect("Couldn't get java string!")
+        .into();
+    if access_token.is_empty() {
+        return None;
+    }
+    Some(access_token)
+}
+
+// [data] on success, [data, error message, error code] on failure,
+// the code is the decimal MpcErrorKind code, see MpcTypes.ERROR_*
+fn fill_j_obj_arr(mut env: JNIEnv, data: Vec<u8>, option_err: Option<MpcError>) -> JObjectArray {
     let mut array_length = 1;
     if option_err.is_some() {
-        array_length = 2;
+        array_length = 3;
     }
     let array_class = env.find_class("[B").expect("Failed to find byte array class");
     let result = env.new_object_array(array_length, array_class, JObject::null()).expect("Failed to create jobjectArray");
@@ -205,8 +412,10 @@ fn fill_j_obj_arr(mut env: JNIEnv, data: Vec<u8>, option_err: Option<String>) ->
     env.set_object_array_element(&result, 0, data_array).expect("Failed to set object array element");
 
     if let Some(err) = option_err {
-        let err_array = env.byte_array_from_slice(err.as_bytes()).expect("Failed to create err_array");
+        let err_array = env.byte_array_from_slice(err.message.as_bytes()).expect("Failed to create err_array");
         env.set_object_array_element(&result, 1, err_array).expect("Failed to set object array element");
+        let code_array = env.byte_array_from_slice(err.code().to_string().as_bytes()).expect("Failed to create code_array");
+        env.set_object_array_element(&result, 2, code_array).expect("Failed to set object array element");
     }
 
     result
@@ -219,14 +428,362 @@ fn get_runtime() -> Runtime {
     rt
 }
 
-fn parse_share(share_bytes: Vec<u8>, expected_scope: u8) -> Result<SavedShare, String> {
-    let saved_share_result = serde_json::from_slice(&share_bytes);
-    if saved_share_result.is_err() {
-        return Err(format!("fail to parse share:{}", saved_share_result.err().unwrap().to_string()));
-    }
-    let saved_share: SavedShare = saved_share_result.unwrap();
+fn parse_share(share_bytes: Vec<u8>, expected_scope: u8) -> Result<SavedShare, MpcError> {
+    let saved_share = parse_share_any_scope(share_bytes)?;
     if &saved_share.scope != &expected_scope {
-        return Err("share scope not match".to_string());
+        return Err(MpcError::new(MpcErrorKind::InvalidRequest, "share scope not match"));
     }
     Ok(saved_share)
-}
\ No newline at end of file
+}
+
+// legacy share json is migrated to the current schema
+fn parse_share_any_scope(share_bytes: Vec<u8>) -> Result<SavedShare, MpcError> {
+    let saved_share_result = SavedShare::from_json(&share_bytes);
+    if saved_share_result.is_err() {
+        let err = format!("fail to parse share:{}", saved_share_result.err().unwrap());
+        return Err(MpcError::new(MpcErrorKind::MalformedMessage, err));
+    }
+    Ok(saved_share_result.unwrap())
+}
+#[allow(clippy::type_complexity)]
+static NOTICE_SUBSCRIPTIONS: LazyLock<Mutex<HashMap<i64, oneshot::Sender<()>>>> = LazyLock::new(|| {
+    Mutex::new(HashMap::new())
+});
+static NEXT_SUBSCRIPTION: AtomicI64 = AtomicI64::new(1);
+
+// listener implements NativeMpc.NoticeListener, its onNotice receives the json of NoticeEvent on a native thread.
+// return the subscription handle as json, the connection is kept until unsubscribeNotice
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_subscribeNotice<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_identity_id: JString, j_ws_url: JString, j_access_token: JString, j_listener: JObject) -> JObjectArray<'local> {
+    let identity_id: String = env
+        .get_string(&j_identity_id)
+        .expect("Couldn't get java string!")
+        .into();
+    let ws_url: String = env
+        .get_string(&j_ws_url)
+        .expect("Couldn't get java string!")
+        .into();
+    let access_token = access_token_arg(&mut env, &j_access_token);
+    let vm = env.get_java_vm().expect("fail to get java vm");
+    let listener = env.new_global_ref(j_listener).expect("fail to create global ref");
+
+    let (stop_tx, stop_rx) = oneshot::channel::<()>();
+    let (result_tx, result_rx) = std::sync::mpsc::channel::<Result<(), MpcError>>();
+    std::thread::spawn(move || {
+        let rt = get_runtime();
+        rt.block_on(async move {
+            let connect_result = SyncClient::connect_server_with_token(identity_id, ws_url, 10, access_token).await;
+            if connect_result.is_err() {
+                result_tx.send(Err(connect_result.err().unwrap())).unwrap_or(());
+                return;
+            }
+            let sync_client = connect_result.unwrap();
+            let callback: NoticeCallback = Arc::new(move |event: NoticeEvent| {
+                // the runtime runs on this thread only, attach it once
+                let env_result = vm.attach_current_thread_permanently();
+                if env_result.is_err() {
+                    println!("fail to attach notice thread:{}", env_result.err().unwrap());
+                    return;
+                }
+                let mut env = env_result.unwrap();
+                let event_bytes = serde_json::to_vec(&event).unwrap();
+                let j_event = JObject::from(env.byte_array_from_slice(&event_bytes).expect("Failed to create event_array"));
+                let call_result = env.call_method(listener.as_obj(), "onNotice", "([B)V", &[JValue::Object(&j_event)]);
+                if call_result.is_err() {
+                    // an exception thrown by the listener must not be left pending
+                    env.exception_clear().unwrap_or(());
+                }
+            });
+            let subscribe_result = sync_client.subscribe_notice(callback).await;
+            let subscribed = subscribe_result.is_ok();
+            result_tx.send(subscribe_result).unwrap_or(());
+            if subscribed {
+                stop_rx.await.unwrap_or(());
+            }
+        });
+    });
+
+    let result = result_rx.recv().unwrap_or(Err(MpcError::new(MpcErrorKind::Internal, "notice thread exited")));
+    return if result.is_ok() {
+        let handle = NEXT_SUBSCRIPTION.fetch_add(1, Ordering::SeqCst);
+        NOTICE_SUBSCRIPTIONS.lock().unwrap().insert(handle, stop_tx);
+        fill_j_obj_arr(env, serde_json::to_vec(&handle).unwrap(), None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_unsubscribeNotice<'local>
+(env: JNIEnv<'local>, _class: JClass, j_handle: jlong) -> JObjectArray<'local> {
+    let option_stop_tx = NOTICE_SUBSCRIPTIONS.lock().unwrap().remove(&j_handle);
+    return if let Some(stop_tx) = option_stop_tx {
+        // the notice thread drops the connection & returns
+        stop_tx.send(()).unwrap_or(());
+        fill_j_obj_arr(env, vec![], None)
+    } else {
+        fill_j_obj_arr(env, vec![], Some(MpcError::new(MpcErrorKind::NotFound, "subscription not found")))
+    };
+}
+
+// the runtime keeps the connection & heartbeat of the client running between calls
+struct MpcClientHandle {
+    rt: Runtime,
+    client: MpcClient,
+}
+
+#[allow(clippy::type_complexity)]
+static MPC_CLIENTS: LazyLock<Mutex<HashMap<i64, Arc<MpcClientHandle>>>> = LazyLock::new(|| {
+    Mutex::new(HashMap::new())
+});
+static NEXT_MPC_CLIENT: AtomicI64 = AtomicI64::new(1);
+
+fn get_mpc_client(handle: i64) -> Result<Arc<MpcClientHandle>, MpcError> {
+    let option_client = MPC_CLIENTS.lock().unwrap().get(&handle).cloned();
+    option_client.ok_or_else(|| MpcError::new(MpcErrorKind::NotFound, "mpc client not found"))
+}
+
+// secp256k1 signatures are json of Secp256k1Sig, ed25519 ones are the 64 raw bytes
+pub(crate) fn signature_bytes(sig: MpcSignature) -> Vec<u8> {
+    match sig {
+        MpcSignature::Secp256k1(sig) => serde_json::to_vec(&sig).unwrap(),
+        MpcSignature::Ed25519(sig) => sig,
+    }
+}
+
+// config is json of MpcClientConfig, return the json of the client handle, the connection is kept until mpcClientClose
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_mpcClientConnect<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_config: JByteArray) -> JObjectArray<'local> {
+    let config_bytes = env.convert_byte_array(&j_config).expect("fail to get java bytes");
+    let config_result = serde_json::from_slice::<MpcClientConfig>(&config_bytes);
+    if config_result.is_err() {
+        let err = format!("fail to parse config:{}", config_result.err().unwrap());
+        return fill_j_obj_arr(env, vec![], Some(MpcError::new(MpcErrorKind::MalformedMessage, err)));
+    }
+    let config = config_result.unwrap();
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build().unwrap();
+    let result = rt.block_on(MpcClient::connect(config));
+
+    return if let Ok(client) = result {
+        let handle = NEXT_MPC_CLIENT.fetch_add(1, Ordering::SeqCst);
+        MPC_CLIENTS.lock().unwrap().insert(handle, Arc::new(MpcClientHandle { rt, client }));
+        fill_j_obj_arr(env, serde_json::to_vec(&handle).unwrap(), None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_mpcClientClose<'local>
+(env: JNIEnv<'local>, _class: JClass, j_handle: jlong) -> JObjectArray<'local> {
+    let option_client = MPC_CLIENTS.lock().unwrap().remove(&j_handle);
+    return if option_client.is_some() {
+        // the connection & runtime are dropped once running calls return
+        fill_j_obj_arr(env, vec![], None)
+    } else {
+        fill_j_obj_arr(env, vec![], Some(MpcError::new(MpcErrorKind::NotFound, "mpc client not found")))
+    };
+}
+
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_mpcClientLoadShare<'local>
+(env: JNIEnv<'local>, _class: JClass, j_handle: jlong, j_share: JByteArray) -> JObjectArray<'local> {
+    let share_bytes = env.convert_byte_array(&j_share).expect("fail to get java bytes");
+
+    let result = get_mpc_client(j_handle).and_then(|handle| {
+        let saved_share = parse_share_any_scope(share_bytes)?;
+        handle.client.load_share(saved_share)
+    });
+
+    return if result.is_ok() {
+        fill_j_obj_arr(env, vec![], None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_mpcClientUnloadShare<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_handle: jlong, j_share_id: JString) -> JObjectArray<'local> {
+    let share_id: String = env
+        .get_string(&j_share_id)
+        .expect("Couldn't get java string!")
+        .into();
+
+    let result = get_mpc_client(j_handle).map(|handle| {
+        handle.client.unload_share(&share_id);
+    });
+
+    return if result.is_ok() {
+        fill_j_obj_arr(env, vec![], None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+// the new share is loaded
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_mpcClientKeygen<'local>
+(env: JNIEnv<'local>, _class: JClass, j_handle: jlong, j_scope: jint) -> JObjectArray<'local> {
+    let result = get_mpc_client(j_handle).and_then(|handle| {
+        handle.rt.block_on(handle.client.keygen(j_scope as u8))
+    });
+
+    return if let Ok(share) = result {
+        let share_bytes = serde_json::to_vec(&share).unwrap();
+        fill_j_obj_arr(env, share_bytes, None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_mpcClientSign<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_handle: jlong, j_share_id: JString, j_message_digest: JByteArray) -> JObjectArray<'local> {
+    let share_id: String = env
+        .get_string(&j_share_id)
+        .expect("Couldn't get java string!")
+        .into();
+    let message_digest = env.convert_byte_array(&j_message_digest).expect("fail to get java bytes");
+
+    let result = get_mpc_client(j_handle).and_then(|handle| {
+        handle.rt.block_on(handle.client.sign(&share_id, message_digest))
+    });
+
+    return if let Ok(sig) = result {
+        fill_j_obj_arr(env, signature_bytes(sig), None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_mpcClientSignPayload<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_handle: jlong, j_share_id: JString, j_sign_payload: JByteArray) -> JObjectArray<'local> {
+    let share_id: String = env
+        .get_string(&j_share_id)
+        .expect("Couldn't get java string!")
+        .into();
+    let sign_payload_bytes = env.convert_byte_array(&j_sign_payload).expect("fail to get java bytes");
+
+    let result = get_mpc_client(j_handle).and_then(|handle| {
+        let sign_payload_result = serde_json::from_slice::<SignPayload>(&sign_payload_bytes);
+        if sign_payload_result.is_err() {
+            let err = format!("fail to parse sign_payload:{}", sign_payload_result.err().unwrap());
+            return Err(MpcError::new(MpcErrorKind::MalformedMessage, err));
+        }
+        let sign_payload = sign_payload_result.unwrap();
+        handle.rt.block_on(handle.client.sign_payload(&share_id, sign_payload))
+    });
+
+    return if let Ok(sig) = result {
+        fill_j_obj_arr(env, signature_bytes(sig), None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+// the new share stays pending at server until mpcClientRotateCommit, persist it before commit
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_mpcClientRotate<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_handle: jlong, j_share_id: JString) -> JObjectArray<'local> {
+    let share_id: String = env
+        .get_string(&j_share_id)
+        .expect("Couldn't get java string!")
+        .into();
+
+    let result = get_mpc_client(j_handle).and_then(|handle| {
+        handle.rt.block_on(handle.client.rotate(&share_id))
+    });
+
+    return if let Ok(new_share) = result {
+        let new_share_bytes = serde_json::to_vec(&new_share).unwrap();
+        fill_j_obj_arr(env, new_share_bytes, None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_mpcClientRotateCommit<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_handle: jlong, j_share_id: JString, j_new_share: JByteArray, j_kms_key: JByteArray) -> JObjectArray<'local> {
+    let share_id: String = env
+        .get_string(&j_share_id)
+        .expect("Couldn't get java string!")
+        .into();
+    let new_share_bytes = env.convert_byte_array(&j_new_share).expect("fail to get java bytes");
+    // null does not bind the new share
+    let option_kms_key = if j_kms_key.is_null() {
+        None
+    } else {
+        Some(env.convert_byte_array(&j_kms_key).expect("fail to get java bytes"))
+    };
+
+    let result = get_mpc_client(j_handle).and_then(|handle| {
+        let new_share = parse_share_any_scope(new_share_bytes)?;
+        let option_kms = option_kms_key.map(|kms_key| LocalKms::from_slice(&kms_key)).transpose()?;
+        handle.rt.block_on(handle.client.rotate_commit(&share_id, new_share, option_kms.as_ref().map(|kms| kms as &dyn Kms)))
+    });
+
+    return if result.is_ok() {
+        fill_j_obj_arr(env, vec![], None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_mpcClientExport<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_handle: jlong, j_share_id: JString) -> JObjectArray<'local> {
+    let share_id: String = env
+        .get_string(&j_share_id)
+        .expect("Couldn't get java string!")
+        .into();
+
+    let result = get_mpc_client(j_handle).and_then(|handle| {
+        handle.rt.block_on(handle.client.export(&share_id))
+    });
+
+    return if let Ok(x) = result {
+        fill_j_obj_arr(env, x.into_bytes(), None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
+
+#[no_mangle]
+pub extern "system" fn Java_twoparty_mpc_NativeMpc_mpcClientShareStatus<'local>
+(mut env: JNIEnv<'local>, _class: JClass, j_handle: jlong, j_share_id: JString) -> JObjectArray<'local> {
+    let share_id: String = env
+        .get_string(&j_share_id)
+        .expect("Couldn't get java string!")
+        .into();
+
+    let result = get_mpc_client(j_handle).and_then(|handle| {
+        handle.rt.block_on(handle.client.share_status(&share_id))
+    });
+
+    return if let Ok(status) = result {
+        let status_bytes = serde_json::to_vec(&status).unwrap();
+        fill_j_obj_arr(env, status_bytes, None)
+    } else {
+        let err = result.err().unwrap();
+        fill_j_obj_arr(env, vec![], Some(err))
+    };
+}
@@ -0,0 +1,81 @@
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+
+// the key management service which encrypts identity-secret & share before uploading them to mpc-server,
+// e.g. aws kms in production, LocalKms for tests & devices with a local key store
+pub trait Kms: Send + Sync {
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String>;
+
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+const NONCE_LEN: usize = 12;
+
+// software kms with an aes-256-gcm key, ciphertext= nonce || aes_gcm(plaintext)
+pub struct LocalKms {
+    key: [u8; 32],
+}
+
+impl LocalKms {
+    pub fn new(key: [u8; 32]) -> Self {
+        LocalKms { key }
+    }
+
+    pub fn from_slice(key: &[u8]) -> Result<Self, String> {
+        if key.len() != 32 {
+            return Err(format!("local kms key length should be 32, got {}", key.len()));
+        }
+        let mut key_bytes = [0u8; 32];
+        key_bytes.copy_from_slice(key);
+        Ok(LocalKms::new(key_bytes))
+    }
+
+    pub fn generate() -> Self {
+        let key = Aes256Gcm::generate_key(&mut OsRng);
+        LocalKms::from_slice(key.as_slice()).unwrap()
+    }
+}
+
+impl Kms for LocalKms {
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key));
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let encrypted_result = cipher.encrypt(&nonce, plaintext);
+        if encrypted_result.is_err() {
+            return Err(format!("local kms encrypt fail: {}", encrypted_result.err().unwrap()));
+        }
+
+        let mut ciphertext = nonce.to_vec();
+        ciphertext.extend_from_slice(&encrypted_result.unwrap());
+        Ok(ciphertext)
+    }
+
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+        if ciphertext.len() < NONCE_LEN {
+            return Err("local kms ciphertext too short".to_string());
+        }
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key));
+        let nonce = Nonce::from_slice(&ciphertext[..NONCE_LEN]);
+        let decrypted_result = cipher.decrypt(nonce, &ciphertext[NONCE_LEN..]);
+        if decrypted_result.is_err() {
+            return Err(format!("local kms decrypt fail: {}", decrypted_result.err().unwrap()));
+        }
+        Ok(decrypted_result.unwrap())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::kms::{Kms, LocalKms};
+
+    #[test]
+    fn test_local_kms() {
+        let kms = LocalKms::generate();
+        let ciphertext = kms.encrypt(b"identity-secret").unwrap();
+        assert_eq!(kms.decrypt(&ciphertext).unwrap(), b"identity-secret".to_vec());
+
+        // another key can not decrypt
+        let other_kms = LocalKms::generate();
+        assert!(other_kms.decrypt(&ciphertext).is_err());
+    }
+}
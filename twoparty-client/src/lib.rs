@@ -2,8 +2,16 @@
 #![allow(non_snake_case)]
 
 
+#[cfg(feature = "native")]
 pub mod websocket;
+#[cfg(feature = "native")]
 pub mod ffi;
 pub mod mpc;
+pub mod kms;
+#[cfg(feature = "native")]
+pub mod capi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-
+#[cfg(all(feature = "native", feature = "wasm"))]
+compile_error!("features native and wasm select different curv backends, enable only one of them");
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use common::errors::{MpcError, MpcErrorKind};
+use common::payload::SignPayload;
+use common::socketmsg::types::{MPC_EXPORT, MPC_KEYGEN, MPC_ROTATE, MPC_ROTATE_COMMIT, MPC_SCOPE_ED25519EDDSA, MPC_SCOPE_SECP256K1ECDSA, MPC_SHARE_STATUS, MPC_SIGN, SavedShare, ShareStatusMsg};
+use crate::kms::Kms;
+use crate::mpc::secp256k1::Secp256k1Sig;
+use crate::mpc::Party1Client;
+use crate::websocket::{ClientOptions, SyncClient};
+use crate::websocket::discovery::ServerProfile;
+
+// deserialized from json by the bindings, the fields of ClientOptions are flattened, missing ones take defaults
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MpcClientConfig {
+    pub url: String,
+    pub identity_id: String,
+    #[serde(default)]
+    pub access_token: Option<String>,
+    // fetch /ws-config & /capabilities before connecting
+    #[serde(default = "default_auto_config")]
+    pub auto_config: bool,
+    // overrides the ws_client_interval of the server
+    #[serde(default)]
+    pub heartbeat_sec: Option<u8>,
+    #[serde(flatten)]
+    pub options: ClientOptions,
+}
+
+// used when neither heartbeat_sec nor the server gives one
+const DEFAULT_HEARTBEAT_SEC: u8 = 10;
+
+fn default_auto_config() -> bool {
+    true
+}
+
+impl MpcClientConfig {
+    pub fn new(url: String, identity_id: String) -> Self {
+        MpcClientConfig {
+            url,
+            identity_id,
+            access_token: None,
+            auto_config: true,
+            heartbeat_sec: None,
+            options: ClientOptions::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum MpcSignature {
+    Secp256k1(Secp256k1Sig),
+    // R || s, 64 bytes
+    Ed25519(Vec<u8>),
+}
+
+// one connection to party2 with the loaded shares of an identity, kept across operations.
+// party2 runs one mpc session per connection, so operations of a client run one at a time
+pub struct MpcClient {
+    config: MpcClientConfig,
+    party1: Party1Client<SyncClient>,
+    // none if auto_config is off, every operation is then sent to the server
+    profile: Option<ServerProfile>,
+    // share_id -> share
+    shares: RwLock<HashMap<String, SavedShare>>,
+    session_lock: Mutex<()>,
+}
+
+impl MpcClient {
+    pub async fn connect(config: MpcClientConfig) -> Result<Self, MpcError> {
+        let mut profile = None;
+        if config.auto_config {
+            let fetch_result = ServerProfile::fetch(&config.url).await;
+            profile = Some(fetch_result.map_err(|e| MpcError::new(MpcErrorKind::Unavailable, e))?);
+        }
+        let heartbeat_sec = config.heartbeat_sec
+            .or(profile.as_ref().map(|p| p.heartbeat_sec()))
+            .unwrap_or(DEFAULT_HEARTBEAT_SEC);
+        let sync_client = SyncClient::connect_server_with_options(
+            config.identity_id.clone(),
+            config.url.clone(),
+            heartbeat_sec,
+            config.access_token.clone(),
+            config.options.clone(),
+        ).await?;
+        Ok(MpcClient {
+            config,
+            party1: Party1Client::new(sync_client),
+            profile,
+            shares: RwLock::new(HashMap::new()),
+            session_lock: Mutex::new(()),
+        })
+    }
+
+    pub fn config(&self) -> &MpcClientConfig {
+        &self.config
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.party1.transport.is_connected()
+    }
+
+    pub fn profile(&self) -> Option<&ServerProfile> {
+        self.profile.as_ref()
+    }
+
+    // refuse an operation the server does not advertise before any step is sent
+    fn check_command(&self, scope: u8, command: u8) -> Result<(), MpcError> {
+        if let Some(profile) = &self.profile {
+            return profile.check_command(scope, command).map_err(|e| MpcError::new(MpcErrorKind::Unsupported, e));
+        }
+        Ok(())
+    }
+
+    // only party1 shares of the identity of the client can be loaded
+    pub fn load_share(&self, saved_share: SavedShare) -> Result<(), MpcError> {
+        if saved_share.identity_id != self.config.identity_id {
+            let err = format!("share of identity {} can not be loaded by {}", saved_share.identity_id, self.config.identity_id);
+            return Err(MpcError::new(MpcErrorKind::Forbidden, err));
+        }
+        if saved_share.party != 1 {
+            let err = format!("share of party{} can not be loaded", saved_share.party);
+            return Err(MpcError::new(MpcErrorKind::InvalidRequest, err));
+        }
+        self.shares.write().unwrap().insert(saved_share.share_id.clone(), saved_share);
+        Ok(())
+    }
+
+    pub fn unload_share(&self, share_id: &str) -> Option<SavedShare> {
+        self.shares.write().unwrap().remove(share_id)
+    }
+
+    pub fn share(&self, share_id: &str) -> Result<SavedShare, MpcError> {
+        let option_share = self.shares.read().unwrap().get(share_id).cloned();
+        option_share.ok_or_else(|| MpcError::new(MpcErrorKind::ShareNotFound, format!("share not loaded, share_id={}", share_id)))
+    }
+
+    pub fn share_ids(&self) -> Vec<String> {
+        self.shares.read().unwrap().keys().cloned().collect()
+    }
+
+    // the new share is loaded, persist it as well
+    pub async fn keygen(&self, scope: u8) -> Result<SavedShare, MpcError> {
+        self.check_command(scope, MPC_KEYGEN)?;
+        let _session = self.session_lock.lock().await;
+        let saved_share = match scope {
+            MPC_SCOPE_SECP256K1ECDSA => self.party1.secp256k1_keygen().await?,
+            MPC_SCOPE_ED25519EDDSA => self.party1.ed25519_keygen().await?,
+            _ => return Err(unsupported_scope(scope)),
+        };
+        self.load_share(saved_share.clone())?;
+        Ok(saved_share)
+    }
+
+    pub async fn sign(&self, share_id: &str, message_digest: Vec<u8>) -> Result<MpcSignature, MpcError> {
+        let saved_share = self.share(share_id)?;
+        self.check_command(saved_share.scope, MPC_SIGN)?;
+        let _session = self.session_lock.lock().await;
+        match saved_share.scope {
+            MPC_SCOPE_SECP256K1ECDSA => {
+                let sig = self.party1.secp256k1_sign(&saved_share, message_digest).await?;
+                Ok(MpcSignature::Secp256k1(sig))
+            }
+            MPC_SCOPE_ED25519EDDSA => {
+                let sig = self.party1.ed25519_sign(&saved_share, message_digest).await?;
+                Ok(MpcSignature::Ed25519(sig))
+            }
+            _ => Err(unsupported_scope(saved_share.scope)),
+        }
+    }
+
+    // party2 checks the unsigned payload, the digest is computed from it
+    pub async fn sign_payload(&self, share_id: &str, sign_payload: SignPayload) -> Result<MpcSignature, MpcError> {
+        let saved_share = self.share(share_id)?;
+        self.check_command(saved_share.scope, MPC_SIGN)?;
+        let _session = self.session_lock.lock().await;
+        match saved_share.scope {
+            MPC_SCOPE_SECP256K1ECDSA => {
+                let sig = self.party1.secp256k1_sign_payload(&saved_share, sign_payload).await?;
+                Ok(MpcSignature::Secp256k1(sig))
+            }
+            MPC_SCOPE_ED25519EDDSA => {
+                let sig = self.party1.ed25519_sign_payload(&saved_share, sign_payload).await?;
+                Ok(MpcSignature::Ed25519(sig))
+            }
+            _ => Err(unsupported_scope(saved_share.scope)),
+        }
+    }
+
+    // the new share stays pending at server and is not loaded until rotate_commit,
+    // persist it before committing
+    pub async fn rotate(&self, share_id: &str) -> Result<SavedShare, MpcError> {
+        let saved_share = self.share(share_id)?;
+        self.check_command(saved_share.scope, MPC_ROTATE)?;
+        let _session = self.session_lock.lock().await;
+        match saved_share.scope {
+            MPC_SCOPE_SECP256K1ECDSA => self.party1.secp256k1_rotate(&saved_share).await,
+            MPC_SCOPE_ED25519EDDSA => self.party1.ed25519_rotate(&saved_share).await,
+            _ => Err(unsupported_scope(saved_share.scope)),
+        }
+    }
+
+    // replace the loaded old share with the committed new one. the commit drops the recovery binding of the old share,
+    // with the kms of the binding the new share is bound in its place
+    pub async fn rotate_commit(&self, share_id: &str, new_share: SavedShare, kms: Option<&dyn Kms>) -> Result<(), MpcError> {
+        let old_share = self.share(share_id)?;
+        self.check_command(old_share.scope, MPC_ROTATE_COMMIT)?;
+        if new_share.scope != old_share.scope || new_share.epoch != old_share.epoch + 1 {
+            return Err(MpcError::new(MpcErrorKind::InvalidRequest, "new share is not a rotation of the old share"));
+        }
+        let _session = self.session_lock.lock().await;
+        self.party1.rotate_commit(&old_share, &new_share).await?;
+        self.unload_share(share_id);
+        self.load_share(new_share.clone())?;
+        if let Some(kms) = kms {
+            self.party1.identity_bind(&new_share, kms).await?;
+        }
+        Ok(())
+    }
+
+    // bind a loaded share for recovery, see identity_bind
+    pub async fn identity_bind(&self, share_id: &str, kms: &dyn Kms) -> Result<(), MpcError> {
+        let saved_share = self.share(share_id)?;
+        let _session = self.session_lock.lock().await;
+        self.party1.identity_bind(&saved_share, kms).await
+    }
+
+    // load the bound shares of the identity, persist the returned shares
+    pub async fn identity_recover(&self, kms: &dyn Kms) -> Result<Vec<SavedShare>, MpcError> {
+        let saved_shares = {
+            let _session = self.session_lock.lock().await;
+            self.party1.identity_recover(&self.config.identity_id, kms).await?
+        };
+        for saved_share in &saved_shares {
+            self.load_share(saved_share.clone())?;
+        }
+        Ok(saved_shares)
+    }
+
+    // return the hex encoded private key, ed25519 shares can not be exported
+    pub async fn export(&self, share_id: &str) -> Result<String, MpcError> {
+        let saved_share = self.share(share_id)?;
+        self.check_command(saved_share.scope, MPC_EXPORT)?;
+        let _session = self.session_lock.lock().await;
+        match saved_share.scope {
+            MPC_SCOPE_SECP256K1ECDSA => self.party1.secp256k1_export(&saved_share).await,
+            _ => Err(MpcError::new(MpcErrorKind::Unsupported, format!("export is not supported for scope {}", saved_share.scope))),
+        }
+    }
+
+    pub async fn share_status(&self, share_id: &str) -> Result<ShareStatusMsg, MpcError> {
+        let saved_share = self.share(share_id)?;
+        self.check_command(saved_share.scope, MPC_SHARE_STATUS)?;
+        let _session = self.session_lock.lock().await;
+        self.party1.query_share_status(&saved_share).await
+    }
+}
+
+fn unsupported_scope(scope: u8) -> MpcError {
+    MpcError::new(MpcErrorKind::Unsupported, format!("unsupported scope {}", scope))
+}
+
+// backends run the flows on tokio::spawn, a !Send future of MpcClient fails the build here
+#[allow(dead_code)]
+fn assert_send_futures(client: &MpcClient, share_id: &str) {
+    fn assert_send<T: Send>(_: &T) {}
+    assert_send(&client.sign(share_id, vec![]));
+}
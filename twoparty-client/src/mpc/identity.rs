@@ -0,0 +1,105 @@
+use common::errors::MpcError;
+use common::get_uuid;
+use common::identity::{EncryptedShare, IdentityBindMsg, IdentityRecoverMsg1, IdentityRecoverMsg2, IdentityRecoverMsg3, IdentitySecretMsg, prove_identity_secret};
+use common::socketmsg::types::{EmptyMsg, Mpc22Msg, MPC_IDENTITY_BIND, MPC_IDENTITY_RECOVER, SavedShare};
+use crate::kms::Kms;
+use crate::mpc::parse_rsp;
+use crate::mpc::Party1Client;
+use crate::mpc::transport::Party1Transport;
+
+impl<T: Party1Transport> Party1Client<T> {
+    // upload (Enc(identity-secret), Enc(share)) to mpc-server, so the share can be recovered with identity_id.
+    // binding another share of a bound identity, or the new share after a rotation, needs the kms of the first binding
+    pub async fn identity_bind(&self, saved_share: &SavedShare, kms: &dyn Kms) -> Result<(), MpcError> {
+        let identity_id = &saved_share.identity_id;
+        let mpc22_msg = Mpc22Msg {
+            command: MPC_IDENTITY_BIND,
+            scope: saved_share.scope,
+            party: 1,
+            step: 1,
+            msg_detail: vec![],
+            identity_id: identity_id.clone(),
+            share_id: "".to_string(),
+            epoch: 0,
+            sign_payload: None,
+            session_id: get_uuid(),
+            version: self.transport.protocol_version(saved_share.scope),
+        };
+        let rsp1 = self.send_mpc22_msg(&EmptyMsg {}, mpc22_msg.clone()).await?;
+        let identity_secret_msg = parse_rsp::<IdentitySecretMsg>(&rsp1)?;
+
+        let share_bytes = serde_json::to_vec(saved_share).unwrap();
+        let encrypted_share = EncryptedShare {
+            share_id: saved_share.share_id.clone(),
+            scope: saved_share.scope,
+            encrypted_share: kms.encrypt(&share_bytes)?,
+        };
+        let bind_msg = match identity_secret_msg.bound {
+            // add the share to the binding by proving the identity-secret, as recover does
+            Some(recover_msg1) => {
+                let identity_secret = kms.decrypt(&recover_msg1.encrypted_identity_secret)?;
+                IdentityBindMsg {
+                    encrypted_identity_secret: vec![],
+                    encrypted_share,
+                    secret_proof: Some(prove_identity_secret(&identity_secret, &recover_msg1.challenge)?),
+                }
+            }
+            None => IdentityBindMsg {
+                encrypted_identity_secret: kms.encrypt(&identity_secret_msg.identity_secret)?,
+                encrypted_share,
+                secret_proof: None,
+            },
+        };
+        let mut mpc22_step2 = mpc22_msg.clone();
+        mpc22_step2.step = 2;
+        let rsp2 = self.send_mpc22_msg(&bind_msg, mpc22_step2).await?;
+        if let Some(err) = MpcError::from_rsp(&rsp2) {
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    // prove the knowledge of identity-secret to get back Enc(share), then decrypt the shares with kms
+    pub async fn identity_recover(&self, identity_id: &str, kms: &dyn Kms) -> Result<Vec<SavedShare>, MpcError> {
+        let mpc22_msg = Mpc22Msg {
+            command: MPC_IDENTITY_RECOVER,
+            scope: 0,
+            party: 1,
+            step: 1,
+            msg_detail: vec![],
+            identity_id: identity_id.to_string(),
+            share_id: "".to_string(),
+            epoch: 0,
+            sign_payload: None,
+            session_id: get_uuid(),
+            version: self.transport.protocol_version(0),
+        };
+        let rsp1 = self.send_mpc22_msg(&EmptyMsg {}, mpc22_msg.clone()).await?;
+        let recover_msg1 = parse_rsp::<IdentityRecoverMsg1>(&rsp1)?;
+
+        let identity_secret = kms.decrypt(&recover_msg1.encrypted_identity_secret)?;
+        let secret_proof = prove_identity_secret(&identity_secret, &recover_msg1.challenge)?;
+        let recover_msg2 = IdentityRecoverMsg2 { secret_proof };
+        let mut mpc22_step2 = mpc22_msg.clone();
+        mpc22_step2.step = 2;
+        let rsp2 = self.send_mpc22_msg(&recover_msg2, mpc22_step2).await?;
+        let recover_msg3 = parse_rsp::<IdentityRecoverMsg3>(&rsp2)?;
+
+        let mut saved_shares = vec![];
+        for encrypted_share in recover_msg3.encrypted_shares {
+            let share_bytes = kms.decrypt(&encrypted_share.encrypted_share)?;
+            saved_shares.push(SavedShare::from_json(&share_bytes)?);
+        }
+        Ok(saved_shares)
+    }
+}
+
+pub async fn identity_bind(url: String, access_token: Option<String>, saved_share: &SavedShare, kms: &dyn Kms) -> Result<(), MpcError> {
+    let party1 = Party1Client::connect(saved_share.identity_id.clone(), url, access_token).await?;
+    party1.identity_bind(saved_share, kms).await
+}
+
+pub async fn identity_recover(identity_id: String, url: String, access_token: Option<String>, kms: &dyn Kms) -> Result<Vec<SavedShare>, MpcError> {
+    let party1 = Party1Client::connect(identity_id.clone(), url, access_token).await?;
+    party1.identity_recover(&identity_id, kms).await
+}
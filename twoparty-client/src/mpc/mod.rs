@@ -1,48 +1,134 @@
 use serde::Serialize;
-use common::socketmsg::{MsgWrapper, REQ_CODE_MPC22, RSP_CODE_SUCCESS};
-use common::socketmsg::types::Mpc22Msg;
+use common::errors::{MpcError, MpcErrorKind};
+use common::socketmsg::codec::{decode_detail, encode_detail};
+use common::socketmsg::MsgWrapper;
+use common::socketmsg::types::{EmptyMsg, Mpc22Msg, MPC_ROTATE_COMMIT, MPC_SHARE_STATUS, RotateCommitMsg, SavedShare, SHARE_STATUS_ACTIVE, ShareStatusMsg};
+use crate::mpc::transport::Party1Transport;
+#[cfg(feature = "native")]
 use crate::websocket::SyncClient;
 
+pub mod transport;
 pub mod secp256k1;
 pub mod ed25519;
-#[cfg(test)]
+#[cfg(feature = "native")]
+pub mod identity;
+#[cfg(feature = "native")]
+pub mod client;
+#[cfg(all(test, feature = "native"))]
 mod test;
 
-impl SyncClient {
-    pub async fn send_mpc22_msg<T>(&self, msg_detail: &T, mut mpc22_msg: Mpc22Msg) -> Result<MsgWrapper, String>
-        where T: ?Sized + Serialize
+// party1 of the mpc22 flows, over any transport to party2
+pub struct Party1Client<T: Party1Transport> {
+    pub transport: T,
+}
+
+#[cfg(feature = "native")]
+impl Party1Client<SyncClient> {
+    // access_token is the bearer of the upgrade, required when the server enables authentication
+    pub async fn connect(identity_id: String, url: String, access_token: Option<String>) -> Result<Self, MpcError> {
+        let sync_client = SyncClient::connect_server_with_token(identity_id, url, 10, access_token).await?;
+        Ok(Party1Client::new(sync_client))
+    }
+}
+
+impl<T: Party1Transport> Party1Client<T> {
+    pub fn new(transport: T) -> Self {
+        Party1Client { transport }
+    }
+
+    pub async fn send_mpc22_msg<M>(&self, msg_detail: &M, mut mpc22_msg: Mpc22Msg) -> Result<MsgWrapper, MpcError>
+        where M: ?Sized + Serialize
     {
-        // serialize msg_detail
-        let detail_bytes = serde_json::to_vec(msg_detail);
-        if detail_bytes.is_err() {
-            return Err(detail_bytes.err().unwrap().to_string());
-        }
-        mpc22_msg.msg_detail = detail_bytes.unwrap();
+        // serialize msg_detail & mpc22_msg in the agreed msg version, a resend keeps it
+        let msg_version = self.transport.msg_version();
+        mpc22_msg.msg_detail = encode_detail(msg_version, msg_detail)?;
+        let mpc22_bytes = encode_detail(msg_version, &mpc22_msg)?;
+        self.transport.send_mpc22(mpc22_msg.step, mpc22_bytes, msg_version).await
+    }
 
-        // serialize mpc22_msg
-        let mpc22_bytes = serde_json::to_vec(&mpc22_msg);
-        if mpc22_bytes.is_err() {
-            return Err(mpc22_bytes.err().unwrap().to_string());
-        }
+    pub async fn query_share_status(&self, saved_share: &SavedShare) -> Result<ShareStatusMsg, MpcError> {
+        let mpc22_msg = Mpc22Msg {
+            command: MPC_SHARE_STATUS,
+            scope: saved_share.scope,
+            party: 1,
+            step: 1,
+            msg_detail: vec![],
+            identity_id: saved_share.identity_id.clone(),
+            share_id: saved_share.share_id.clone(),
+            epoch: saved_share.epoch,
+            sign_payload: None,
+            session_id: "".to_string(),
+            version: self.transport.protocol_version(saved_share.scope),
+        };
+        let rsp = self.send_mpc22_msg(&EmptyMsg {}, mpc22_msg).await?;
+        parse_rsp::<ShareStatusMsg>(&rsp)
+    }
 
-        // send_req
-        let rsp = self.send_req(REQ_CODE_MPC22, mpc22_bytes.unwrap(), None).await;
-        if rsp.is_err() {
-            return Err(rsp.err().unwrap().to_string());
+    // refuse to run a session when party2 holds another epoch of the share
+    pub async fn check_share_epoch(&self, saved_share: &SavedShare) -> Result<(), MpcError> {
+        let status = self.query_share_status(saved_share).await?;
+        if status.status != SHARE_STATUS_ACTIVE {
+            let err = format!("share is not active at server, status={}, active_share_id={}", status.status, status.active_share_id);
+            return Err(MpcError::new(MpcErrorKind::ShareNotFound, err));
+        }
+        if status.epoch != saved_share.epoch {
+            // the same kind as the refusal of the server
+            let err = format!("share epoch not match, party1 epoch={}, party2 epoch={}", saved_share.epoch, status.epoch);
+            return Err(MpcError::new(MpcErrorKind::VersionMismatch, err));
         }
+        Ok(())
+    }
 
-        Ok(rsp.unwrap())
+    // the share returned by rotate stays pending at server until committed,
+    // party1 should persist the new share before committing it
+    pub async fn rotate_commit(&self, old_share: &SavedShare, new_share: &SavedShare) -> Result<(), MpcError> {
+        let mpc22_msg = Mpc22Msg {
+            command: MPC_ROTATE_COMMIT,
+            scope: old_share.scope,
+            party: 1,
+            step: 1,
+            msg_detail: vec![],
+            identity_id: old_share.identity_id.clone(),
+            share_id: old_share.share_id.clone(),
+            epoch: old_share.epoch,
+            sign_payload: None,
+            session_id: "".to_string(),
+            version: self.transport.protocol_version(old_share.scope),
+        };
+        let commit_msg = RotateCommitMsg {
+            new_share_id: new_share.share_id.clone(),
+        };
+        let rsp = self.send_mpc22_msg(&commit_msg, mpc22_msg).await?;
+        if let Some(err) = MpcError::from_rsp(&rsp) {
+            return Err(err);
+        }
+        Ok(())
     }
 }
 
-fn parse_rsp<'a,T: serde::Deserialize<'a>>(msg_wrapper: &'a MsgWrapper) -> Result<T, String> {
-    if RSP_CODE_SUCCESS != msg_wrapper.action_code {
-        return Err(msg_wrapper.error_msg.clone());
+#[cfg(feature = "native")]
+pub async fn rotate_commit(url: String, access_token: Option<String>, old_share: &SavedShare, new_share: &SavedShare) -> Result<(), MpcError> {
+    let party1 = Party1Client::connect(old_share.identity_id.clone(), url, access_token).await?;
+    party1.rotate_commit(old_share, new_share).await
+}
+
+// query the server which version of the share is active
+#[cfg(feature = "native")]
+pub async fn share_status(url: String, access_token: Option<String>, saved_share: &SavedShare) -> Result<ShareStatusMsg, MpcError> {
+    let party1 = Party1Client::connect(saved_share.identity_id.clone(), url, access_token).await?;
+    party1.query_share_status(saved_share).await
+}
+
+// the MpcErrorKind of a failed rsp is told by its error_code, or by its rsp_code from older servers
+fn parse_rsp<'a,T: serde::Deserialize<'a>>(msg_wrapper: &'a MsgWrapper) -> Result<T, MpcError> {
+    if let Some(err) = MpcError::from_rsp(msg_wrapper) {
+        return Err(err);
     }
 
-    let t = serde_json::from_slice::<T>(&msg_wrapper.body);
+    let t = decode_detail::<T>(msg_wrapper.version, &msg_wrapper.body);
     if t.is_err() {
-        return Err(format!("parse msg_detail fail, err={}", t.err().unwrap()));
+        let err = format!("parse msg_detail fail, err={}", t.err().unwrap());
+        return Err(MpcError::new(MpcErrorKind::MalformedMessage, err));
     }
 
     Ok(t.unwrap())
@@ -1,24 +1,53 @@
 use crate::mpc::ed25519::{ed25519_keygen, ed25519_rotate, ed25519_sign};
-use crate::mpc::secp256k1::{secp256k1_export, secp256k1_rotate, secp256k1_sign, Secp256k1Sig};
+use crate::mpc::secp256k1::{secp256k1_export, secp256k1_rotate, secp256k1_sign, secp256k1_sign_payload, Secp256k1Sig};
+use common::payload::{PAYLOAD_FORMAT_ETH_TX, SignPayload};
+use crate::mpc::{rotate_commit, share_status};
+use common::socketmsg::types::{SHARE_STATUS_ACTIVE, SHARE_STATUS_RETIRED};
+use crate::kms::LocalKms;
+use crate::mpc::identity::{identity_bind, identity_recover};
 use super::secp256k1;
+use crate::mpc::client::{MpcClient, MpcClientConfig, MpcSignature};
+use common::socketmsg::types::{MPC_SCOPE_ED25519EDDSA, MPC_SCOPE_SECP256K1ECDSA};
+use std::time::{Duration, Instant};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use common::{get_tsp, get_uuid};
+use common::errors::MpcErrorKind;
+use common::socketmsg::{MSG_ACTION_REQ, MSG_ACTION_RSP, MSG_VERSION_V1, MSG_VERSION_V2, MsgWrapper, REQ_CODE_MPC22, RSP_CODE_SUCCESS};
+use common::socketmsg::codec::{decode_detail, decode_msg, encode_detail, encode_msg};
+use common::socketmsg::types::{Mpc22Msg, MPC_KEYGEN, MPC_PROTOCOL_V2};
+use twoparty_secp256k1::keygen;
 
 #[tokio::test(flavor = "multi_thread")]
 async fn test_secp256k1_ecdsa() {
     let identity_id = "wangcy";
     let url = "ws://localhost:8822/ws";
-    let saved_share = secp256k1::secp256k1_keygen(identity_id.to_string(), url.to_string()).await.unwrap();
+    let saved_share = secp256k1::secp256k1_keygen(identity_id.to_string(), url.to_string(), None).await.unwrap();
     println!("secp256k1 keygen success, share_id={}", &saved_share.share_id);
-    let x = secp256k1_export(url.to_string(), &saved_share).await.unwrap();
+    let x = secp256k1_export(url.to_string(), None, &saved_share).await.unwrap();
     println!("export success x={}", x);
 
     let message_digest = vec![1, 2, 3, 4];
-    let sig = secp256k1_sign(url.to_string(), &saved_share, message_digest).await.unwrap();
+    let sig = secp256k1_sign(url.to_string(), None, &saved_share, message_digest).await.unwrap();
     println!("{:?}", sig);
 
-    let new_share = secp256k1_rotate(url.to_string(), &saved_share).await.unwrap();
+    // unsigned eip155 tx, party2 recomputes the digest from it
+    let sign_payload = SignPayload {
+        format: PAYLOAD_FORMAT_ETH_TX,
+        payload: hex::decode("ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080").unwrap(),
+        input_index: 0,
+    };
+    let tx_sig = secp256k1_sign_payload(url.to_string(), None, &saved_share, sign_payload).await.unwrap();
+    println!("{:?}", tx_sig);
+
+    let new_share = secp256k1_rotate(url.to_string(), None, &saved_share).await.unwrap();
     println!("rotate success, new_share_id={}", new_share.share_id);
+    rotate_commit(url.to_string(), None, &saved_share, &new_share).await.unwrap();
+    let old_status = share_status(url.to_string(), None, &saved_share).await.unwrap();
+    assert_eq!(old_status.status, SHARE_STATUS_RETIRED);
+    assert_eq!(old_status.active_share_id, new_share.share_id);
 
-    let x_rotate = secp256k1_export(url.to_string(), &new_share).await.unwrap();
+    let x_rotate = secp256k1_export(url.to_string(), None, &new_share).await.unwrap();
     println!("export new_share success, x={}", x_rotate);
 
     if x != x_rotate {
@@ -31,17 +60,20 @@ async fn test_secp256k1_ecdsa() {
 async fn test_ed25519_eddsa() {
     let identity_id = "wangcy";
     let url = "ws://localhost:8822/ws";
-    let saved_share = ed25519_keygen(identity_id.to_string(), url.to_string()).await.unwrap();
+    let saved_share = ed25519_keygen(identity_id.to_string(), url.to_string(), None).await.unwrap();
     println!("ed25519 keygen success, share_id={}", &saved_share.share_id);
 
     let message_digest = vec![1, 2, 3, 4];
-    let sig = ed25519_sign(url.to_string(), &saved_share, message_digest.clone()).await.unwrap();
+    let sig = ed25519_sign(url.to_string(), None, &saved_share, message_digest.clone()).await.unwrap();
     println!("sig length={}", sig.len());
 
-    let rotated_share = ed25519_rotate(url.to_string(), &saved_share).await.unwrap();
+    let rotated_share = ed25519_rotate(url.to_string(), None, &saved_share).await.unwrap();
     println!("ed25519 rotate success, share_id={}", &rotated_share.share_id);
+    rotate_commit(url.to_string(), None, &saved_share, &rotated_share).await.unwrap();
+    let new_status = share_status(url.to_string(), None, &rotated_share).await.unwrap();
+    assert_eq!(new_status.status, SHARE_STATUS_ACTIVE);
 
-    let sig2 = ed25519_sign(url.to_string(), &rotated_share, message_digest).await.unwrap();
+    let sig2 = ed25519_sign(url.to_string(), None, &rotated_share, message_digest).await.unwrap();
     println!("sig length={}", sig2.len());
 
     if sig2 != sig {
@@ -50,6 +82,102 @@ async fn test_ed25519_eddsa() {
 }
 
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_identity_bind_recover() {
+    let identity_id = format!("identity-{}", common::get_uuid());
+    let url = "ws://localhost:8822/ws";
+    let kms = LocalKms::generate();
+    let saved_share = ed25519_keygen(identity_id.clone(), url.to_string(), None).await.unwrap();
+    identity_bind(url.to_string(), None, &saved_share, &kms).await.unwrap();
+
+    let recovered_shares = identity_recover(identity_id, url.to_string(), None, &kms).await.unwrap();
+    assert_eq!(recovered_shares.len(), 1);
+    assert_eq!(recovered_shares[0].share_id, saved_share.share_id);
+    assert_eq!(recovered_shares[0].share_detail, saved_share.share_detail);
+
+    // without the kms key, identity-secret can not be decrypted
+    let other_kms = LocalKms::generate();
+    assert!(identity_recover(saved_share.identity_id.clone(), url.to_string(), None, &other_kms).await.is_err());
+    // nor can another share be bound
+    let secp_share = secp256k1::secp256k1_keygen(saved_share.identity_id.clone(), url.to_string(), None).await.unwrap();
+    assert!(identity_bind(url.to_string(), None, &secp_share, &other_kms).await.is_err());
+
+    identity_bind(url.to_string(), None, &secp_share, &kms).await.unwrap();
+    let recovered_shares = identity_recover(saved_share.identity_id.clone(), url.to_string(), None, &kms).await.unwrap();
+    assert_eq!(recovered_shares.len(), 2);
+
+    // the retired share is unbound, the rotated one is bound again
+    let rotated_share = ed25519_rotate(url.to_string(), None, &saved_share).await.unwrap();
+    rotate_commit(url.to_string(), None, &saved_share, &rotated_share).await.unwrap();
+    let recovered_shares = identity_recover(saved_share.identity_id.clone(), url.to_string(), None, &kms).await.unwrap();
+    assert_eq!(recovered_shares.len(), 1);
+    assert_eq!(recovered_shares[0].share_id, secp_share.share_id);
+    identity_bind(url.to_string(), None, &rotated_share, &kms).await.unwrap();
+    let recovered_shares = identity_recover(saved_share.identity_id.clone(), url.to_string(), None, &kms).await.unwrap();
+    assert_eq!(recovered_shares.len(), 2);
+}
+
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mpc_client_rebind() {
+    let identity_id = format!("identity-{}", get_uuid());
+    let config = MpcClientConfig::new("ws://localhost:8822/ws".to_string(), identity_id.clone());
+    let mpc_client = MpcClient::connect(config).await.unwrap();
+    let kms = LocalKms::generate();
+    let saved_share = mpc_client.keygen(MPC_SCOPE_SECP256K1ECDSA).await.unwrap();
+    mpc_client.identity_bind(&saved_share.share_id, &kms).await.unwrap();
+
+    // the commit binds the new share in place of the retired one
+    let new_share = mpc_client.rotate(&saved_share.share_id).await.unwrap();
+    mpc_client.rotate_commit(&saved_share.share_id, new_share.clone(), Some(&kms)).await.unwrap();
+    mpc_client.unload_share(&new_share.share_id);
+    let recovered_shares = mpc_client.identity_recover(&kms).await.unwrap();
+    assert_eq!(recovered_shares.len(), 1);
+    assert_eq!(recovered_shares[0].share_id, new_share.share_id);
+    assert!(mpc_client.share(&new_share.share_id).is_ok());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mpc_client() {
+    let config = MpcClientConfig::new("ws://localhost:8822/ws".to_string(), "wangcy".to_string());
+    let mpc_client = MpcClient::connect(config).await.unwrap();
+
+    // every operation runs on the same connection
+    let secp_share = mpc_client.keygen(MPC_SCOPE_SECP256K1ECDSA).await.unwrap();
+    let ed_share = mpc_client.keygen(MPC_SCOPE_ED25519EDDSA).await.unwrap();
+    assert_eq!(mpc_client.share_ids().len(), 2);
+
+    let sig = mpc_client.sign(&secp_share.share_id, vec![1, 2, 3, 4]).await.unwrap();
+    assert!(matches!(sig, MpcSignature::Secp256k1(_)));
+    let sig = mpc_client.sign(&ed_share.share_id, vec![1, 2, 3, 4]).await.unwrap();
+    assert!(matches!(sig, MpcSignature::Ed25519(ref sig_bytes) if sig_bytes.len() == 64));
+
+    let x = mpc_client.export(&secp_share.share_id).await.unwrap();
+    assert_eq!(mpc_client.export(&ed_share.share_id).await.unwrap_err().kind, MpcErrorKind::Unsupported);
+
+    let new_share = mpc_client.rotate(&secp_share.share_id).await.unwrap();
+    mpc_client.rotate_commit(&secp_share.share_id, new_share.clone(), None).await.unwrap();
+    assert_eq!(mpc_client.share(&secp_share.share_id).unwrap_err().kind, MpcErrorKind::ShareNotFound);
+    let status = mpc_client.share_status(&new_share.share_id).await.unwrap();
+    assert_eq!(status.status, SHARE_STATUS_ACTIVE);
+    assert_eq!(mpc_client.export(&new_share.share_id).await.unwrap(), x);
+}
+
+#[test]
+fn test_mpc_client_config() {
+    let config = serde_json::from_str::<MpcClientConfig>(
+        r#"{"url":"ws://localhost:8822/ws","identity_id":"wangcy","request_timeout_ms":5000,"step_retry":{"max_retries":0}}"#
+    ).unwrap();
+    assert!(config.auto_config);
+    assert_eq!(config.heartbeat_sec, None);
+    assert_eq!(config.access_token, None);
+    assert_eq!(config.options.request_timeout_ms, 5000);
+    assert_eq!(config.options.step_retry.max_retries, 0);
+    assert_eq!(config.options.step_retry.resume_timeout_ms, 30_000);
+    assert!(config.options.reconnect.enabled);
+    assert!(config.options.binary_encoding);
+}
+
 #[test]
 fn test_serde() {
     let share_id = "hello".to_string();
@@ -62,4 +190,116 @@ fn test_serde() {
         println!("{:?}", bytes);
         println!("{:?}", json_bytes);
     }
-}
\ No newline at end of file
+}
+
+
+fn wire_frame(msg_version: u16, action: u8, action_code: u32, body: Vec<u8>) -> Vec<u8> {
+    let msg_wrapper = MsgWrapper {
+        seq: 1,
+        timestamp: get_tsp(),
+        action,
+        action_code,
+        body,
+        error_msg: "".to_string(),
+        notice_id: "".to_string(),
+        version: msg_version,
+        error_code: 0,
+    };
+    encode_msg(&msg_wrapper).unwrap()
+}
+
+fn keygen_req_frame<T: Serialize>(msg_version: u16, step: u8, msg_detail: &T) -> Vec<u8> {
+    let mpc22_msg = Mpc22Msg {
+        command: MPC_KEYGEN,
+        scope: MPC_SCOPE_SECP256K1ECDSA,
+        party: 1,
+        step,
+        msg_detail: encode_detail(msg_version, msg_detail).unwrap(),
+        identity_id: "wangcy".to_string(),
+        share_id: "".to_string(),
+        epoch: 0,
+        sign_payload: None,
+        session_id: get_uuid(),
+        version: MPC_PROTOCOL_V2,
+    };
+    wire_frame(msg_version, MSG_ACTION_REQ, REQ_CODE_MPC22, encode_detail(msg_version, &mpc22_msg).unwrap())
+}
+
+fn open_req_frame<T: DeserializeOwned>(frame: &[u8]) -> T {
+    let msg_wrapper = decode_msg(frame).unwrap();
+    let mpc22_msg = decode_detail::<Mpc22Msg>(msg_wrapper.version, &msg_wrapper.body).unwrap();
+    decode_detail::<T>(msg_wrapper.version, &mpc22_msg.msg_detail).unwrap()
+}
+
+fn rsp_frame<T: Serialize>(msg_version: u16, body: &T) -> Vec<u8> {
+    wire_frame(msg_version, MSG_ACTION_RSP, RSP_CODE_SUCCESS, encode_detail(msg_version, body).unwrap())
+}
+
+fn open_rsp_frame<T: DeserializeOwned>(frame: &[u8]) -> T {
+    let msg_wrapper = decode_msg(frame).unwrap();
+    decode_detail::<T>(msg_wrapper.version, &msg_wrapper.body).unwrap()
+}
+
+// an in-process secp256k1 keygen, every step message crosses the wire encoding of msg_version.
+// return the bytes of the 4 frames & the time spent encoding and decoding them
+fn keygen_over_wire(msg_version: u16) -> (usize, Duration) {
+    let mut codec_time = Duration::ZERO;
+    let (party1_msg1, witness, party1_keypair) = keygen::party1::party1_step1();
+
+    let start = Instant::now();
+    let req1 = keygen_req_frame(msg_version, 1, &party1_msg1);
+    let party1_msg1 = open_req_frame::<keygen::party1::Party1KeyGenMsg1>(&req1);
+    codec_time += start.elapsed();
+
+    let (party2_msg1, party2_keypair) = keygen::party2::party2_step1();
+
+    let start = Instant::now();
+    let rsp1 = rsp_frame(msg_version, &party2_msg1);
+    let party2_msg1 = open_rsp_frame::<keygen::party2::Party2KeyGenMsg1>(&rsp1);
+    codec_time += start.elapsed();
+
+    let (party1_msg2, _party1_share) = keygen::party1::party1_step2(party2_msg1, witness, party1_keypair).unwrap();
+
+    let start = Instant::now();
+    let req2 = keygen_req_frame(msg_version, 2, &party1_msg2);
+    let party1_msg2 = open_req_frame::<keygen::party1::Party1KeygenMsg2>(&req2);
+    codec_time += start.elapsed();
+
+    keygen::party2::party2_step2(party1_msg2, party1_msg1, party2_keypair).unwrap();
+
+    let start = Instant::now();
+    let rsp2 = rsp_frame(msg_version, &get_uuid());
+    open_rsp_frame::<String>(&rsp2);
+    codec_time += start.elapsed();
+
+    (req1.len() + rsp1.len() + req2.len() + rsp2.len(), codec_time)
+}
+
+// cargo test -p twoparty-client test_keygen_wire_encoding -- --nocapture
+#[test]
+fn test_keygen_wire_encoding() {
+    let rounds = 10;
+    let mut results = vec![];
+    for msg_version in [MSG_VERSION_V1, MSG_VERSION_V2] {
+        let mut total_bytes = 0;
+        let mut total_time = Duration::ZERO;
+        for _ in 0..rounds {
+            let (bytes, codec_time) = keygen_over_wire(msg_version);
+            total_bytes += bytes;
+            total_time += codec_time;
+        }
+        let avg_bytes = total_bytes / rounds;
+        let avg_time = total_time / rounds as u32;
+        results.push((avg_bytes, avg_time));
+    }
+    let (json_bytes, json_time) = results[0];
+    let (binary_bytes, binary_time) = results[1];
+    // the rows of the table in the Wire encoding section of the README
+    println!("| msg version | wire bytes | codec time |");
+    println!("|---|---|---|");
+    println!("| v1 json | {} | {:?} |", json_bytes, json_time);
+    println!("| v2 bincode | {} ({:.1}%) | {:?} ({:.1}%) |",
+        binary_bytes, binary_bytes as f64 * 100.0 / json_bytes as f64,
+        binary_time, binary_time.as_secs_f64() * 100.0 / json_time.as_secs_f64());
+    assert!(binary_bytes < json_bytes);
+}
@@ -0,0 +1,24 @@
+use common::errors::MpcError;
+use common::socketmsg::MsgWrapper;
+
+// natively the flows run on tokio::spawn, e.g. many signing jobs of one backend, so their futures are Send
+#[cfg(not(feature = "wasm"))]
+pub type TransportFuture<'a, T> = futures_util::future::BoxFuture<'a, T>;
+// the browser websocket lives on one thread, its futures are not Send
+#[cfg(feature = "wasm")]
+pub type TransportFuture<'a, T> = futures_util::future::LocalBoxFuture<'a, T>;
+
+// what the party1 flows need from a connection to party2: SyncClient natively, BrowserTransport on wasm
+pub trait Party1Transport {
+    fn identity_id(&self) -> &str;
+
+    // the step protocol version agreed with the server for the scope
+    fn protocol_version(&self, scope: u8) -> u16;
+
+    // the envelope version agreed with the server
+    fn msg_version(&self) -> u16;
+
+    // send an Mpc22Msg encoded in msg_version as a REQ_CODE_MPC22 req, a transport that can resend it on
+    // timeout or disconnection should, party2 resumes the session by its session_id
+    fn send_mpc22(&self, step: u8, mpc22_bytes: Vec<u8>, msg_version: u16) -> TransportFuture<'_, Result<MsgWrapper, MpcError>>;
+}
@@ -0,0 +1,125 @@
+use std::rc::Rc;
+use js_sys::{Promise, Reflect, Uint8Array};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+use common::errors::MpcError;
+use common::socketmsg::types::SavedShare;
+use crate::mpc::Party1Client;
+use crate::wasm::transport::BrowserTransport;
+
+pub mod transport;
+
+// party1 in the browser: one websocket to party2, the flows of both curves. shares & results are json strings,
+// a rejected promise carries a js Error with the MpcErrorKind code in `code`
+#[wasm_bindgen]
+pub struct WasmMpcClient {
+    inner: Rc<Party1Client<BrowserTransport>>,
+}
+
+#[wasm_bindgen]
+impl WasmMpcClient {
+    // access_token is sent as a query param, browsers can not set headers on a websocket
+    pub async fn connect(url: String, identity_id: String, access_token: Option<String>) -> Result<WasmMpcClient, JsValue> {
+        let transport = BrowserTransport::connect(identity_id, url, access_token).await
+            .map_err(js_err)?;
+        Ok(WasmMpcClient { inner: Rc::new(Party1Client::new(transport)) })
+    }
+
+    #[wasm_bindgen(js_name = isConnected)]
+    pub fn is_connected(&self) -> bool {
+        self.inner.transport.is_connected()
+    }
+
+    pub fn close(&self) {
+        self.inner.transport.close();
+    }
+
+    // resolve json of the new SavedShare
+    #[wasm_bindgen(js_name = secp256k1Keygen)]
+    pub fn secp256k1_keygen(&self) -> Promise {
+        let inner = self.inner.clone();
+        future_to_promise(async move {
+            let saved_share = inner.secp256k1_keygen().await.map_err(js_err)?;
+            Ok(json_value(&saved_share))
+        })
+    }
+
+    // resolve json of Secp256k1Sig
+    #[wasm_bindgen(js_name = secp256k1Sign)]
+    pub fn secp256k1_sign(&self, share: String, message_digest: Vec<u8>) -> Promise {
+        let inner = self.inner.clone();
+        future_to_promise(async move {
+            let saved_share = parse_share(&share)?;
+            let sig = inner.secp256k1_sign(&saved_share, message_digest).await.map_err(js_err)?;
+            Ok(json_value(&sig))
+        })
+    }
+
+    // resolve json of the pending SavedShare, persist it before rotateCommit
+    #[wasm_bindgen(js_name = secp256k1Rotate)]
+    pub fn secp256k1_rotate(&self, share: String) -> Promise {
+        let inner = self.inner.clone();
+        future_to_promise(async move {
+            let old_share = parse_share(&share)?;
+            let new_share = inner.secp256k1_rotate(&old_share).await.map_err(js_err)?;
+            Ok(json_value(&new_share))
+        })
+    }
+
+    #[wasm_bindgen(js_name = ed25519Keygen)]
+    pub fn ed25519_keygen(&self) -> Promise {
+        let inner = self.inner.clone();
+        future_to_promise(async move {
+            let saved_share = inner.ed25519_keygen().await.map_err(js_err)?;
+            Ok(json_value(&saved_share))
+        })
+    }
+
+    // resolve a Uint8Array of the 64 bytes R || s
+    #[wasm_bindgen(js_name = ed25519Sign)]
+    pub fn ed25519_sign(&self, share: String, message_digest: Vec<u8>) -> Promise {
+        let inner = self.inner.clone();
+        future_to_promise(async move {
+            let saved_share = parse_share(&share)?;
+            let sig = inner.ed25519_sign(&saved_share, message_digest).await.map_err(js_err)?;
+            Ok(Uint8Array::from(sig.as_slice()).into())
+        })
+    }
+
+    #[wasm_bindgen(js_name = ed25519Rotate)]
+    pub fn ed25519_rotate(&self, share: String) -> Promise {
+        let inner = self.inner.clone();
+        future_to_promise(async move {
+            let old_share = parse_share(&share)?;
+            let new_share = inner.ed25519_rotate(&old_share).await.map_err(js_err)?;
+            Ok(json_value(&new_share))
+        })
+    }
+
+    // activate the share returned by a rotate of either curve
+    #[wasm_bindgen(js_name = rotateCommit)]
+    pub fn rotate_commit(&self, old_share: String, new_share: String) -> Promise {
+        let inner = self.inner.clone();
+        future_to_promise(async move {
+            let old_share = parse_share(&old_share)?;
+            let new_share = parse_share(&new_share)?;
+            inner.rotate_commit(&old_share, &new_share).await.map_err(js_err)?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+}
+
+fn js_err(e: MpcError) -> JsValue {
+    let error = js_sys::Error::new(&e.message);
+    Reflect::set(&error, &JsValue::from_str("code"), &JsValue::from(e.code())).unwrap_or(false);
+    error.into()
+}
+
+fn parse_share(share: &str) -> Result<SavedShare, JsValue> {
+    SavedShare::from_json(share.as_bytes()).map_err(|e| js_err(e.into()))
+}
+
+fn json_value<T: Serialize>(value: &T) -> JsValue {
+    JsValue::from_str(&serde_json::to_string(value).unwrap())
+}
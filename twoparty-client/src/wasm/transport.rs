@@ -0,0 +1,237 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use futures_channel::oneshot;
+use futures_util::future::{Either, select};
+use js_sys::{Function, Promise, Reflect, Uint8Array};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{BinaryType, CloseEvent, console, Event, MessageEvent, WebSocket};
+use common::errors::{MpcError, MpcErrorKind};
+use common::get_tsp;
+use common::socketmsg::codec::{decode_msg, encode_msg};
+use common::socketmsg::{MSG_ACTION_REQ, MSG_ACTION_RSP, MSG_VERSION_V1, MSG_VERSIONS, MsgWrapper, NegotiatedMsg, NegotiateMsg, REQ_CODE_MPC22, REQ_CODE_NEGOTIATE, RSP_CODE_SUCCESS, ScopeVersions};
+use common::socketmsg::types::{MPC_PROTOCOL_V1, MPC_PROTOCOL_V2, MPC_SCOPE_ED25519EDDSA, MPC_SCOPE_SECP256K1ECDSA};
+use crate::mpc::transport::{Party1Transport, TransportFuture};
+
+// same as the native client, see websocket
+const PROTOCOL_VERSIONS: [u16; 2] = [MPC_PROTOCOL_V1, MPC_PROTOCOL_V2];
+const NEGOTIATE_TIMEOUT_MS: u32 = 5_000;
+const REQUEST_TIMEOUT_MS: u32 = 20_000;
+
+// the websocket of the browser, or of a web worker. there is no reconnection, a step fails with
+// MpcErrorKind::Disconnected once the socket closes, connect again to retry the operation
+pub struct BrowserTransport {
+    identity_id: String,
+    socket: WebSocket,
+    shared: Rc<TransportShared>,
+    // the callbacks of the socket live as long as the transport
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(CloseEvent)>,
+}
+
+struct TransportShared {
+    seq: Cell<u32>,
+    // seq -> rsp_tx, dropped on timeout or close
+    requests: RefCell<HashMap<u32, oneshot::Sender<MsgWrapper>>>,
+    closed: Cell<bool>,
+    negotiated: RefCell<Option<NegotiatedMsg>>,
+}
+
+impl BrowserTransport {
+    // browsers can not set the Authorization header of a websocket, access_token goes in the query instead
+    pub async fn connect(identity_id: String, url: String, access_token: Option<String>) -> Result<Self, MpcError> {
+        let mut ws_url = url;
+        if let Some(token) = access_token {
+            let separator = if ws_url.contains('?') { '&' } else { '?' };
+            ws_url = format!("{}{}access_token={}", ws_url, separator, js_sys::encode_uri_component(&token));
+        }
+        let socket = WebSocket::new(&ws_url)
+            .map_err(|e| MpcError::new(MpcErrorKind::Unavailable, format!("fail to open websocket: {:?}", e)))?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+
+        // wait for open, or for close if the server can not be reached
+        let (open_tx, open_rx) = oneshot::channel::<bool>();
+        let open_tx = Rc::new(RefCell::new(Some(open_tx)));
+        let opened_tx = open_tx.clone();
+        let on_open = Closure::<dyn FnMut(Event)>::new(move |_: Event| {
+            if let Some(tx) = opened_tx.borrow_mut().take() {
+                tx.send(true).unwrap_or(());
+            }
+        });
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+
+        let shared = Rc::new(TransportShared {
+            seq: Cell::new(0),
+            requests: RefCell::new(HashMap::new()),
+            closed: Cell::new(false),
+            negotiated: RefCell::new(None),
+        });
+        let close_shared = shared.clone();
+        let on_close = Closure::<dyn FnMut(CloseEvent)>::new(move |e: CloseEvent| {
+            console::log_1(&format!("websocket closed, code={}, reason={}", e.code(), e.reason()).into());
+            close_shared.closed.set(true);
+            // fail the pending requests now rather than at their timeout
+            close_shared.requests.borrow_mut().clear();
+            if let Some(tx) = open_tx.borrow_mut().take() {
+                tx.send(false).unwrap_or(());
+            }
+        });
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        let message_shared = shared.clone();
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |e: MessageEvent| {
+            if let Ok(buffer) = e.data().dyn_into::<js_sys::ArrayBuffer>() {
+                message_shared.on_message(Uint8Array::new(&buffer).to_vec());
+            }
+        });
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        let opened = open_rx.await.unwrap_or(false);
+        socket.set_onopen(None);
+        drop(on_open);
+        if !opened {
+            return Err(MpcError::new(MpcErrorKind::Unavailable, format!("fail to connect {}", ws_url)));
+        }
+
+        let transport = BrowserTransport {
+            identity_id,
+            socket,
+            shared,
+            _on_message: on_message,
+            _on_close: on_close,
+        };
+        transport.negotiate().await;
+        Ok(transport)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        !self.shared.closed.get()
+    }
+
+    pub fn close(&self) {
+        self.socket.close().unwrap_or(());
+    }
+
+    // agree on the versions with the server, keep v1 if it fails
+    async fn negotiate(&self) {
+        let scopes = [0, MPC_SCOPE_SECP256K1ECDSA, MPC_SCOPE_ED25519EDDSA].iter()
+            .map(|scope| ScopeVersions { scope: *scope, versions: PROTOCOL_VERSIONS.to_vec() })
+            .collect();
+        let negotiate_msg = NegotiateMsg { msg_versions: MSG_VERSIONS.to_vec(), scopes };
+        let rsp = self.send_req(REQ_CODE_NEGOTIATE, serde_json::to_vec(&negotiate_msg).unwrap(), NEGOTIATE_TIMEOUT_MS, MSG_VERSION_V1).await;
+        if rsp.is_err() {
+            console::warn_1(&format!("fail to negotiate versions, use v1: {}", rsp.err().unwrap()).into());
+            return;
+        }
+        let rsp = rsp.unwrap();
+        if rsp.action_code != RSP_CODE_SUCCESS {
+            console::warn_1(&format!("fail to negotiate versions, use v1: {}", rsp.error_msg).into());
+            return;
+        }
+        if let Ok(negotiated) = serde_json::from_slice::<NegotiatedMsg>(&rsp.body) {
+            *self.shared.negotiated.borrow_mut() = Some(negotiated);
+        }
+    }
+
+    async fn send_req(&self, req_code: u32, req_body: Vec<u8>, timeout_ms: u32, msg_version: u16) -> Result<MsgWrapper, MpcError> {
+        if self.shared.closed.get() {
+            return Err(MpcError::new(MpcErrorKind::Disconnected, "not connected"));
+        }
+        let seq = self.shared.seq.get();
+        self.shared.seq.set(seq.wrapping_add(1));
+        let req = MsgWrapper {
+            seq,
+            timestamp: get_tsp(),
+            action: MSG_ACTION_REQ,
+            action_code: req_code,
+            body: req_body,
+            error_msg: "".to_string(),
+            notice_id: "".to_string(),
+            version: msg_version,
+            error_code: 0,
+        };
+        let req_bytes = encode_msg(&req)
+            .map_err(|e| MpcError::new(MpcErrorKind::MalformedMessage, format!("serialize req_msg error:{}", e)))?;
+
+        let (rsp_tx, rsp_rx) = oneshot::channel();
+        self.shared.requests.borrow_mut().insert(seq, rsp_tx);
+        if self.socket.send_with_u8_array(&req_bytes).is_err() {
+            self.shared.requests.borrow_mut().remove(&seq);
+            return Err(MpcError::new(MpcErrorKind::Disconnected, "fail to send on the websocket"));
+        }
+
+        match select(rsp_rx, sleep(timeout_ms)).await {
+            Either::Left((Ok(rsp), _)) => Ok(rsp),
+            // the rsp_tx was dropped by on_close
+            Either::Left((Err(_canceled), _)) => Err(MpcError::new(MpcErrorKind::Disconnected, "connection lost before the rsp")),
+            Either::Right(_) => {
+                self.shared.requests.borrow_mut().remove(&seq);
+                Err(MpcError::new(MpcErrorKind::TransportTimeout, "timeout"))
+            }
+        }
+    }
+}
+
+impl TransportShared {
+    fn on_message(&self, bytes: Vec<u8>) {
+        let parse_result = decode_msg(&bytes);
+        if parse_result.is_err() {
+            console::warn_1(&format!("parse server binary to MsgWrapper fail, err={}", parse_result.err().unwrap()).into());
+            return;
+        }
+        let msg_wrapper = parse_result.unwrap();
+        // notices are not subscribed by the browser client
+        if msg_wrapper.action != MSG_ACTION_RSP {
+            return;
+        }
+        let option_tx = self.requests.borrow_mut().remove(&msg_wrapper.seq);
+        if let Some(tx) = option_tx {
+            tx.send(msg_wrapper).unwrap_or(());
+        }
+    }
+}
+
+impl Party1Transport for BrowserTransport {
+    fn identity_id(&self) -> &str {
+        &self.identity_id
+    }
+
+    fn protocol_version(&self, scope: u8) -> u16 {
+        let negotiated = self.shared.negotiated.borrow();
+        negotiated.as_ref()
+            .and_then(|n| n.scopes.iter().find(|s| s.scope == scope))
+            .map(|s| s.version)
+            .unwrap_or(MPC_PROTOCOL_V1)
+    }
+
+    fn msg_version(&self) -> u16 {
+        self.shared.negotiated.borrow().as_ref().map(|n| n.msg_version).unwrap_or(MSG_VERSION_V1)
+    }
+
+    fn send_mpc22(&self, _step: u8, mpc22_bytes: Vec<u8>, msg_version: u16) -> TransportFuture<'_, Result<MsgWrapper, MpcError>> {
+        Box::pin(self.send_req(REQ_CODE_MPC22, mpc22_bytes, REQUEST_TIMEOUT_MS, msg_version))
+    }
+}
+
+impl Drop for BrowserTransport {
+    fn drop(&mut self) {
+        self.socket.set_onmessage(None);
+        self.socket.set_onclose(None);
+        self.socket.close().unwrap_or(());
+    }
+}
+
+// setTimeout of the window or the worker
+async fn sleep(ms: u32) {
+    let promise = Promise::new(&mut |resolve, _reject| {
+        let set_timeout = Reflect::get(&js_sys::global(), &JsValue::from_str("setTimeout"))
+            .ok()
+            .and_then(|f| f.dyn_into::<Function>().ok());
+        if let Some(set_timeout) = set_timeout {
+            set_timeout.call2(&JsValue::NULL, &resolve, &JsValue::from(ms)).unwrap_or(JsValue::UNDEFINED);
+        }
+    });
+    JsFuture::from(promise).await.unwrap_or(JsValue::UNDEFINED);
+}
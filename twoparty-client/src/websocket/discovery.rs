@@ -0,0 +1,119 @@
+use std::sync::LazyLock;
+use std::time::Duration;
+use serde::de::DeserializeOwned;
+use url::Url;
+use common::socketmsg::{Capabilities, WsConfig};
+
+const CONNECT_TIMEOUT_MS: u64 = 5_000;
+const REQUEST_TIMEOUT_MS: u64 = 10_000;
+
+// an unreachable or stalled server fails the fetch instead of hanging the connect
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .connect_timeout(Duration::from_millis(CONNECT_TIMEOUT_MS))
+        .timeout(Duration::from_millis(REQUEST_TIMEOUT_MS))
+        .build()
+        .expect("fail to build http client")
+});
+
+// the documents mpc-server serves besides the websocket, fetched before connecting
+#[derive(Debug, Clone)]
+pub struct ServerProfile {
+    pub ws_config: WsConfig,
+    pub capabilities: Capabilities,
+}
+
+impl ServerProfile {
+    // /ws-config & /capabilities are resolved against the ws url, e.g. wss://host/mpc/ws -> https://host/mpc/capabilities
+    pub async fn fetch(ws_url: &str) -> Result<ServerProfile, String> {
+        let ws_config = get_json::<WsConfig>(&http_url(ws_url, "ws-config")?).await?;
+        let capabilities = get_json::<Capabilities>(&http_url(ws_url, "capabilities")?).await?;
+        Ok(ServerProfile { ws_config, capabilities })
+    }
+
+    // the heartbeat must be shorter than the idle timeout of the server
+    pub fn heartbeat_sec(&self) -> u8 {
+        let interval = self.ws_config.ws_client_interval;
+        let idle = self.ws_config.ws_server_idle;
+        if interval > 0 && interval < idle {
+            return interval;
+        }
+        (idle / 2).max(1)
+    }
+
+    pub fn check_command(&self, scope: u8, command: u8) -> Result<(), String> {
+        if !self.capabilities.supports(scope, command) {
+            return Err(format!("command {} of scope {} is not supported by the server", command, scope));
+        }
+        Ok(())
+    }
+}
+
+fn http_url(ws_url: &str, path: &str) -> Result<String, String> {
+    let parse_result = Url::parse(ws_url);
+    if parse_result.is_err() {
+        return Err(parse_result.err().unwrap().to_string());
+    }
+    let ws_url = parse_result.unwrap();
+    let scheme = match ws_url.scheme() {
+        "ws" => "http",
+        "wss" => "https",
+        other => return Err(format!("unsupported scheme {}", other)),
+    };
+    let mut http_url = ws_url.join(path).unwrap();
+    http_url.set_query(None);
+    http_url.set_scheme(scheme).unwrap();
+    Ok(http_url.to_string())
+}
+
+async fn get_json<T: DeserializeOwned>(url: &str) -> Result<T, String> {
+    let rsp_result = HTTP_CLIENT.get(url).send().await;
+    if rsp_result.is_err() {
+        return Err(format!("fail to get {}: {}", url, rsp_result.err().unwrap()));
+    }
+    let rsp = rsp_result.unwrap();
+    if !rsp.status().is_success() {
+        return Err(format!("fail to get {}: status={}", url, rsp.status()));
+    }
+    let json_result = rsp.json::<T>().await;
+    if json_result.is_err() {
+        return Err(format!("fail to parse {}: {}", url, json_result.err().unwrap()));
+    }
+    Ok(json_result.unwrap())
+}
+
+
+#[cfg(test)]
+mod test {
+    use common::socketmsg::{Capabilities, ScopeCapabilities, WsConfig};
+    use common::socketmsg::types::{MPC_EXPORT, MPC_IDENTITY_RECOVER, MPC_SCOPE_ED25519EDDSA, MPC_SCOPE_SECP256K1ECDSA, MPC_SIGN};
+    use crate::websocket::discovery::{http_url, ServerProfile};
+
+    #[test]
+    fn test_http_url() {
+        assert_eq!(http_url("ws://localhost:8822/ws", "ws-config").unwrap(), "http://localhost:8822/ws-config");
+        assert_eq!(http_url("wss://mpc.example.com/mpc/ws?token=1", "capabilities").unwrap(), "https://mpc.example.com/mpc/capabilities");
+        assert!(http_url("http://localhost:8822/ws", "ws-config").is_err());
+    }
+
+    #[test]
+    fn test_server_profile() {
+        let mut profile = ServerProfile {
+            ws_config: WsConfig { ws_server_idle: 30, ws_client_interval: 10 },
+            capabilities: Capabilities {
+                req_codes: vec![],
+                scopes: vec![
+                    ScopeCapabilities { scope: MPC_SCOPE_SECP256K1ECDSA, commands: vec![MPC_SIGN, MPC_EXPORT, MPC_IDENTITY_RECOVER], protocol_versions: vec![1] },
+                    ScopeCapabilities { scope: MPC_SCOPE_ED25519EDDSA, commands: vec![MPC_SIGN], protocol_versions: vec![1] },
+                ],
+            },
+        };
+        assert_eq!(profile.heartbeat_sec(), 10);
+        profile.ws_config.ws_client_interval = 40;
+        assert_eq!(profile.heartbeat_sec(), 15);
+
+        assert!(profile.check_command(MPC_SCOPE_SECP256K1ECDSA, MPC_EXPORT).is_ok());
+        assert!(profile.check_command(MPC_SCOPE_ED25519EDDSA, MPC_EXPORT).is_err());
+        assert!(profile.check_command(0, MPC_IDENTITY_RECOVER).is_ok());
+    }
+}
@@ -1,20 +1,30 @@
 mod request_holder;
+pub mod pool;
+pub mod discovery;
 #[cfg(test)]
 mod test;
 
 
-use std::error;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use std::time::Duration;
 use futures_util::{SinkExt, StreamExt};
-use tokio_tungstenite::{connect_async};
+use futures_util::stream::SplitSink;
+use tokio_tungstenite::{connect_async_with_config, MaybeTlsStream, WebSocketStream};
 use tokio_tungstenite::tungstenite::{Message};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::http::header::AUTHORIZATION;
 use url::Url;
 use common::get_tsp;
-use common::socketmsg::{MSG_ACTION_REQ, MSG_ACTION_RSP, MSG_ACTION_NOTICE, MsgWrapper};
+use common::socketmsg::codec::{decode_detail, decode_msg, encode_detail, encode_msg};
+use common::socketmsg::{MSG_ACTION_ACK, MSG_ACTION_REQ, MSG_ACTION_RSP, MSG_ACTION_NOTICE, MSG_VERSION_V1, MSG_VERSIONS, MsgWrapper, NegotiatedMsg, NegotiateMsg, Notice, REQ_CODE_MPC22, REQ_CODE_NEGOTIATE, REQ_CODE_SUBSCRIBE_NOTICE, RSP_CODE_SUCCESS, ScopeVersions, SubscribeNoticeMsg};
+use common::socketmsg::types::{MPC_PROTOCOL_V1, MPC_PROTOCOL_V2, MPC_SCOPE_ED25519EDDSA, MPC_SCOPE_SECP256K1ECDSA};
 use std::sync::atomic::{AtomicU32, Ordering};
-use tokio::sync::{mpsc, mpsc::UnboundedSender};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, mpsc::Receiver, mpsc::Sender, watch};
 
 
 use tokio::task::{AbortHandle};
@@ -22,127 +32,344 @@ use tokio::task::{AbortHandle};
 
 use tokio::time;
 
-use common::errors::GenericError;
-use crate::websocket::request_holder::{drop_req_tx, register_request, resolve_request};
+use common::errors::{MpcError, MpcErrorKind};
+use crate::mpc::transport::{Party1Transport, TransportFuture};
+use crate::websocket::request_holder::RequestRegistry;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// the protocol versions mpc-client runs for every scope, scope 0 is for commands not bound to a scope
+const PROTOCOL_VERSIONS: [u16; 2] = [MPC_PROTOCOL_V1, MPC_PROTOCOL_V2];
+// servers older than negotiation do not reply it, v1 is then used
+const NEGOTIATE_TIMEOUT_MS: u64 = 5_000;
+
+// missing fields take the default values when deserialized
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ClientOptions {
+    // outbound msgs queued before send_req waits for the ws_sender
+    pub outbound_queue: usize,
+    // bytes of a msg in either direction, should match ws_limits of the server
+    pub max_message_size: usize,
+    // used when send_req is called without a timeout
+    pub request_timeout_ms: u64,
+    pub reconnect: ReconnectPolicy,
+    pub step_retry: StepRetryPolicy,
+    // offer the bincode msg version, turn it off to keep json on the wire for debugging
+    pub binary_encoding: bool,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        ClientOptions {
+            outbound_queue: 32,
+            max_message_size: 4 << 20,
+            request_timeout_ms: 20_000,
+            reconnect: ReconnectPolicy::default(),
+            step_retry: StepRetryPolicy::default(),
+            binary_encoding: true,
+        }
+    }
+}
+
+// reconnect after the connection drops, the delay doubles from initial_backoff_ms up to max_backoff_ms
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ReconnectPolicy {
+    pub enabled: bool,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    // 0 means retry forever
+    pub max_attempts: u32,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy { enabled: true, initial_backoff_ms: 500, max_backoff_ms: 30_000, max_attempts: 0 }
+    }
+}
+
+// resend a timed out or disconnected mpc step, party2 replies an exact resend with the rsp it cached
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct StepRetryPolicy {
+    pub max_retries: u8,
+    // wait for the reconnection before resending a step, party2 keeps the session for session_grace_secs
+    pub resume_timeout_ms: u64,
+}
+
+impl Default for StepRetryPolicy {
+    fn default() -> Self {
+        StepRetryPolicy { max_retries: 2, resume_timeout_ms: 30_000 }
+    }
+}
+
+// a notice pushed by the server
+#[derive(Serialize, Debug, Clone)]
+pub struct NoticeEvent {
+    pub notice_id: String,
+    // NOTICE_ROTATION_REQUIRED | NOTICE_SHARE_FROZEN | NOTICE_POLICY_CHANGED
+    pub notice_type: u32,
+    pub notice: Notice,
+}
+
+// called on the connection task, the notice is acknowledged after it returns.
+// a notice may be delivered again if the ack is lost, dedupe by notice_id
+pub type NoticeCallback = Arc<dyn Fn(NoticeEvent) + Send + Sync>;
+
+// shared by SyncClient and its connection task, which outlives single connections
+struct ClientShared {
+    identity_id: String,
+    seq: AtomicU32,
+    // held from taking a seq until the req is queued, so reqs reach the server in seq order
+    send_lock: tokio::sync::Mutex<()>,
+    requests: RequestRegistry,
+    // send ws_message by tx
+    tx: Sender<Message>,
+    max_message_size: usize,
+    request_timeout_ms: u64,
+    step_retry: StepRetryPolicy,
+    binary_encoding: bool,
+    connected_rx: watch::Receiver<bool>,
+    notice_callback: RwLock<Option<NoticeCallback>>,
+    // none before the negotiation or if the server does not negotiate
+    negotiated: RwLock<Option<NegotiatedMsg>>,
+}
 
 pub struct SyncClient {
-    seq: Arc<AtomicU32>,
     pub identity_id: String,
-    // send ws_message by tx
-    tx: UnboundedSender<Message>,
+    shared: Arc<ClientShared>,
     abort_handles: Vec<AbortHandle>,
 }
 
 impl SyncClient {
-    pub async fn connect_server(identity_id: String, url: String, heartbeat_sec: u8) -> Result<Self, String> {
-        let parsed_url_result = Url::parse(&url);
-        if parsed_url_result.is_err() {
-            return Err(parsed_url_result.unwrap_err().to_string());
-        }
-        let connect_result = connect_async(
-            parsed_url_result.unwrap()
-        ).await;
-        if connect_result.is_err() {
-            return Err(connect_result.unwrap_err().to_string());
-        }
-        let (ws_stream, _) = connect_result.unwrap();
+    pub async fn connect_server(identity_id: String, url: String, heartbeat_sec: u8) -> Result<Self, MpcError> {
+        SyncClient::connect_server_with_token(identity_id, url, heartbeat_sec, None).await
+    }
 
-        let (mut sender, mut receiver) = ws_stream.split();
-        let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
-        let (heartbeat_tx, mut heartbeat_rx) = mpsc::unbounded_channel::<u8>();
+    // the server binds the connection to the subject of access_token, when authentication is enabled
+    pub async fn connect_server_with_token(identity_id: String, url: String, heartbeat_sec: u8, access_token: Option<String>) -> Result<Self, MpcError> {
+        SyncClient::connect_server_with_options(identity_id, url, heartbeat_sec, access_token, ClientOptions::default()).await
+    }
+
+    // the first connection must succeed, later ones are reconnected by options.reconnect
+    pub async fn connect_server_with_options(identity_id: String, url: String, heartbeat_sec: u8, access_token: Option<String>, options: ClientOptions) -> Result<Self, MpcError> {
+        let ws_stream = open_socket(&url, &access_token, options.max_message_size).await
+            .map_err(|e| MpcError::new(MpcErrorKind::Unavailable, e))?;
 
-        // spawn a task to send heartbeat ping
-        let c_tx = tx.clone();
+        let (tx, rx) = mpsc::channel::<Message>(options.outbound_queue);
+        let (connected_tx, connected_rx) = watch::channel(true);
+        let shared = Arc::new(ClientShared {
+            identity_id: identity_id.clone(),
+            seq: AtomicU32::default(),
+            send_lock: tokio::sync::Mutex::new(()),
+            requests: RequestRegistry::default(),
+            tx: tx.clone(),
+            max_message_size: options.max_message_size,
+            request_timeout_ms: options.request_timeout_ms,
+            step_retry: options.step_retry.clone(),
+            binary_encoding: options.binary_encoding,
+            connected_rx: connected_rx.clone(),
+            notice_callback: RwLock::new(None),
+            negotiated: RwLock::new(None),
+        });
+
+        // spawn a task to send heartbeat ping, both ping_msg & req_msg keep a connection alive at server
         let heartbeat_task = tokio::spawn(async move {
-            // both ping_msg & req_msg will be used to judge if a connection is idle at server,
-            // so send "1" to heartbeat_rx when send a req_msg;
-            // if heartbeat_rx get "2", the heartbeat task should stop
             loop {
-                match time::timeout(Duration::from_secs(heartbeat_sec as u64), heartbeat_rx.recv()).await {
-                    Err(_elapsed) => {
-                        // send ping
-                        println!("client send ping");
-                        c_tx.send(Message::Ping(vec![1])).unwrap_or(());
-                    }
-                    Ok(msg_option) => {
-                        if msg_option.is_none() {
-                            // channel already closed
-                            println!("heartbeat channel is closed");
-                            return;
-                        }
-                        let msg = msg_option.unwrap();
-                        if msg == 2 {
-                            // stop the heartbeat
-                            return;
-                        }
-                    }
+                time::sleep(Duration::from_secs(heartbeat_sec as u64)).await;
+                if *connected_rx.borrow() {
+                    println!("client send ping");
+                    // skip the ping if the queue is full, queued msgs keep the connection alive
+                    tx.try_send(Message::Ping(vec![1])).unwrap_or(());
                 }
             }
         });
 
-        // spawn a task to manage ws_sender
-        let ws_sender_task = tokio::spawn(async move {
-            loop {
-                let option_msg = rx.recv().await;
-                if option_msg.is_none() {
-                    // channel closed, stop the consumer task, stop heartbeat
-                    heartbeat_tx.send(2).unwrap_or(());
-                    return;
+        // spawn a task to serve the connection & reconnect it
+        let connection = Connection {
+            shared: shared.clone(),
+            url,
+            access_token,
+            options,
+            connected_tx,
+        };
+        let connection_task = tokio::spawn(connection.run(ws_stream, rx));
+
+        let sync_client = SyncClient {
+            identity_id,
+            shared,
+            abort_handles: vec![heartbeat_task.abort_handle(), connection_task.abort_handle()],
+        };
+        sync_client.shared.negotiate().await;
+        Ok(sync_client)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        *self.shared.connected_rx.borrow()
+    }
+
+    // false if still disconnected after timeout_ms
+    pub async fn wait_connected(&self, timeout_ms: u64) -> bool {
+        let mut connected_rx = self.shared.connected_rx.clone();
+        let wait_result = time::timeout(Duration::from_millis(timeout_ms), async move {
+            while !*connected_rx.borrow_and_update() {
+                if connected_rx.changed().await.is_err() {
+                    return false;
                 }
-                let msg = option_msg.unwrap();
-                sender.send(msg).await.unwrap_or(());
             }
-        });
+            true
+        }).await;
+        wait_result.unwrap_or(false)
+    }
+
+    // receive notices of identity_id, including those not acknowledged on former connections.
+    // the subscription is renewed on every reconnection
+    pub async fn subscribe_notice(&self, callback: NoticeCallback) -> Result<(), MpcError> {
+        *self.shared.notice_callback.write().unwrap() = Some(callback);
+        self.shared.subscribe_notice().await
+    }
+
+    // the step protocol version agreed with the server for the scope, v1 if not negotiated
+    pub fn protocol_version(&self, scope: u8) -> u16 {
+        let negotiated = self.shared.negotiated.read().unwrap();
+        negotiated.as_ref()
+            .and_then(|n| n.scopes.iter().find(|s| s.scope == scope))
+            .map(|s| s.version)
+            .unwrap_or(MPC_PROTOCOL_V1)
+    }
+
+    pub fn negotiated_versions(&self) -> Option<NegotiatedMsg> {
+        self.shared.negotiated.read().unwrap().clone()
+    }
 
-        // spawn a task to receive msg
-        let ws_receiver_task = tokio::spawn(async move {
+    pub fn step_retry(&self) -> &StepRetryPolicy {
+        &self.shared.step_retry
+    }
+
+    pub(crate) fn has_notice_subscription(&self) -> bool {
+        self.shared.notice_callback.read().unwrap().is_some()
+    }
+
+    // fails with MpcErrorKind::Disconnected if the connection is down or drops before the rsp,
+    // MpcErrorKind::TransportTimeout if no rsp within the timeout
+    pub async fn send_req(&self, req_code: u32, req_body: Vec<u8>, option_timeout: Option<u64>) -> Result<MsgWrapper, MpcError> {
+        self.shared.send_req(req_code, req_body, option_timeout, self.msg_version()).await
+    }
+
+    // req_body must be encoded in msg_version, see codec
+    pub async fn send_versioned_req(&self, req_code: u32, req_body: Vec<u8>, option_timeout: Option<u64>, msg_version: u16) -> Result<MsgWrapper, MpcError> {
+        self.shared.send_req(req_code, req_body, option_timeout, msg_version).await
+    }
+
+    // the envelope version agreed with the server, v1 if not negotiated
+    pub fn msg_version(&self) -> u16 {
+        self.shared.msg_version()
+    }
+}
+
+// resend a timed out step, or on the new connection after a disconnection, party2 resumes the session by its session_id
+impl Party1Transport for SyncClient {
+    fn identity_id(&self) -> &str {
+        &self.identity_id
+    }
+
+    fn protocol_version(&self, scope: u8) -> u16 {
+        SyncClient::protocol_version(self, scope)
+    }
+
+    fn msg_version(&self) -> u16 {
+        SyncClient::msg_version(self)
+    }
+
+    fn send_mpc22(&self, step: u8, mpc22_bytes: Vec<u8>, msg_version: u16) -> TransportFuture<'_, Result<MsgWrapper, MpcError>> {
+        Box::pin(async move {
+            let step_retry = self.step_retry();
+            let mut retries = 0;
             loop {
-                match receiver.next().await {
-                    Some(msg_result) => {
-                        if msg_result.is_err() {
-                            println!("receive msg from server error:{}", msg_result.err().unwrap());
-                            continue;
+                let rsp = self.send_versioned_req(REQ_CODE_MPC22, mpc22_bytes.clone(), None, msg_version).await;
+                if rsp.is_err() {
+                    let err = rsp.err().unwrap();
+                    let disconnected = err.kind == MpcErrorKind::Disconnected;
+                    if retries < step_retry.max_retries && (err.kind == MpcErrorKind::TransportTimeout || disconnected) {
+                        if disconnected && !self.wait_connected(step_retry.resume_timeout_ms).await {
+                            return Err(err);
                         }
-                        let msg = msg_result.unwrap();
-                        if msg.is_binary() {
-                            // resolve request promise or dispatch notice
-                            let parse_result = serde_json::from_slice::<MsgWrapper>(&msg.into_data());
-                            if let Ok(msg_wrapper) = parse_result {
-                                let action = msg_wrapper.action;
-                                match action {
-                                    MSG_ACTION_RSP => {
-                                        resolve_request(msg_wrapper).await;
-                                    }
-                                    MSG_ACTION_NOTICE => {}
-                                    _ => {}
-                                }
-                            } else {
-                                println!("parse server binary to MsgWrapper fail, err={}", parse_result.err().unwrap());
-                            }
-                        } else if msg.is_close() {
-                            println!("client get close");
-                        }
-                    }
-                    None => {
-                        println!("connection already closed");
-                        return;
+                        retries += 1;
+                        println!("step {} fail: {}, resend it, retries={}", step, err, retries);
+                        continue;
                     }
+                    return Err(err);
                 }
+                return Ok(rsp.unwrap());
             }
-        });
+        })
+    }
+}
 
-        let sync_client = SyncClient {
-            seq: Arc::new(Default::default()),
-            identity_id,
-            tx,
-            abort_handles: vec![heartbeat_task.abort_handle(), ws_sender_task.abort_handle(), ws_receiver_task.abort_handle()],
-            // abort_handles: vec![heartbeat_task, ws_sender_task, ws_receiver_task],
-        };
-        Ok(sync_client)
+impl ClientShared {
+    fn is_connected(&self) -> bool {
+        *self.connected_rx.borrow()
+    }
+
+    fn next_seq(&self) -> u32 {
+        self.seq.fetch_add(1, Ordering::SeqCst)
+    }
+
+    async fn subscribe_notice(&self) -> Result<(), MpcError> {
+        let subscribe_msg = SubscribeNoticeMsg { identity_id: self.identity_id.clone() };
+        let msg_version = self.msg_version();
+        let subscribe_bytes = encode_detail(msg_version, &subscribe_msg)?;
+        let rsp = self.send_req(REQ_CODE_SUBSCRIBE_NOTICE, subscribe_bytes, None, msg_version).await?;
+        if let Some(err) = MpcError::from_rsp(&rsp) {
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn msg_version(&self) -> u16 {
+        self.negotiated.read().unwrap().as_ref().map(|n| n.msg_version).unwrap_or(MSG_VERSION_V1)
+    }
+
+    // agree on the versions with the server, keep v1 if it fails.
+    // sent in v1, which every server reads
+    async fn negotiate(&self) {
+        let scopes = [0, MPC_SCOPE_SECP256K1ECDSA, MPC_SCOPE_ED25519EDDSA].iter()
+            .map(|scope| ScopeVersions { scope: *scope, versions: PROTOCOL_VERSIONS.to_vec() })
+            .collect();
+        let mut msg_versions = vec![MSG_VERSION_V1];
+        if self.binary_encoding {
+            msg_versions = MSG_VERSIONS.to_vec();
+        }
+        let negotiate_msg = NegotiateMsg { msg_versions, scopes };
+        let rsp = self.send_req(REQ_CODE_NEGOTIATE, serde_json::to_vec(&negotiate_msg).unwrap(), Some(NEGOTIATE_TIMEOUT_MS), MSG_VERSION_V1).await;
+        if rsp.is_err() {
+            println!("fail to negotiate versions, use v1: {}", rsp.err().unwrap());
+            return;
+        }
+        let rsp = rsp.unwrap();
+        if rsp.action_code != RSP_CODE_SUCCESS {
+            println!("fail to negotiate versions, use v1: {}", rsp.error_msg);
+            return;
+        }
+        match serde_json::from_slice::<NegotiatedMsg>(&rsp.body) {
+            Ok(negotiated) => {
+                *self.negotiated.write().unwrap() = Some(negotiated);
+            }
+            Err(e) => {
+                println!("fail to parse negotiated versions, use v1: {}", e);
+            }
+        }
     }
 
-    pub async fn send_req(&self, req_code: u32, req_body: Vec<u8>, option_timeout: Option<u64>) -> Result<MsgWrapper, Box<dyn error::Error>> {
-        let seq = self.seq.fetch_add(1, Ordering::SeqCst);
+    async fn send_req(&self, req_code: u32, req_body: Vec<u8>, option_timeout: Option<u64>, msg_version: u16) -> Result<MsgWrapper, MpcError> {
+        if !self.is_connected() {
+            return Err(MpcError::new(MpcErrorKind::Disconnected, "not connected"));
+        }
+        // concurrent reqs, e.g. a resent step & the negotiation after a reconnection, must not be queued out
+        // of seq order, the server refuses a seq lower than the last one
+        let send_guard = self.send_lock.lock().await;
+        let seq = self.next_seq();
         let req = MsgWrapper {
             seq,
             timestamp: get_tsp(),
@@ -151,48 +378,236 @@ impl SyncClient {
             body: req_body,
             error_msg: "".to_string(),
             notice_id: "".to_string(),
+            version: msg_version,
+            error_code: 0,
         };
 
         // serialize req_msg
-        let req_bytes = serde_json::to_vec(&req);
+        let req_bytes = encode_msg(&req);
         if req_bytes.is_err() {
-            let err = GenericError(format!("serialize req_msg error:{}", req_bytes.err().unwrap()));
-            return Err(Box::new(err));
+            let err = format!("serialize req_msg error:{}", req_bytes.err().unwrap());
+            return Err(MpcError::new(MpcErrorKind::MalformedMessage, err));
+        }
+
+        let req_bytes = req_bytes.unwrap();
+        if req_bytes.len() > self.max_message_size {
+            let err = format!("req_msg size {} exceeds {}", req_bytes.len(), self.max_message_size);
+            return Err(MpcError::new(MpcErrorKind::InvalidRequest, err));
         }
 
         // register request
-        let req_rx = register_request(seq).await;
-        // send msg to server
-        self.tx.send(Message::from(req_bytes.unwrap()))?;
+        let req_rx = self.requests.register_request(seq).await;
+        // pending requests are dropped when the connection drops, check again after registering
+        if !self.is_connected() {
+            self.requests.drop_req_tx(seq).await;
+            return Err(MpcError::new(MpcErrorKind::Disconnected, "not connected"));
+        }
+        // send msg to server, wait if the outbound queue is full
+        if self.tx.send(Message::from(req_bytes)).await.is_err() {
+            self.requests.drop_req_tx(seq).await;
+            return Err(MpcError::new(MpcErrorKind::Disconnected, "connection task exited"));
+        }
+        drop(send_guard);
 
         // poll rsp_msg
-        let mut timeout_ms = 20_000;
+        let mut timeout_ms = self.request_timeout_ms;
         if let Some(_timeout) = option_timeout {
             timeout_ms = option_timeout.unwrap();
         }
         match time::timeout(Duration::from_millis(timeout_ms), req_rx).await {
             Err(_elapsed) => {
-                drop_req_tx(seq).await;
-                Err(Box::new(GenericError("timeout".to_string())))
+                self.requests.drop_req_tx(seq).await;
+                Err(MpcError::new(MpcErrorKind::TransportTimeout, "timeout"))
             }
             Ok(msg_result) => {
                 if let Ok(msg) = msg_result {
                     // resolve_request will drop the req_tx
                     return Ok(msg);
                 }
-                drop_req_tx(seq).await;
-                let err = GenericError(format!("req_rx recv error={}", msg_result.err().unwrap()));
-                Err(Box::new(err))
+                // the req_tx was dropped by the connection task
+                Err(MpcError::new(MpcErrorKind::Disconnected, "connection lost before the rsp"))
+            }
+        }
+    }
+}
+
+struct Connection {
+    shared: Arc<ClientShared>,
+    url: String,
+    access_token: Option<String>,
+    options: ClientOptions,
+    connected_tx: watch::Sender<bool>,
+}
+
+impl Connection {
+    async fn run(self, mut ws_stream: WsStream, mut rx: Receiver<Message>) {
+        loop {
+            let reason = self.serve(ws_stream, &mut rx).await;
+            println!("connection lost: {}", reason);
+            self.connected_tx.send_replace(false);
+            // fail the pending requests now rather than at their timeout
+            self.shared.requests.drop_all().await;
+            if !self.options.reconnect.enabled {
+                return;
+            }
+            let option_stream = self.reconnect().await;
+            if option_stream.is_none() {
+                println!("give up reconnecting to {}", self.url);
+                return;
+            }
+            ws_stream = option_stream.unwrap();
+            // drop msgs queued while disconnected, e.g. pings
+            while rx.try_recv().is_ok() {}
+            self.connected_tx.send_replace(true);
+
+            // the rsp is received by serve, so negotiate & subscribe on another task.
+            // the server may have been upgraded, a resent step keeps the version of its session
+            let shared = self.shared.clone();
+            tokio::spawn(async move {
+                shared.negotiate().await;
+                if shared.notice_callback.read().unwrap().is_none() {
+                    return;
+                }
+                if let Err(e) = shared.subscribe_notice().await {
+                    println!("fail to renew the notice subscription: {}", e);
+                }
+            });
+        }
+    }
+
+    // return the reason once the connection drops
+    async fn serve(&self, ws_stream: WsStream, rx: &mut Receiver<Message>) -> String {
+        let (mut sender, mut receiver) = ws_stream.split();
+        loop {
+            tokio::select! {
+                option_msg = rx.recv() => {
+                    if option_msg.is_none() {
+                        return "client dropped".to_string();
+                    }
+                    if let Err(e) = sender.send(option_msg.unwrap()).await {
+                        return format!("send error: {}", e);
+                    }
+                }
+                option_incoming = receiver.next() => {
+                    match option_incoming {
+                        None => return "connection already closed".to_string(),
+                        Some(Err(e)) => return format!("receive error: {}", e),
+                        Some(Ok(msg)) => self.on_message(msg, &mut sender).await,
+                    }
+                }
+            }
+        }
+    }
+
+    async fn on_message(&self, msg: Message, sender: &mut SplitSink<WsStream, Message>) {
+        if msg.is_binary() {
+            // resolve request promise or dispatch notice
+            let parse_result = decode_msg(&msg.into_data());
+            if parse_result.is_err() {
+                println!("parse server binary to MsgWrapper fail, err={}", parse_result.err().unwrap());
+                return;
+            }
+            let msg_wrapper = parse_result.unwrap();
+            match msg_wrapper.action {
+                MSG_ACTION_RSP => {
+                    self.shared.requests.resolve_request(msg_wrapper).await;
+                }
+                MSG_ACTION_NOTICE => {
+                    let option_callback = self.shared.notice_callback.read().unwrap().clone();
+                    if let Some(callback) = option_callback {
+                        let notice_id = msg_wrapper.notice_id.clone();
+                        dispatch_notice(msg_wrapper, callback);
+                        let ack = MsgWrapper {
+                            seq: self.shared.next_seq(),
+                            timestamp: get_tsp(),
+                            action: MSG_ACTION_ACK,
+                            action_code: 0,
+                            body: vec![],
+                            error_msg: "".to_string(),
+                            notice_id,
+                            version: self.shared.msg_version(),
+                            error_code: 0,
+                        };
+                        if let Ok(ack_bytes) = ack.to_bytes() {
+                            sender.send(Message::from(ack_bytes)).await.unwrap_or(());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        } else if let Message::Close(option_frame) = msg {
+            // e.g. code=1009 if a msg exceeds max_message_size of the server
+            println!("client get close:{:?}", option_frame);
+        }
+    }
+
+    async fn reconnect(&self) -> Option<WsStream> {
+        let policy = &self.options.reconnect;
+        let mut backoff_ms = policy.initial_backoff_ms;
+        let mut attempts = 0;
+        while policy.max_attempts == 0 || attempts < policy.max_attempts {
+            attempts += 1;
+            time::sleep(Duration::from_millis(backoff_ms)).await;
+            match open_socket(&self.url, &self.access_token, self.options.max_message_size).await {
+                Ok(ws_stream) => {
+                    println!("reconnected to {} after {} attempts", self.url, attempts);
+                    return Some(ws_stream);
+                }
+                Err(e) => {
+                    println!("reconnect attempt {} fail: {}", attempts, e);
+                }
             }
+            backoff_ms = (backoff_ms * 2).min(policy.max_backoff_ms);
         }
+        None
     }
 }
 
+async fn open_socket(url: &str, access_token: &Option<String>, max_message_size: usize) -> Result<WsStream, String> {
+    let parsed_url_result = Url::parse(url);
+    if parsed_url_result.is_err() {
+        return Err(parsed_url_result.unwrap_err().to_string());
+    }
+    let request_result = parsed_url_result.unwrap().as_str().into_client_request();
+    if request_result.is_err() {
+        return Err(request_result.unwrap_err().to_string());
+    }
+    let mut request = request_result.unwrap();
+    if let Some(token) = access_token {
+        let header_result = HeaderValue::from_str(&format!("Bearer {}", token));
+        if header_result.is_err() {
+            return Err(header_result.unwrap_err().to_string());
+        }
+        request.headers_mut().insert(AUTHORIZATION, header_result.unwrap());
+    }
+    let ws_config = WebSocketConfig {
+        max_message_size: Some(max_message_size),
+        ..WebSocketConfig::default()
+    };
+    let connect_result = connect_async_with_config(request, Some(ws_config), false).await;
+    if connect_result.is_err() {
+        return Err(connect_result.unwrap_err().to_string());
+    }
+    let (ws_stream, _) = connect_result.unwrap();
+    Ok(ws_stream)
+}
+
+fn dispatch_notice(msg_wrapper: MsgWrapper, callback: NoticeCallback) {
+    let parse_result = decode_detail::<Notice>(msg_wrapper.version, &msg_wrapper.body);
+    if parse_result.is_err() {
+        println!("parse notice fail, notice_id={}, err={}", msg_wrapper.notice_id, parse_result.err().unwrap());
+        return;
+    }
+    callback(NoticeEvent {
+        notice_id: msg_wrapper.notice_id,
+        notice_type: msg_wrapper.action_code,
+        notice: parse_result.unwrap(),
+    });
+}
+
 // https://doc.rust-lang.org/reference/destructors.html
 impl Drop for SyncClient {
     fn drop(&mut self) {
         self.abort_handles.iter().for_each(|h| h.abort());
     }
 }
-
-
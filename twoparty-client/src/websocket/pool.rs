@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use common::errors::MpcError;
+use crate::websocket::{ClientOptions, SyncClient};
+
+#[derive(Clone, Debug)]
+pub struct PoolOptions {
+    // idle connections kept for one (url, identity_id, access_token)
+    pub max_idle_per_key: usize,
+    // idle connections older than this are closed instead of reused
+    pub max_idle_secs: u64,
+    pub heartbeat_sec: u8,
+    pub client_options: ClientOptions,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        PoolOptions { max_idle_per_key: 4, max_idle_secs: 300, heartbeat_sec: 10, client_options: ClientOptions::default() }
+    }
+}
+
+// connections are bound to the subject of access_token at the server, so the token is part of the key
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct PoolKey {
+    url: String,
+    identity_id: String,
+    access_token: Option<String>,
+}
+
+struct PoolInner {
+    options: PoolOptions,
+    idle: Mutex<HashMap<PoolKey, Vec<(SyncClient, Instant)>>>,
+}
+
+// reuses authenticated connections to the same server.
+// the server runs one mpc session per connection, so a connection is leased to one user at a time
+#[derive(Clone)]
+pub struct ClientPool {
+    inner: Arc<PoolInner>,
+}
+
+impl ClientPool {
+    pub fn new(options: PoolOptions) -> Self {
+        ClientPool {
+            inner: Arc::new(PoolInner { options, idle: Mutex::new(HashMap::new()) })
+        }
+    }
+
+    // lease an idle connection of the key, or connect a new one
+    pub async fn acquire(&self, identity_id: String, url: String, access_token: Option<String>) -> Result<PooledClient, MpcError> {
+        let key = PoolKey { url, identity_id, access_token };
+        if let Some(client) = self.inner.take_idle(&key) {
+            return Ok(PooledClient { client: Some(client), key, pool: self.inner.clone() });
+        }
+
+        let options = &self.inner.options;
+        let client = SyncClient::connect_server_with_options(
+            key.identity_id.clone(),
+            key.url.clone(),
+            options.heartbeat_sec,
+            key.access_token.clone(),
+            options.client_options.clone(),
+        ).await?;
+        Ok(PooledClient { client: Some(client), key, pool: self.inner.clone() })
+    }
+
+    pub fn idle_count(&self) -> usize {
+        self.inner.idle.lock().unwrap().values().map(|clients| clients.len()).sum()
+    }
+
+    // close all idle connections, leased ones are closed when released
+    pub fn clear(&self) {
+        self.inner.idle.lock().unwrap().clear();
+    }
+}
+
+impl PoolInner {
+    fn take_idle(&self, key: &PoolKey) -> Option<SyncClient> {
+        let max_idle = Duration::from_secs(self.options.max_idle_secs);
+        let mut idle = self.idle.lock().unwrap();
+        let clients = idle.get_mut(key)?;
+        // the most recently released first, stale & disconnected ones are dropped on the way
+        let mut found = None;
+        while let Some((client, released_at)) = clients.pop() {
+            if client.is_connected() && released_at.elapsed() < max_idle {
+                found = Some(client);
+                break;
+            }
+        }
+        if clients.is_empty() {
+            idle.remove(key);
+        }
+        found
+    }
+
+    fn release(&self, key: &PoolKey, client: SyncClient) {
+        // a notice subscription belongs to the lessee who made it
+        if !client.is_connected() || client.has_notice_subscription() {
+            return;
+        }
+        let mut idle = self.idle.lock().unwrap();
+        let clients = idle.entry(key.clone()).or_default();
+        if clients.len() < self.options.max_idle_per_key {
+            clients.push((client, Instant::now()));
+        }
+    }
+}
+
+// returned to the pool on drop
+pub struct PooledClient {
+    client: Option<SyncClient>,
+    key: PoolKey,
+    pool: Arc<PoolInner>,
+}
+
+impl PooledClient {
+    // take the connection out of the pool for good
+    pub fn detach(mut self) -> SyncClient {
+        self.client.take().unwrap()
+    }
+}
+
+impl Deref for PooledClient {
+    type Target = SyncClient;
+
+    fn deref(&self) -> &Self::Target {
+        self.client.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledClient {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            self.pool.release(&self.key, client);
+        }
+    }
+}
@@ -1,36 +1,43 @@
 use std::collections::HashMap;
-use std::sync::{Arc, LazyLock};
 
 use tokio::sync::{oneshot, Mutex};
 
 use common::socketmsg::MsgWrapper;
 
 
-#[allow(clippy::type_complexity)]
-static REQUEST_TXS: LazyLock<Arc<Mutex<HashMap<u32, oneshot::Sender<MsgWrapper>>>>> = LazyLock::new(|| {
-    Arc::new(Mutex::new(HashMap::new()))
-});
+// pending requests of one SyncClient, seq is only unique within a client
+#[derive(Default)]
+pub struct RequestRegistry {
+    req_txs: Mutex<HashMap<u32, oneshot::Sender<MsgWrapper>>>,
+}
 
-pub async fn register_request(seq: u32) -> oneshot::Receiver<MsgWrapper>{
-    let (req_tx, req_rx) = oneshot::channel::<MsgWrapper>();
+impl RequestRegistry {
+    pub async fn register_request(&self, seq: u32) -> oneshot::Receiver<MsgWrapper> {
+        let (req_tx, req_rx) = oneshot::channel::<MsgWrapper>();
 
-    // cache the req_tx
-    REQUEST_TXS.lock().await.insert(seq, req_tx);
-    req_rx
-}
+        // cache the req_tx
+        self.req_txs.lock().await.insert(seq, req_tx);
+        req_rx
+    }
 
+    pub async fn resolve_request(&self, rsp_msg: MsgWrapper) {
+        let seq = rsp_msg.seq;
+        let option_req_tx = self.req_txs.lock().await.remove(&seq);
+        if let Some(req_tx) = option_req_tx {
+            req_tx.send(rsp_msg).unwrap_or(());
+        } else {
+            println!("******** cna not find req_tx with rsp_msg={:?}", rsp_msg);
+        }
+    }
 
-pub async fn resolve_request(rsp_msg: MsgWrapper) {
-    let seq = rsp_msg.seq;
-    let option_req_tx = REQUEST_TXS.lock().await.remove(&seq);
-    if let Some(req_tx) = option_req_tx {
-        req_tx.send(rsp_msg).unwrap_or(());
-    } else {
-        println!("******** cna not find req_tx with rsp_msg={:?}", rsp_msg);
+    pub async fn drop_req_tx(&self, seq: u32) {
+        self.req_txs.lock().await.remove(&seq);
+        println!("remove req_tx: seq={}", seq);
     }
-}
 
-pub async fn drop_req_tx(seq: u32) {
-    REQUEST_TXS.lock().await.remove(&seq);
-    println!("remove req_tx: seq={}", seq);
-}
\ No newline at end of file
+    // dropped req_tx fails the req_rx at once
+    pub async fn drop_all(&self) {
+        self.req_txs.lock().await.clear();
+        println!("drop pending requests");
+    }
+}
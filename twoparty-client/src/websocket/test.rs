@@ -5,8 +5,9 @@ use std::sync::{Arc, LazyLock};
 use std::thread::sleep;
 use std::time::Duration;
 
-use common::socketmsg::REQ_CODE_MPC22;
+use common::socketmsg::{MSG_ACTION_RSP, MSG_VERSION_V1, MsgWrapper, REQ_CODE_MPC22};
 use crate::websocket::SyncClient;
+use crate::websocket::request_holder::RequestRegistry;
 use tokio::sync::{oneshot, mpsc, RwLock};
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::time::timeout;
@@ -59,6 +60,38 @@ async fn test_websocket() {
     let _ = hang_rx.await;
 }
 
+#[tokio::test]
+async fn test_request_registry() {
+    fn rsp(seq: u32, body: &[u8]) -> MsgWrapper {
+        MsgWrapper {
+            seq,
+            timestamp: 0,
+            action: MSG_ACTION_RSP,
+            action_code: 0,
+            body: body.to_vec(),
+            error_msg: "".to_string(),
+            notice_id: "".to_string(),
+            version: MSG_VERSION_V1,
+            error_code: 0,
+        }
+    }
+
+    // two clients with the same seq resolve their own responses
+    let registry_a = RequestRegistry::default();
+    let registry_b = RequestRegistry::default();
+    let rx_a = registry_a.register_request(0).await;
+    let rx_b = registry_b.register_request(0).await;
+    registry_b.resolve_request(rsp(0, b"b")).await;
+    registry_a.resolve_request(rsp(0, b"a")).await;
+    assert_eq!(rx_a.await.unwrap().body, b"a");
+    assert_eq!(rx_b.await.unwrap().body, b"b");
+
+    // dropped requests fail at once
+    let rx = registry_a.register_request(1).await;
+    registry_a.drop_all().await;
+    assert!(rx.await.is_err());
+}
+
 #[test]
 fn assign_cell_pointer() {
     let value = RefCell::new("hello".to_string());
@@ -0,0 +1,62 @@
+use blake2::VarBlake2b;
+use blake2::digest::{Update, VariableOutput};
+use sha3::{Digest, Keccak256};
+use crate::socketmsg::types::{MPC_SCOPE_ED25519EDDSA, MPC_SCOPE_SECP256K1ECDSA, SavedShare};
+
+// the address of the public key of a share, eth for secp256k1, sui for ed25519
+pub fn share_address(saved_share: &SavedShare) -> Result<String, String> {
+    match saved_share.scope {
+        MPC_SCOPE_SECP256K1ECDSA => eth_address(&saved_share.uncompressed_pub),
+        MPC_SCOPE_ED25519EDDSA => sui_address(&saved_share.uncompressed_pub),
+        _ => Err(format!("unsupported scope {}", saved_share.scope)),
+    }
+}
+
+// last 20 bytes of keccak256(x || y), EIP-55 checksummed
+pub fn eth_address(uncompressed_pub: &[u8]) -> Result<String, String> {
+    if uncompressed_pub.len() != 65 || uncompressed_pub[0] != 0x04 {
+        return Err("expect a 65 bytes uncompressed secp256k1 public key".to_string());
+    }
+    let hash = Keccak256::digest(&uncompressed_pub[1..]);
+    let lower = hex::encode(&hash[12..]);
+
+    // https://eips.ethereum.org/EIPS/eip-55
+    let checksum = Keccak256::digest(lower.as_bytes());
+    let checksummed: String = lower.chars().enumerate()
+        .map(|(i, c)| {
+            let nibble = (checksum[i / 2] >> (4 * (1 - i % 2))) & 0x0f;
+            if nibble >= 8 { c.to_ascii_uppercase() } else { c }
+        })
+        .collect();
+    Ok(format!("0x{}", checksummed))
+}
+
+// blake2b-256(flag || public key), the flag of ed25519 is 0x00
+pub fn sui_address(pub_key: &[u8]) -> Result<String, String> {
+    if pub_key.len() != 32 {
+        return Err("expect a 32 bytes ed25519 public key".to_string());
+    }
+    let mut hasher = VarBlake2b::new(32).unwrap();
+    hasher.update([0x00]);
+    hasher.update(pub_key);
+    let mut address = vec![];
+    hasher.finalize_variable(|res| address.extend_from_slice(res));
+    Ok(format!("0x{}", hex::encode(address)))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::address::{eth_address, sui_address};
+
+    #[test]
+    fn test_address() {
+        // public key of the private key 1
+        let g = hex::decode("0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8").unwrap();
+        assert_eq!(eth_address(&g).unwrap(), "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf");
+        assert!(eth_address(&g[1..]).is_err());
+
+        let pub_key = (0u8..32).collect::<Vec<u8>>();
+        assert_eq!(sui_address(&pub_key).unwrap(), "0x0ddaaec3ffac93977c83c3d7440e9e65663850d4861be2f48532548d0a463336");
+        assert!(sui_address(&g).is_err());
+    }
+}
@@ -1,11 +1,12 @@
 use std::error;
 use std::fmt::{Debug, Display, Formatter};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use crate::socketmsg::{MsgWrapper, RSP_CODE_BAD_REQUEST, RSP_CODE_FORBIDDEN, RSP_CODE_INTERNAL_SERVER_ERROR, RSP_CODE_NOT_FOUND, RSP_CODE_NOT_IMPLEMENTED, RSP_CODE_SERVICE_UNAVAILABLE, RSP_CODE_SUCCESS, RSP_CODE_TOO_MANY_REQUESTS, RSP_CODE_UNAUTHORIZED};
 
 pub const SCOPE_ECDSA_SECP256K1: &str = "ecdsa-secp256k1";
 pub const SCOPE_EDDSA_ED25519: &str = "eddsa-edd25519";
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TwoPartyError {
     pub scope: String,
     pub party: u8,
@@ -22,16 +23,223 @@ impl Display for TwoPartyError {
 
 impl error::Error for TwoPartyError {}
 
+// a wire msg or stored state that can not be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    // not json of the expected type
+    Json(String),
+    // not bincode of the expected type, or over the size limit
+    Binary(String),
+    // the encoding of the frame and the msg version inside disagree
+    FrameMismatch { binary_frame: bool, version: u16 },
+    // e.g. an unknown schema_version of a SavedShare
+    UnsupportedVersion { what: &'static str, version: u64 },
+    // state a step depends on was never stored
+    Missing(String),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Json(reason) => write!(f, "json decode fail: {}", reason),
+            DecodeError::Binary(reason) => write!(f, "bincode decode fail: {}", reason),
+            DecodeError::FrameMismatch { binary_frame, version } => {
+                write!(f, "{} frame of msg version {}", if *binary_frame { "binary" } else { "json" }, version)
+            }
+            DecodeError::UnsupportedVersion { what, version } => write!(f, "unsupported {} version={}", what, version),
+            DecodeError::Missing(what) => write!(f, "{} not found", what),
+        }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+// the callers mostly carry String errors
+impl From<DecodeError> for String {
+    fn from(e: DecodeError) -> Self {
+        e.to_string()
+    }
+}
+
+// the kinds of failure apps branch on, the code of a kind is stable across releases,
+// carried in MsgWrapper.error_code & through JNI. never renumber, only append
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MpcErrorKind {
+    Internal,
+    // a wire msg, step msg or share that can not be decoded
+    MalformedMessage,
+    // a zk proof or commitment of the other party does not verify
+    ProofVerificationFailed,
+    // no rsp within the request timeout
+    TransportTimeout,
+    // the connection is down, or dropped before the rsp arrived
+    Disconnected,
+    // the share is not loaded, not stored, or not active
+    ShareNotFound,
+    // denied by the sign policy of mpc-server
+    PolicyDenied,
+    // the peer does not run the msg or protocol version
+    VersionMismatch,
+    Unauthorized,
+    Forbidden,
+    RateLimited,
+    // a valid msg in the wrong state, e.g. step2 before step1
+    InvalidRequest,
+    NotFound,
+    // a command or scope the peer does not support
+    Unsupported,
+    // busy or unreachable
+    Unavailable,
+}
+
+const MPC_ERROR_KINDS: [(MpcErrorKind, u32); 15] = [
+    (MpcErrorKind::Internal, 1000),
+    (MpcErrorKind::MalformedMessage, 1001),
+    (MpcErrorKind::ProofVerificationFailed, 1002),
+    (MpcErrorKind::TransportTimeout, 1003),
+    (MpcErrorKind::Disconnected, 1004),
+    (MpcErrorKind::ShareNotFound, 1005),
+    (MpcErrorKind::PolicyDenied, 1006),
+    (MpcErrorKind::VersionMismatch, 1007),
+    (MpcErrorKind::Unauthorized, 1008),
+    (MpcErrorKind::Forbidden, 1009),
+    (MpcErrorKind::RateLimited, 1010),
+    (MpcErrorKind::InvalidRequest, 1011),
+    (MpcErrorKind::NotFound, 1012),
+    (MpcErrorKind::Unsupported, 1013),
+    (MpcErrorKind::Unavailable, 1014),
+];
+
+impl MpcErrorKind {
+    pub fn code(&self) -> u32 {
+        MPC_ERROR_KINDS.iter().find(|(kind, _)| kind == self).map(|(_, code)| *code).unwrap()
+    }
+
+    // None for 0 & codes of newer releases
+    pub fn from_code(code: u32) -> Option<MpcErrorKind> {
+        MPC_ERROR_KINDS.iter().find(|(_, c)| *c == code).map(|(kind, _)| *kind)
+    }
+
+    // the rsp_code mpc-server replies with
+    pub fn rsp_code(&self) -> u32 {
+        match self {
+            MpcErrorKind::MalformedMessage
+            | MpcErrorKind::VersionMismatch
+            | MpcErrorKind::InvalidRequest => RSP_CODE_BAD_REQUEST,
+            MpcErrorKind::Unauthorized => RSP_CODE_UNAUTHORIZED,
+            MpcErrorKind::ProofVerificationFailed
+            | MpcErrorKind::Forbidden
+            | MpcErrorKind::PolicyDenied => RSP_CODE_FORBIDDEN,
+            MpcErrorKind::ShareNotFound
+            | MpcErrorKind::NotFound => RSP_CODE_NOT_FOUND,
+            MpcErrorKind::RateLimited => RSP_CODE_TOO_MANY_REQUESTS,
+            MpcErrorKind::Unsupported => RSP_CODE_NOT_IMPLEMENTED,
+            MpcErrorKind::TransportTimeout
+            | MpcErrorKind::Disconnected
+            | MpcErrorKind::Unavailable => RSP_CODE_SERVICE_UNAVAILABLE,
+            MpcErrorKind::Internal => RSP_CODE_INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // the coarse kind of a rsp without error_code, e.g. from servers older than error codes
+    pub fn from_rsp_code(rsp_code: u32) -> MpcErrorKind {
+        match rsp_code {
+            RSP_CODE_BAD_REQUEST => MpcErrorKind::InvalidRequest,
+            RSP_CODE_UNAUTHORIZED => MpcErrorKind::Unauthorized,
+            RSP_CODE_FORBIDDEN => MpcErrorKind::Forbidden,
+            RSP_CODE_NOT_FOUND => MpcErrorKind::NotFound,
+            RSP_CODE_TOO_MANY_REQUESTS => MpcErrorKind::RateLimited,
+            RSP_CODE_NOT_IMPLEMENTED => MpcErrorKind::Unsupported,
+            RSP_CODE_SERVICE_UNAVAILABLE => MpcErrorKind::Unavailable,
+            _ => MpcErrorKind::Internal,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MpcError {
+    pub kind: MpcErrorKind,
+    pub message: String,
+}
 
+impl MpcError {
+    pub fn new(kind: MpcErrorKind, message: impl Into<String>) -> Self {
+        MpcError { kind, message: message.into() }
+    }
 
+    pub fn code(&self) -> u32 {
+        self.kind.code()
+    }
 
-#[derive(Debug)]
-pub struct GenericError(pub String);
+    // the error of a failed rsp, None if it succeeded
+    pub fn from_rsp(rsp: &MsgWrapper) -> Option<MpcError> {
+        if rsp.action_code == RSP_CODE_SUCCESS {
+            return None;
+        }
+        let kind = MpcErrorKind::from_code(rsp.error_code)
+            .unwrap_or_else(|| MpcErrorKind::from_rsp_code(rsp.action_code));
+        Some(MpcError::new(kind, rsp.error_msg.clone()))
+    }
+}
 
-impl Display for GenericError {
+impl Display for MpcError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.message)
+    }
+}
+
+impl error::Error for MpcError {}
+
+// untyped failures are internal
+impl From<String> for MpcError {
+    fn from(message: String) -> Self {
+        MpcError::new(MpcErrorKind::Internal, message)
+    }
+}
+
+impl From<DecodeError> for MpcError {
+    fn from(e: DecodeError) -> Self {
+        let kind = match e {
+            DecodeError::UnsupportedVersion { .. } | DecodeError::FrameMismatch { .. } => MpcErrorKind::VersionMismatch,
+            _ => MpcErrorKind::MalformedMessage,
+        };
+        MpcError::new(kind, e.to_string())
+    }
+}
+
+// the steps of both parties fail with a TwoPartyError when a proof of the other party does not verify
+impl From<TwoPartyError> for MpcError {
+    fn from(e: TwoPartyError) -> Self {
+        MpcError::new(MpcErrorKind::ProofVerificationFailed, e.to_string())
     }
 }
 
-impl error::Error for GenericError {}
\ No newline at end of file
+impl From<MpcError> for String {
+    fn from(e: MpcError) -> Self {
+        e.message
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::errors::{MPC_ERROR_KINDS, MpcError, MpcErrorKind};
+    use crate::socketmsg::{RSP_CODE_FORBIDDEN, RSP_CODE_NOT_FOUND};
+
+    #[test]
+    fn test_error_codes() {
+        // the codes are part of the api, apps & the java bindings hard code them
+        assert_eq!(MpcErrorKind::Internal.code(), 1000);
+        assert_eq!(MpcErrorKind::ProofVerificationFailed.code(), 1002);
+        assert_eq!(MpcErrorKind::Unavailable.code(), 1014);
+        for (i, (kind, code)) in MPC_ERROR_KINDS.iter().enumerate() {
+            assert_eq!(*code, 1000 + i as u32);
+            assert_eq!(MpcErrorKind::from_code(*code), Some(*kind));
+        }
+        assert_eq!(MpcErrorKind::from_code(0), None);
+
+        assert_eq!(MpcErrorKind::ShareNotFound.rsp_code(), RSP_CODE_NOT_FOUND);
+        assert_eq!(MpcErrorKind::from_rsp_code(RSP_CODE_FORBIDDEN), MpcErrorKind::Forbidden);
+        let e: MpcError = "boom".to_string().into();
+        assert_eq!(e.kind, MpcErrorKind::Internal);
+    }
+}
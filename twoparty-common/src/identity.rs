@@ -0,0 +1,102 @@
+//! Bind shares with a third-party identity id, see README.
+//!
+//! the identity-secret is a secp256k1 scalar x generated by mpc-server, mpc-server only keeps
+//! Q= x*G with (Enc(identity-secret), Enc(share)), and mpc-client proves it has decrypted
+//! Enc(identity-secret) by a d_log_proof of x with the challenge from mpc-server.
+#![allow(non_snake_case)]
+
+use curv::arithmetic::Samplable;
+use curv::BigInt;
+use curv::elliptic::curves::{Point, Scalar, Secp256k1};
+use serde::{Deserialize, Serialize};
+use crate::dlog::DLogProof;
+
+const CHALLENGE_BITS: usize = 256;
+
+// party2 -> party1, bind step1
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IdentitySecretMsg {
+    // empty if identity_id is already bound
+    pub identity_secret: Vec<u8>,
+    // Enc(identity-secret) & a challenge if identity_id is already bound,
+    // another share is bound only with a proof of the identity-secret
+    #[serde(default)]
+    pub bound: Option<IdentityRecoverMsg1>,
+}
+
+// party1 -> party2, bind step2, the share replaces a bound share of the same share_id
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IdentityBindMsg {
+    // empty if identity_id is already bound
+    pub encrypted_identity_secret: Vec<u8>,
+    pub encrypted_share: EncryptedShare,
+    // needed if identity_id is already bound
+    #[serde(default)]
+    pub secret_proof: Option<DLogProof<Secp256k1>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EncryptedShare {
+    pub share_id: String,
+    pub scope: u8,
+    pub encrypted_share: Vec<u8>,
+}
+
+// party2 -> party1, recover step1
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IdentityRecoverMsg1 {
+    pub encrypted_identity_secret: Vec<u8>,
+    pub challenge: BigInt,
+}
+
+// party1 -> party2, recover step2
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IdentityRecoverMsg2 {
+    pub secret_proof: DLogProof<Secp256k1>,
+}
+
+// party2 -> party1, recover step2
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IdentityRecoverMsg3 {
+    pub encrypted_shares: Vec<EncryptedShare>,
+}
+
+// return (identity_secret, Q)
+pub fn generate_identity_secret() -> (Vec<u8>, Point<Secp256k1>) {
+    let x = Scalar::<Secp256k1>::random();
+    let Q = &x * Point::<Secp256k1>::generator();
+    (x.to_bytes().to_vec(), Q)
+}
+
+pub fn generate_challenge() -> BigInt {
+    BigInt::sample(CHALLENGE_BITS)
+}
+
+pub fn prove_identity_secret(identity_secret: &[u8], challenge: &BigInt) -> Result<DLogProof<Secp256k1>, String> {
+    let x_result = Scalar::<Secp256k1>::from_bytes(identity_secret);
+    if x_result.is_err() {
+        return Err("identity_secret is not a valid scalar".to_string());
+    }
+    Ok(DLogProof::prove(&x_result.unwrap(), Some(challenge)))
+}
+
+pub fn verify_identity_proof(proof: &DLogProof<Secp256k1>, identity_public: &Point<Secp256k1>, challenge: &BigInt) -> bool {
+    &proof.Q == identity_public && proof.verify(Some(challenge))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::identity::{generate_challenge, generate_identity_secret, prove_identity_secret, verify_identity_proof};
+
+    #[test]
+    fn test_identity_proof() {
+        let (identity_secret, Q) = generate_identity_secret();
+        let challenge = generate_challenge();
+        let proof = prove_identity_secret(&identity_secret, &challenge).unwrap();
+        assert!(verify_identity_proof(&proof, &Q, &challenge));
+
+        // a proof can not be replayed with another challenge
+        let other_challenge = generate_challenge();
+        assert!(!verify_identity_proof(&proof, &Q, &other_challenge));
+    }
+}
@@ -1,19 +1,30 @@
 #![allow(non_snake_case)]
 
 
+#[cfg(not(target_arch = "wasm32"))]
 use std::time::SystemTime;
 use uuid::Uuid;
 
 pub mod errors;
 pub mod dlog;
 pub mod socketmsg;
+pub mod identity;
+pub mod payload;
+pub mod address;
 
 
+#[cfg(not(target_arch = "wasm32"))]
 pub fn get_tsp() -> u128 {
     
     SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis()
 }
 
+// SystemTime::now panics on wasm32-unknown-unknown
+#[cfg(target_arch = "wasm32")]
+pub fn get_tsp() -> u128 {
+    js_sys::Date::now() as u128
+}
+
 
 pub fn get_uuid() -> String {
     let v4 = Uuid::new_v4();
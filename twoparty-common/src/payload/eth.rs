@@ -0,0 +1,179 @@
+use curv::arithmetic::Converter;
+use curv::BigInt;
+use sha3::{Digest, Keccak256};
+use crate::payload::{DecodedPayload, PAYLOAD_FORMAT_EIP712, PAYLOAD_FORMAT_ETH_TX, PayloadOutput};
+
+enum Rlp<'a> {
+    Bytes(&'a [u8]),
+    List(Vec<Rlp<'a>>),
+}
+
+impl<'a> Rlp<'a> {
+    fn as_bytes(&self) -> Result<&'a [u8], String> {
+        match self {
+            Rlp::Bytes(bytes) => Ok(bytes),
+            Rlp::List(_) => Err("expect rlp bytes, got list".to_string()),
+        }
+    }
+}
+
+// https://ethereum.org/en/developers/docs/data-structures-and-encoding/rlp/
+fn rlp_decode(input: &[u8]) -> Result<(Rlp, &[u8]), String> {
+    let prefix = *input.first().ok_or("rlp input is empty".to_string())?;
+    let (is_list, offset, len) = match prefix {
+        0x00..=0x7f => return Ok((Rlp::Bytes(&input[..1]), &input[1..])),
+        0x80..=0xb7 => (false, 1, (prefix - 0x80) as usize),
+        0xb8..=0xbf => {
+            let len_of_len = (prefix - 0xb7) as usize;
+            (false, 1 + len_of_len, read_be_len(input, len_of_len)?)
+        }
+        0xc0..=0xf7 => (true, 1, (prefix - 0xc0) as usize),
+        0xf8..=0xff => {
+            let len_of_len = (prefix - 0xf7) as usize;
+            (true, 1 + len_of_len, read_be_len(input, len_of_len)?)
+        }
+    };
+    let end = offset.checked_add(len).ok_or("rlp length overflow".to_string())?;
+    if input.len() < end {
+        return Err("rlp input too short".to_string());
+    }
+    let content = &input[offset..end];
+    if !is_list {
+        return Ok((Rlp::Bytes(content), &input[end..]));
+    }
+
+    let mut items = vec![];
+    let mut rest = content;
+    while !rest.is_empty() {
+        let (item, next) = rlp_decode(rest)?;
+        items.push(item);
+        rest = next;
+    }
+    Ok((Rlp::List(items), &input[end..]))
+}
+
+fn read_be_len(input: &[u8], len_of_len: usize) -> Result<usize, String> {
+    if len_of_len > 8 || input.len() < 1 + len_of_len {
+        return Err("invalid rlp length".to_string());
+    }
+    let mut len = 0usize;
+    for b in &input[1..1 + len_of_len] {
+        len = len.checked_mul(256).ok_or("rlp length overflow".to_string())? + *b as usize;
+    }
+    Ok(len)
+}
+
+fn to_u64(bytes: &[u8]) -> Result<u64, String> {
+    if bytes.len() > 8 {
+        return Err("integer overflows u64".to_string());
+    }
+    Ok(bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64))
+}
+
+fn to_address(bytes: &[u8]) -> Result<String, String> {
+    match bytes.len() {
+        // contract creation
+        0 => Ok("".to_string()),
+        20 => Ok(format!("0x{}", hex::encode(bytes))),
+        _ => Err("invalid recipient address".to_string()),
+    }
+}
+
+// legacy(eip155 or not), eip2930 & eip1559 unsigned transactions
+pub fn decode_tx(payload: &[u8]) -> Result<DecodedPayload, String> {
+    let first = *payload.first().ok_or("eth payload is empty".to_string())?;
+    // eip2718: a first byte <= 0x7f is the tx type, a legacy tx starts with its rlp list prefix >= 0xc0
+    let (tx_type, rlp_bytes) = match first {
+        0x01 | 0x02 => (first, &payload[1..]),
+        0x00..=0x7f => return Err(format!("unsupported eth tx type={}", first)),
+        _ => (0, payload),
+    };
+
+    let (rlp, rest) = rlp_decode(rlp_bytes)?;
+    if !rest.is_empty() {
+        return Err("trailing bytes after eth tx".to_string());
+    }
+    let fields = match rlp {
+        Rlp::List(fields) => fields,
+        Rlp::Bytes(_) => return Err("eth tx is not a rlp list".to_string()),
+    };
+
+    // index of (chain_id, to, value)
+    let (chain_id_index, to_index, value_index, field_count) = match (tx_type, fields.len()) {
+        // pre-eip155 [nonce, gasPrice, gas, to, value, data]
+        (0, 6) => (None, 3, 4, 6),
+        // eip155 [nonce, gasPrice, gas, to, value, data, chainId, 0, 0]
+        (0, 9) => (Some(6), 3, 4, 9),
+        // [chainId, nonce, gasPrice, gas, to, value, data, accessList]
+        (1, 8) => (Some(0), 4, 5, 8),
+        // [chainId, nonce, maxPriorityFeePerGas, maxFeePerGas, gas, to, value, data, accessList]
+        (2, 9) => (Some(0), 5, 6, 9),
+        _ => return Err(format!("unsupported eth tx type={} with {} fields", tx_type, fields.len())),
+    };
+    debug_assert_eq!(fields.len(), field_count);
+
+    let chain_id = match chain_id_index {
+        Some(index) => Some(to_u64(fields[index].as_bytes()?)?),
+        None => None,
+    };
+    let output = PayloadOutput {
+        recipient: to_address(fields[to_index].as_bytes()?)?,
+        value: BigInt::from_bytes(fields[value_index].as_bytes()?).to_string(),
+    };
+
+    Ok(DecodedPayload {
+        format: PAYLOAD_FORMAT_ETH_TX,
+        digest: Keccak256::digest(payload).to_vec(),
+        outputs: vec![output],
+        chain_id,
+    })
+}
+
+// https://eips.ethereum.org/EIPS/eip-712
+pub fn decode_eip712(payload: &[u8]) -> Result<DecodedPayload, String> {
+    if payload.len() != 66 || payload[..2] != [0x19, 0x01] {
+        return Err("eip712 payload should be 0x1901 || domain_separator || hash_struct(message)".to_string());
+    }
+    Ok(DecodedPayload {
+        format: PAYLOAD_FORMAT_EIP712,
+        digest: Keccak256::digest(payload).to_vec(),
+        outputs: vec![],
+        chain_id: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use sha3::{Digest, Keccak256};
+    use crate::payload::eth::decode_tx;
+
+    #[test]
+    fn test_decode_eip155_tx() {
+        // the unsigned tx of https://eips.ethereum.org/EIPS/eip-155 example
+        let payload = hex::decode("ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080").unwrap();
+        let decoded = decode_tx(&payload).unwrap();
+        assert_eq!(decoded.chain_id, Some(1));
+        assert_eq!(decoded.outputs[0].recipient, "0x3535353535353535353535353535353535353535");
+        assert_eq!(decoded.outputs[0].value, "1000000000000000000");
+        assert_eq!(decoded.digest, Keccak256::digest(&payload).to_vec());
+        assert_eq!(hex::encode(&decoded.digest), "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53");
+    }
+
+    #[test]
+    fn test_reject_malformed_tx() {
+        assert!(decode_tx(&[]).is_err());
+        assert!(decode_tx(&[0xc3, 0x01]).is_err());
+        assert!(decode_tx(&hex::decode("c3010203").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_reject_type0_prefix() {
+        // 0x00 || rlp(legacy tx) is not a typed tx, its digest differs from the legacy one
+        let legacy = hex::decode("ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080").unwrap();
+        let mut payload = vec![0x00];
+        payload.extend_from_slice(&legacy);
+        assert!(decode_tx(&payload).is_err());
+        payload[0] = 0x03;
+        assert!(decode_tx(&payload).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+//! The unsigned payload behind a message_digest.
+//!
+//! party1 may send the payload with its declared format, so party2 can recompute the digest
+//! itself and know what it is co-signing, e.g. the recipient, value and chain id of a transaction.
+mod eth;
+mod psbt;
+
+use serde::{Deserialize, Serialize};
+
+pub const PAYLOAD_FORMAT_ETH_TX: u8 = 1;
+pub const PAYLOAD_FORMAT_EIP712: u8 = 2;
+pub const PAYLOAD_FORMAT_PSBT: u8 = 3;
+pub const PAYLOAD_FORMAT_SUI_INTENT: u8 = 4;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignPayload {
+    pub format: u8,
+    // eth: unsigned rlp tx, typed tx with its type prefix
+    // eip712: 0x1901 || domain_separator || hash_struct(message)
+    // psbt: serialized psbt
+    // sui: intent || bcs(transaction_data)
+    pub payload: Vec<u8>,
+    // psbt only, the input to sign
+    #[serde(default)]
+    pub input_index: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PayloadOutput {
+    // eth: 0x prefixed address, psbt: hex encoded script_pubkey
+    pub recipient: String,
+    // decimal, wei or satoshi
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DecodedPayload {
+    pub format: u8,
+    pub digest: Vec<u8>,
+    // empty if the format does not expose outputs, e.g. eip712 & sui
+    pub outputs: Vec<PayloadOutput>,
+    pub chain_id: Option<u64>,
+}
+
+impl SignPayload {
+    // decode the payload and compute the digest to sign
+    pub fn decode(&self) -> Result<DecodedPayload, String> {
+        match self.format {
+            PAYLOAD_FORMAT_ETH_TX => eth::decode_tx(&self.payload),
+            PAYLOAD_FORMAT_EIP712 => eth::decode_eip712(&self.payload),
+            PAYLOAD_FORMAT_PSBT => psbt::decode_psbt(&self.payload, self.input_index as usize),
+            PAYLOAD_FORMAT_SUI_INTENT => decode_sui_intent(&self.payload),
+            _ => Err(format!("unsupported payload format={}", self.format)),
+        }
+    }
+
+    // decode the payload and check it hashes to message_digest
+    pub fn verify_digest(&self, message_digest: &[u8]) -> Result<DecodedPayload, String> {
+        let decoded = self.decode()?;
+        if decoded.digest != message_digest {
+            return Err("message_digest does not match the payload".to_string());
+        }
+        Ok(decoded)
+    }
+}
+
+// https://docs.sui.io/concepts/cryptography/transaction-auth/intent-signing
+fn decode_sui_intent(payload: &[u8]) -> Result<DecodedPayload, String> {
+    use blake2::VarBlake2b;
+    use blake2::digest::{Update, VariableOutput};
+
+    // intent scope TransactionData, version V0, app id Sui
+    if payload.len() <= 3 || payload[..3] != [0, 0, 0] {
+        return Err("sui payload is not a transaction data intent message".to_string());
+    }
+    let mut hasher = VarBlake2b::new(32).unwrap();
+    hasher.update(payload);
+    let mut digest = vec![];
+    hasher.finalize_variable(|res| digest.extend_from_slice(res));
+
+    Ok(DecodedPayload {
+        format: PAYLOAD_FORMAT_SUI_INTENT,
+        digest,
+        outputs: vec![],
+        chain_id: None,
+    })
+}
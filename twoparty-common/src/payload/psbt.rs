@@ -0,0 +1,230 @@
+use sha2::{Digest, Sha256};
+use crate::payload::{DecodedPayload, PAYLOAD_FORMAT_PSBT, PayloadOutput};
+
+const PSBT_MAGIC: [u8; 5] = [0x70, 0x73, 0x62, 0x74, 0xff];
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const PSBT_IN_WITNESS_UTXO: u8 = 0x01;
+const SIGHASH_ALL: u32 = 1;
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).ok_or("psbt length overflow".to_string())?;
+        if end > self.data.len() {
+            return Err("psbt input too short".to_string());
+        }
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.read(4)?.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.read(8)?.try_into().unwrap()))
+    }
+
+    // bitcoin compact size
+    fn read_var_int(&mut self) -> Result<usize, String> {
+        let n = match self.read(1)?[0] {
+            0xfd => u16::from_le_bytes(self.read(2)?.try_into().unwrap()) as u64,
+            0xfe => self.read_u32()? as u64,
+            0xff => self.read_u64()?,
+            n => n as u64,
+        };
+        usize::try_from(n).map_err(|_| "compact size overflow".to_string())
+    }
+
+    fn read_var_bytes(&mut self) -> Result<&'a [u8], String> {
+        let len = self.read_var_int()?;
+        self.read(len)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+}
+
+struct TxIn<'a> {
+    outpoint: &'a [u8],
+    sequence: u32,
+}
+
+struct TxOut<'a> {
+    value: u64,
+    script_pubkey: &'a [u8],
+}
+
+struct UnsignedTx<'a> {
+    version: u32,
+    inputs: Vec<TxIn<'a>>,
+    outputs: Vec<TxOut<'a>>,
+    lock_time: u32,
+}
+
+fn read_tx_out<'a>(reader: &mut Reader<'a>) -> Result<TxOut<'a>, String> {
+    Ok(TxOut {
+        value: reader.read_u64()?,
+        script_pubkey: reader.read_var_bytes()?,
+    })
+}
+
+// the unsigned tx of psbt is serialized without witness
+fn read_unsigned_tx<'a>(reader: &mut Reader<'a>) -> Result<UnsignedTx<'a>, String> {
+    let version = reader.read_u32()?;
+    let input_count = reader.read_var_int()?;
+    let mut inputs = vec![];
+    for _ in 0..input_count {
+        let outpoint = reader.read(36)?;
+        if !reader.read_var_bytes()?.is_empty() {
+            return Err("psbt unsigned tx has script_sig".to_string());
+        }
+        inputs.push(TxIn { outpoint, sequence: reader.read_u32()? });
+    }
+    let output_count = reader.read_var_int()?;
+    let mut outputs = vec![];
+    for _ in 0..output_count {
+        outputs.push(read_tx_out(reader)?);
+    }
+    let lock_time = reader.read_u32()?;
+    if !reader.is_empty() {
+        return Err("trailing bytes after psbt unsigned tx".to_string());
+    }
+    Ok(UnsignedTx { version, inputs, outputs, lock_time })
+}
+
+// iterate a psbt key-value map until the 0x00 separator
+fn read_map<'a>(reader: &mut Reader<'a>) -> Result<Vec<(&'a [u8], &'a [u8])>, String> {
+    let mut pairs = vec![];
+    loop {
+        let key = reader.read_var_bytes()?;
+        if key.is_empty() {
+            return Ok(pairs);
+        }
+        pairs.push((key, reader.read_var_bytes()?));
+    }
+}
+
+fn sha256d(data: &[u8]) -> Vec<u8> {
+    Sha256::digest(&Sha256::digest(data)).to_vec()
+}
+
+fn write_var_int(buf: &mut Vec<u8>, n: usize) {
+    match n {
+        0..=0xfc => buf.push(n as u8),
+        0xfd..=0xffff => {
+            buf.push(0xfd);
+            buf.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        _ => {
+            buf.push(0xfe);
+            buf.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+    }
+}
+
+// https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki
+// only p2wpkh inputs with SIGHASH_ALL are supported
+pub fn decode_psbt(payload: &[u8], input_index: usize) -> Result<DecodedPayload, String> {
+    let mut reader = Reader { data: payload, pos: 0 };
+    if reader.read(5)? != PSBT_MAGIC {
+        return Err("invalid psbt magic".to_string());
+    }
+
+    let globals = read_map(&mut reader)?;
+    let tx_bytes = globals.iter()
+        .find(|(key, _)| key == &[PSBT_GLOBAL_UNSIGNED_TX])
+        .map(|(_, value)| *value)
+        .ok_or("psbt has no unsigned tx".to_string())?;
+    let tx = read_unsigned_tx(&mut Reader { data: tx_bytes, pos: 0 })?;
+    if input_index >= tx.inputs.len() {
+        return Err(format!("psbt input_index={} out of range", input_index));
+    }
+
+    let mut witness_utxo = None;
+    for index in 0..tx.inputs.len() {
+        let input_map = read_map(&mut reader)?;
+        if index == input_index {
+            witness_utxo = input_map.iter()
+                .find(|(key, _)| key == &[PSBT_IN_WITNESS_UTXO])
+                .map(|(_, value)| *value);
+        }
+    }
+    let utxo_bytes = witness_utxo.ok_or("psbt input has no witness_utxo".to_string())?;
+    let utxo = read_tx_out(&mut Reader { data: utxo_bytes, pos: 0 })?;
+    // p2wpkh: OP_0 <20 bytes pubkey hash>
+    if utxo.script_pubkey.len() != 22 || utxo.script_pubkey[..2] != [0x00, 0x14] {
+        return Err("only p2wpkh psbt inputs are supported".to_string());
+    }
+
+    Ok(DecodedPayload {
+        format: PAYLOAD_FORMAT_PSBT,
+        digest: bip143_sighash(&tx, input_index, &utxo),
+        outputs: tx.outputs.iter().map(|o| PayloadOutput {
+            recipient: hex::encode(o.script_pubkey),
+            value: o.value.to_string(),
+        }).collect(),
+        chain_id: None,
+    })
+}
+
+// https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki
+fn bip143_sighash(tx: &UnsignedTx, input_index: usize, utxo: &TxOut) -> Vec<u8> {
+    let mut prevouts = vec![];
+    let mut sequences = vec![];
+    for input in &tx.inputs {
+        prevouts.extend_from_slice(input.outpoint);
+        sequences.extend_from_slice(&input.sequence.to_le_bytes());
+    }
+    let mut outputs = vec![];
+    for output in &tx.outputs {
+        outputs.extend_from_slice(&output.value.to_le_bytes());
+        write_var_int(&mut outputs, output.script_pubkey.len());
+        outputs.extend_from_slice(output.script_pubkey);
+    }
+
+    let input = &tx.inputs[input_index];
+    let mut preimage = vec![];
+    preimage.extend_from_slice(&tx.version.to_le_bytes());
+    preimage.extend_from_slice(&sha256d(&prevouts));
+    preimage.extend_from_slice(&sha256d(&sequences));
+    preimage.extend_from_slice(input.outpoint);
+    // script_code of p2wpkh: OP_DUP OP_HASH160 <pubkey hash> OP_EQUALVERIFY OP_CHECKSIG
+    preimage.extend_from_slice(&[0x19, 0x76, 0xa9, 0x14]);
+    preimage.extend_from_slice(&utxo.script_pubkey[2..]);
+    preimage.extend_from_slice(&[0x88, 0xac]);
+    preimage.extend_from_slice(&utxo.value.to_le_bytes());
+    preimage.extend_from_slice(&input.sequence.to_le_bytes());
+    preimage.extend_from_slice(&sha256d(&outputs));
+    preimage.extend_from_slice(&tx.lock_time.to_le_bytes());
+    preimage.extend_from_slice(&SIGHASH_ALL.to_le_bytes());
+    sha256d(&preimage)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::payload::psbt::decode_psbt;
+
+    // the native p2wpkh example of bip143, wrapped into a psbt
+    const PSBT_HEX: &str = "70736274ff0100a00100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000000001011f0046c323000000001600141d0f172a0ecb48aee1be1f2687d2963ae33f71a1000000";
+
+    #[test]
+    fn test_decode_p2wpkh_psbt() {
+        let payload = hex::decode(PSBT_HEX).unwrap();
+        let decoded = decode_psbt(&payload, 1).unwrap();
+        assert_eq!(hex::encode(&decoded.digest), "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670");
+        assert_eq!(decoded.outputs.len(), 2);
+        assert_eq!(decoded.outputs[0].recipient, "76a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac");
+        assert_eq!(decoded.outputs[0].value, "112340000");
+
+        // input 0 has no witness_utxo
+        assert!(decode_psbt(&payload, 0).is_err());
+        assert!(decode_psbt(&payload, 2).is_err());
+    }
+}
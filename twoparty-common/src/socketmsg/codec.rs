@@ -0,0 +1,70 @@
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::DecodeError;
+use crate::socketmsg::{MSG_VERSION_V2, MsgWrapper};
+
+// first byte of a binary frame, a json frame starts with '{'
+pub const BINARY_FRAME_MAGIC: u8 = 0xb2;
+// a length prefix can not make the decoder allocate more than this
+const BINARY_LIMIT: u64 = 16 << 20;
+
+// varint lengths & integers, trailing bytes are rejected.
+// curv writes points & scalars as raw bytes in non human readable formats, instead of hex strings
+fn binary_options() -> impl Options {
+    bincode::DefaultOptions::new().with_limit(BINARY_LIMIT)
+}
+
+// since MSG_VERSION_V2 the envelope, its body & the msg_detail of Mpc22Msg are bincode, json before
+pub fn is_binary(msg_version: u16) -> bool {
+    msg_version >= MSG_VERSION_V2
+}
+
+// encode a body or a msg_detail in the encoding of the envelope version
+pub fn encode_detail<T>(msg_version: u16, t: &T) -> Result<Vec<u8>, String>
+    where T: ?Sized + Serialize
+{
+    if is_binary(msg_version) {
+        return binary_options().serialize(t).map_err(|e| format!("bincode encode fail: {}", e));
+    }
+    serde_json::to_vec(t).map_err(|e| format!("json encode fail: {}", e))
+}
+
+pub fn decode_detail<'a, T>(msg_version: u16, bytes: &'a [u8]) -> Result<T, DecodeError>
+    where T: Deserialize<'a>
+{
+    if is_binary(msg_version) {
+        return binary_options().deserialize::<T>(bytes).map_err(|e| DecodeError::Binary(e.to_string()));
+    }
+    decode_json(bytes)
+}
+
+// stored state is json whatever the msg version
+pub fn decode_json<'a, T>(bytes: &'a [u8]) -> Result<T, DecodeError>
+    where T: Deserialize<'a>
+{
+    serde_json::from_slice::<T>(bytes).map_err(|e| DecodeError::Json(e.to_string()))
+}
+
+pub fn encode_msg(msg: &MsgWrapper) -> Result<Vec<u8>, String> {
+    if is_binary(msg.version) {
+        let mut frame = vec![BINARY_FRAME_MAGIC];
+        frame.extend(encode_detail(msg.version, msg)?);
+        return Ok(frame);
+    }
+    encode_detail(msg.version, msg)
+}
+
+// the encoding is told by the first byte, the version inside must agree with it
+pub fn decode_msg(bytes: &[u8]) -> Result<MsgWrapper, DecodeError> {
+    let binary_frame = bytes.first() == Some(&BINARY_FRAME_MAGIC);
+    let msg = if binary_frame {
+        decode_detail::<MsgWrapper>(MSG_VERSION_V2, &bytes[1..])?
+    } else {
+        decode_json::<MsgWrapper>(bytes)?
+    };
+    if binary_frame != is_binary(msg.version) {
+        return Err(DecodeError::FrameMismatch { binary_frame, version: msg.version });
+    }
+    Ok(msg)
+}
@@ -1,6 +1,8 @@
 pub mod types;
+pub mod codec;
 
 use serde::{Deserialize, Serialize};
+use crate::errors::DecodeError;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MsgWrapper {
@@ -15,6 +17,23 @@ pub struct MsgWrapper {
     pub error_msg: String,
     // only for notice
     pub notice_id: String,
+    // version of this envelope, missing from clients older than versioning
+    #[serde(default = "legacy_msg_version")]
+    pub version: u16,
+    // only for rsp, the MpcErrorKind code of a failed rsp, 0 if success or from servers older than error codes
+    #[serde(default)]
+    pub error_code: u32,
+}
+
+// the envelope versions mpc-server & mpc-client run, see codec
+// v1: json envelope, json body & msg_detail
+// v2: bincode envelope behind BINARY_FRAME_MAGIC, bincode body & msg_detail
+pub const MSG_VERSION_V1: u16 = 1;
+pub const MSG_VERSION_V2: u16 = 2;
+pub const MSG_VERSIONS: [u16; 2] = [MSG_VERSION_V1, MSG_VERSION_V2];
+
+fn legacy_msg_version() -> u16 {
+    MSG_VERSION_V1
 }
 
 pub const MSG_ACTION_REQ: u8 = 1;
@@ -24,6 +43,15 @@ pub const MSG_ACTION_ACK: u8 = 4;
 
 
 pub const REQ_CODE_MPC22: u32 = 1;
+// body is a SubscribeNoticeMsg, pending notices of the identity are delivered after the rsp
+pub const REQ_CODE_SUBSCRIBE_NOTICE: u32 = 2;
+// agree on the versions of the envelope & of the step messages of every scope
+pub const REQ_CODE_NEGOTIATE: u32 = 3;
+
+// action_code of a notice, its body is a Notice
+pub const NOTICE_ROTATION_REQUIRED: u32 = 1;
+pub const NOTICE_SHARE_FROZEN: u32 = 2;
+pub const NOTICE_POLICY_CHANGED: u32 = 3;
 
 
 pub const RSP_CODE_SUCCESS: u32 = 200;
@@ -37,13 +65,240 @@ pub const RSP_CODE_INTERNAL_SERVER_ERROR: u32 = 500;
 pub const RSP_CODE_NOT_IMPLEMENTED: u32 = 501;
 pub const RSP_CODE_SERVICE_UNAVAILABLE: u32 = 503;
 
+// body of a RSP_CODE_TOO_MANY_REQUESTS rsp
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RetryAfter {
+    // millis to wait before the next request is allowed
+    pub retry_after_ms: u64,
+}
+
+// served at /ws-config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WsConfig {
+    // secs a connection may stay silent before mpc-server closes it
+    pub ws_server_idle: u8,
+    // secs between the heartbeats of mpc-client
+    pub ws_client_interval: u8,
+}
+
+// served at /capabilities, what mpc-server runs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Capabilities {
+    pub req_codes: Vec<u32>,
+    pub scopes: Vec<ScopeCapabilities>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScopeCapabilities {
+    pub scope: u8,
+    pub commands: Vec<u8>,
+    pub protocol_versions: Vec<u16>,
+}
+
+// body of a REQ_CODE_NEGOTIATE req, the versions party1 runs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NegotiateMsg {
+    pub msg_versions: Vec<u16>,
+    pub scopes: Vec<ScopeVersions>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScopeVersions {
+    pub scope: u8,
+    pub versions: Vec<u16>,
+}
+
+// body of the rsp, the highest version both parties run, scopes without a common version are left out
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NegotiatedMsg {
+    pub msg_version: u16,
+    pub scopes: Vec<ScopeVersion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScopeVersion {
+    pub scope: u8,
+    pub version: u16,
+}
+
+// the highest version in both lists
+pub fn highest_common_version(ours: &[u16], theirs: &[u16]) -> Option<u16> {
+    ours.iter().filter(|v| theirs.contains(v)).max().copied()
+}
+
+impl Capabilities {
+    // scope 0 is for commands not bound to a scope, e.g. MPC_IDENTITY_RECOVER, any scope advertising it will do
+    pub fn supports(&self, scope: u8, command: u8) -> bool {
+        self.scopes.iter()
+            .filter(|s| scope == 0 || s.scope == scope)
+            .any(|s| s.commands.contains(&command))
+    }
+}
+
 
 impl MsgWrapper {
-    pub fn to_bytes(&self) -> Vec<u8> {
-        serde_json::to_vec(self).unwrap()
+    // encoded as its version tells
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        codec::encode_msg(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<MsgWrapper, DecodeError> {
+        codec::decode_msg(bytes)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SubscribeNoticeMsg {
+    pub identity_id: String,
+}
+
+// redelivered on every subscription until acknowledged by a MSG_ACTION_ACK with its notice_id
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Notice {
+    // empty if the notice is not about a single share
+    pub share_id: String,
+    pub message: String,
+    pub created_at: u128,
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::socketmsg::{highest_common_version, MSG_ACTION_REQ, MSG_VERSION_V1, MSG_VERSION_V2, MsgWrapper, REQ_CODE_MPC22};
+    use crate::socketmsg::codec::{BINARY_FRAME_MAGIC, decode_detail, decode_msg, encode_detail, encode_msg};
+    use crate::socketmsg::types::{Mpc22Msg, MPC_KEYGEN, MPC_PROTOCOL_V1, MPC_PROTOCOL_V2, MPC_SCOPE_SECP256K1ECDSA, SavedShare};
+    use crate::errors::DecodeError;
+
+    #[test]
+    fn test_highest_common_version() {
+        assert_eq!(highest_common_version(&[1, 2], &[1, 2, 3]), Some(2));
+        assert_eq!(highest_common_version(&[1, 2], &[1]), Some(1));
+        assert_eq!(highest_common_version(&[2], &[1]), None);
     }
 
-    pub fn from_bytes(bytes: &[u8]) -> MsgWrapper {
-        serde_json::from_slice::<MsgWrapper>(bytes).unwrap()
+    #[test]
+    fn test_legacy_versions() {
+        // sent by clients older than versioning
+        let msg_wrapper = serde_json::from_str::<MsgWrapper>(
+            r#"{"seq":1,"timestamp":0,"action":1,"action_code":1,"body":[],"error_msg":"","notice_id":""}"#
+        ).unwrap();
+        assert_eq!(msg_wrapper.version, MSG_VERSION_V1);
+        let mpc22_msg = serde_json::from_str::<Mpc22Msg>(
+            r#"{"command":2,"scope":1,"party":1,"step":1,"msg_detail":[],"identity_id":"","share_id":"s"}"#
+        ).unwrap();
+        assert_eq!(mpc22_msg.version, MPC_PROTOCOL_V1);
+
+        let mut mpc22_msg = mpc22_msg;
+        mpc22_msg.version = MPC_PROTOCOL_V2;
+        let bytes = serde_json::to_vec(&mpc22_msg).unwrap();
+        assert_eq!(serde_json::from_slice::<Mpc22Msg>(&bytes).unwrap().version, MPC_PROTOCOL_V2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_codec() {
+        let mpc22_msg = Mpc22Msg {
+            command: MPC_KEYGEN,
+            scope: MPC_SCOPE_SECP256K1ECDSA,
+            party: 1,
+            step: 1,
+            msg_detail: vec![7; 33],
+            identity_id: "identity".to_string(),
+            share_id: "".to_string(),
+            epoch: 0,
+            sign_payload: None,
+            session_id: "session".to_string(),
+            version: MPC_PROTOCOL_V2,
+        };
+        let mut sizes = vec![];
+        for version in [MSG_VERSION_V1, MSG_VERSION_V2] {
+            let msg_wrapper = MsgWrapper {
+                seq: 1,
+                timestamp: 1,
+                action: MSG_ACTION_REQ,
+                action_code: REQ_CODE_MPC22,
+                body: encode_detail(version, &mpc22_msg).unwrap(),
+                error_msg: "".to_string(),
+                notice_id: "".to_string(),
+                version,
+                error_code: 0,
+            };
+            let frame = encode_msg(&msg_wrapper).unwrap();
+            assert_eq!(frame[0] == BINARY_FRAME_MAGIC, version == MSG_VERSION_V2);
+            let decoded = decode_msg(&frame).unwrap();
+            assert_eq!(decoded.version, version);
+            let decoded_mpc22 = decode_detail::<Mpc22Msg>(version, &decoded.body).unwrap();
+            assert_eq!(decoded_mpc22.msg_detail, mpc22_msg.msg_detail);
+            assert_eq!(decoded_mpc22.session_id, mpc22_msg.session_id);
+            sizes.push(frame.len());
+        }
+        assert!(sizes[1] < sizes[0]);
+
+        // the frame must agree with the version inside
+        let msg_v2 = MsgWrapper {
+            seq: 1,
+            timestamp: 1,
+            action: MSG_ACTION_REQ,
+            action_code: REQ_CODE_MPC22,
+            body: vec![],
+            error_msg: "".to_string(),
+            notice_id: "".to_string(),
+            version: MSG_VERSION_V2,
+            error_code: 0,
+        };
+        let json_v2 = serde_json::to_vec(&msg_v2).unwrap();
+        assert_eq!(decode_msg(&json_v2).unwrap_err(), DecodeError::FrameMismatch { binary_frame: false, version: MSG_VERSION_V2 });
+        let mut binary_v1 = encode_msg(&msg_v2).unwrap();
+        binary_v1[1..].copy_from_slice(&encode_detail(MSG_VERSION_V2, &MsgWrapper { version: MSG_VERSION_V1, ..msg_v2 }).unwrap());
+        assert_eq!(decode_msg(&binary_v1).unwrap_err(), DecodeError::FrameMismatch { binary_frame: true, version: MSG_VERSION_V1 });
+    }
+
+    #[test]
+    fn test_malformed_frames() {
+        let mpc22_msg = Mpc22Msg {
+            command: MPC_KEYGEN,
+            scope: MPC_SCOPE_SECP256K1ECDSA,
+            party: 1,
+            step: 2,
+            msg_detail: vec![1; 64],
+            identity_id: "identity".to_string(),
+            share_id: "share".to_string(),
+            epoch: 3,
+            sign_payload: None,
+            session_id: "session".to_string(),
+            version: MPC_PROTOCOL_V2,
+        };
+        for version in [MSG_VERSION_V1, MSG_VERSION_V2] {
+            let msg_wrapper = MsgWrapper {
+                seq: 7,
+                timestamp: 1,
+                action: MSG_ACTION_REQ,
+                action_code: REQ_CODE_MPC22,
+                body: encode_detail(version, &mpc22_msg).unwrap(),
+                error_msg: "".to_string(),
+                notice_id: "".to_string(),
+                version,
+                error_code: 0,
+            };
+            let frame = msg_wrapper.to_bytes().unwrap();
+            // every truncation & every single bit flip decodes to an error or a msg, never a panic
+            for len in 0..frame.len() {
+                assert!(MsgWrapper::from_bytes(&frame[..len]).is_err());
+            }
+            for i in 0..frame.len() {
+                for bit in 0..8 {
+                    let mut flipped = frame.clone();
+                    flipped[i] ^= 1 << bit;
+                    if let Ok(msg) = MsgWrapper::from_bytes(&flipped) {
+                        let _ = decode_detail::<Mpc22Msg>(msg.version, &msg.body);
+                    }
+                }
+            }
+            // a huge length prefix is refused rather than allocated
+            let mut huge = vec![BINARY_FRAME_MAGIC, 1, 1, 1, 1, 0xfd];
+            huge.extend(u64::MAX.to_le_bytes());
+            assert!(matches!(MsgWrapper::from_bytes(&huge), Err(DecodeError::Binary(_))));
+        }
+        assert!(matches!(MsgWrapper::from_bytes(b"{"), Err(DecodeError::Json(_))));
+        assert!(matches!(SavedShare::from_json(br#"{"schema_version":9}"#), Err(DecodeError::UnsupportedVersion { .. })));
+    }
+}
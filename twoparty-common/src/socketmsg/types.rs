@@ -1,4 +1,7 @@
 use serde::{Deserialize, Serialize};
+use crate::errors::DecodeError;
+use crate::payload::SignPayload;
+use crate::socketmsg::codec::decode_json;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Mpc22Msg {
@@ -7,10 +10,22 @@ pub struct Mpc22Msg {
     pub party: u8,
     pub step: u8,
     pub msg_detail: Vec<u8>,
-    // needed only when keygen
+    // needed only when keygen & identity bind/recover
     pub identity_id: String,
     // needed except keygen
     pub share_id: String,
+    // epoch of party1's share, must equal party2's, needed except keygen
+    #[serde(default)]
+    pub epoch: u32,
+    // optional unsigned payload of sign step1, party2 recomputes the message_digest from it
+    #[serde(default)]
+    pub sign_payload: Option<SignPayload>,
+    // random id of a multi-step session chosen by party1, kept by every step and resend of the session
+    #[serde(default)]
+    pub session_id: String,
+    // protocol version of the step messages, missing from party1 older than versioning
+    #[serde(default = "legacy_protocol_version")]
+    pub version: u16,
 }
 
 
@@ -18,20 +33,127 @@ pub const MPC_KEYGEN: u8 = 1;
 pub const MPC_SIGN: u8 = 2;
 pub const MPC_ROTATE: u8 = 3;
 pub const MPC_EXPORT: u8 = 4;
+// promote the pending share of a rotation, scope independent
+pub const MPC_ROTATE_COMMIT: u8 = 5;
+// query which version of a share is active, scope independent
+pub const MPC_SHARE_STATUS: u8 = 6;
+// bind (Enc(identity-secret), Enc(share)) with identity_id, scope independent
+pub const MPC_IDENTITY_BIND: u8 = 7;
+// recover Enc(share) by proving the knowledge of identity-secret, scope independent
+pub const MPC_IDENTITY_RECOVER: u8 = 8;
 
 
 pub const MPC_SCOPE_SECP256K1ECDSA: u8 = 1;
 pub const MPC_SCOPE_ED25519EDDSA: u8 = 2;
 
-#[derive(Serialize, Deserialize)]
+// versions of the step messages, party2 runs several at a time so party1 can upgrade gradually.
+// v1: steps of a session may omit session_id
+// v2: every step of a multi-step session carries the session_id of its step1
+pub const MPC_PROTOCOL_V1: u16 = 1;
+pub const MPC_PROTOCOL_V2: u16 = 2;
+
+fn legacy_protocol_version() -> u16 {
+    MPC_PROTOCOL_V1
+}
+
+pub const SAVED_SHARE_SCHEMA_VERSION: u16 = 1;
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct SavedShare {
+    pub schema_version: u16,
     pub identity_id: String,
     pub share_id: String,
     pub scope: u8,
     pub party: u8,
+    // 0 after keygen, increased by every rotation
+    pub epoch: u32,
+    // millis when the key was generated, kept across rotations
+    pub created_at: u128,
+    // millis when this epoch started
+    pub rotated_at: u128,
     pub uncompressed_pub: Vec<u8>,
     pub share_detail: Vec<u8>,
 }
 
+// SavedShare before schema_version was introduced
+#[derive(Deserialize)]
+struct LegacySavedShare {
+    identity_id: String,
+    share_id: String,
+    scope: u8,
+    party: u8,
+    uncompressed_pub: Vec<u8>,
+    share_detail: Vec<u8>,
+}
+
+impl From<LegacySavedShare> for SavedShare {
+    fn from(legacy: LegacySavedShare) -> Self {
+        SavedShare {
+            schema_version: SAVED_SHARE_SCHEMA_VERSION,
+            identity_id: legacy.identity_id,
+            share_id: legacy.share_id,
+            scope: legacy.scope,
+            party: legacy.party,
+            // rotated legacy shares can not be told apart, both sides migrate to epoch 0
+            epoch: 0,
+            created_at: 0,
+            rotated_at: 0,
+            uncompressed_pub: legacy.uncompressed_pub,
+            share_detail: legacy.share_detail,
+        }
+    }
+}
+
+impl SavedShare {
+    // parse SavedShare json of any schema version, migrate it to the current one
+    pub fn from_json(bytes: &[u8]) -> Result<SavedShare, DecodeError> {
+        let value = decode_json::<serde_json::Value>(bytes)?;
+
+        let schema_version = value.get("schema_version").and_then(|v| v.as_u64()).unwrap_or(0);
+        match schema_version {
+            0 => {
+                let legacy_result = serde_json::from_value::<LegacySavedShare>(value);
+                if legacy_result.is_err() {
+                    return Err(DecodeError::Json(legacy_result.err().unwrap().to_string()));
+                }
+                Ok(SavedShare::from(legacy_result.unwrap()))
+            }
+            1 => {
+                let share_result = serde_json::from_value::<SavedShare>(value);
+                if share_result.is_err() {
+                    return Err(DecodeError::Json(share_result.err().unwrap().to_string()));
+                }
+                Ok(share_result.unwrap())
+            }
+            _ => Err(DecodeError::UnsupportedVersion { what: "share schema", version: schema_version }),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
-pub struct EmptyMsg {}
\ No newline at end of file
+pub struct EmptyMsg {}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RotateCommitMsg {
+    pub new_share_id: String,
+}
+
+
+pub const SHARE_STATUS_NOT_FOUND: u8 = 0;
+pub const SHARE_STATUS_ACTIVE: u8 = 1;
+// rotated but not committed yet
+pub const SHARE_STATUS_PENDING: u8 = 2;
+// replaced by a committed rotation
+pub const SHARE_STATUS_RETIRED: u8 = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShareStatusMsg {
+    pub share_id: String,
+    pub status: u8,
+    // epoch of the queried share, 0 if not found
+    pub epoch: u32,
+    // the latest committed share, empty if not found or pending
+    pub active_share_id: String,
+    // the rotation waiting for commit, empty if none
+    pub pending_share_id: String,
+}
\ No newline at end of file
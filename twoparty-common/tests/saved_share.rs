@@ -0,0 +1,23 @@
+use common::socketmsg::types::{SAVED_SHARE_SCHEMA_VERSION, SavedShare};
+
+#[test]
+fn test_migrate_legacy_share() {
+    let legacy_json = r#"{"identity_id":"wangcy","share_id":"abc","scope":1,"party":1,"uncompressed_pub":[4,1,2],"share_detail":[123,125]}"#;
+    let share = SavedShare::from_json(legacy_json.as_bytes()).unwrap();
+    assert_eq!(share.schema_version, SAVED_SHARE_SCHEMA_VERSION);
+    assert_eq!(share.epoch, 0);
+    assert_eq!(share.share_id, "abc");
+    assert_eq!(share.uncompressed_pub, vec![4, 1, 2]);
+
+    // the migrated share round trips with the current schema
+    let current_json = serde_json::to_vec(&share).unwrap();
+    let reloaded = SavedShare::from_json(&current_json).unwrap();
+    assert_eq!(reloaded.schema_version, SAVED_SHARE_SCHEMA_VERSION);
+    assert_eq!(reloaded.share_detail, share.share_detail);
+}
+
+#[test]
+fn test_reject_unknown_schema() {
+    let future_json = r#"{"schema_version":99,"identity_id":"wangcy"}"#;
+    assert!(SavedShare::from_json(future_json.as_bytes()).is_err());
+}
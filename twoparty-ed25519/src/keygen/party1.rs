@@ -12,6 +12,7 @@ use crate::keygen::party2::{Party2KeygenMsg1, Party2KeygenMsg2};
 
 pub type Party1KeygenMsg1 = DLogCommitment;
 
+#[derive(Serialize, Deserialize)]
 pub struct Party1InitAssets {
     pub x1: Scalar<Ed25519>,
     pub prefix: [u8; 32],
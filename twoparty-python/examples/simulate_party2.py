@@ -0,0 +1,27 @@
+# keygen & sign of secp256k1 with both parties in python, party2 is simulated instead of running mpc-server.
+# build the module first: cd twoparty-python && maturin develop
+import json
+
+import twoparty
+
+# keygen
+party1_msg1, witness, keypair1 = twoparty.secp256k1_keygen_party1_step1()
+party2_msg1, keypair2 = twoparty.secp256k1_keygen_party2_step1()
+party1_msg2, share1 = twoparty.secp256k1_keygen_party1_step2(party2_msg1, witness, keypair1)
+share2 = twoparty.secp256k1_keygen_party2_step2(party1_msg2, party1_msg1, keypair2)
+
+# sign
+digest = bytes(range(32))
+party1_msg1, witness, eph_keypair1 = twoparty.secp256k1_sign_party1_step1()
+party2_msg1, eph_keypair2 = twoparty.secp256k1_sign_party2_step1()
+party1_msg2, k2_G = twoparty.secp256k1_sign_party1_step2(party2_msg1, witness, digest, eph_keypair1, share1)
+party2_msg2 = twoparty.secp256k1_sign_party2_step2(party1_msg2, party1_msg1, share2, eph_keypair2)
+signature = twoparty.secp256k1_sign_party1_step3(party2_msg2, share1, eph_keypair1, digest, k2_G)
+print("signature", json.loads(signature))
+
+# a tampered msg fails with the code of MpcErrorKind
+try:
+    twoparty.secp256k1_keygen_party1_step2("{}", witness, keypair1)
+except twoparty.MpcException as e:
+    message, code = e.args
+    print("rejected", code, message)
@@ -0,0 +1,128 @@
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use tokio::runtime::Runtime;
+use common::errors::{MpcError, MpcErrorKind};
+use common::payload::SignPayload;
+use common::socketmsg::types::SavedShare;
+use twoparty_client::kms::{Kms, LocalKms};
+use twoparty_client::mpc::client::{MpcClient, MpcClientConfig, MpcSignature};
+use crate::{from_json, mpc_err, to_json};
+
+// one connection to party2, see MpcClient. shares & results are json strings, the gil is released while waiting
+#[pyclass]
+pub struct Client {
+    rt: Runtime,
+    client: MpcClient,
+}
+
+#[pymethods]
+impl Client {
+    // config is json of MpcClientConfig
+    #[staticmethod]
+    fn connect(py: Python, config: &str) -> PyResult<Client> {
+        let config = from_json::<MpcClientConfig>(config, "config")?;
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .map_err(|e| mpc_err(MpcError::new(MpcErrorKind::Internal, e.to_string())))?;
+        let client = py.allow_threads(|| rt.block_on(MpcClient::connect(config)))
+            .map_err(mpc_err)?;
+        Ok(Client { rt, client })
+    }
+
+    fn is_connected(&self) -> bool {
+        self.client.is_connected()
+    }
+
+    fn load_share(&self, share: &str) -> PyResult<()> {
+        let saved_share = SavedShare::from_json(share.as_bytes())
+            .map_err(|e| mpc_err(e.into()))?;
+        self.client.load_share(saved_share).map_err(mpc_err)
+    }
+
+    fn unload_share(&self, share_id: &str) -> bool {
+        self.client.unload_share(share_id).is_some()
+    }
+
+    fn share_ids(&self) -> Vec<String> {
+        self.client.share_ids()
+    }
+
+    // return json of the new SavedShare, which is loaded
+    fn keygen(&self, py: Python, scope: u8) -> PyResult<String> {
+        let saved_share = py.allow_threads(|| self.rt.block_on(self.client.keygen(scope)))
+            .map_err(mpc_err)?;
+        Ok(to_json(&saved_share))
+    }
+
+    // return json of Secp256k1Sig, or the 64 bytes R || s of ed25519
+    fn sign(&self, py: Python, share_id: &str, message_digest: Vec<u8>) -> PyResult<PyObject> {
+        let sig = py.allow_threads(|| self.rt.block_on(self.client.sign(share_id, message_digest)))
+            .map_err(mpc_err)?;
+        Ok(signature_object(py, sig))
+    }
+
+    // sign_payload is json of SignPayload
+    fn sign_payload(&self, py: Python, share_id: &str, sign_payload: &str) -> PyResult<PyObject> {
+        let sign_payload = from_json::<SignPayload>(sign_payload, "sign_payload")?;
+        let sig = py.allow_threads(|| self.rt.block_on(self.client.sign_payload(share_id, sign_payload)))
+            .map_err(mpc_err)?;
+        Ok(signature_object(py, sig))
+    }
+
+    // return json of the pending SavedShare, persist it before rotate_commit
+    fn rotate(&self, py: Python, share_id: &str) -> PyResult<String> {
+        let new_share = py.allow_threads(|| self.rt.block_on(self.client.rotate(share_id)))
+            .map_err(mpc_err)?;
+        Ok(to_json(&new_share))
+    }
+
+    // kms_key, the 32 bytes LocalKms key of the recovery binding, binds the new share in place of the old one
+    #[pyo3(signature = (share_id, new_share, kms_key=None))]
+    fn rotate_commit(&self, py: Python, share_id: &str, new_share: &str, kms_key: Option<&[u8]>) -> PyResult<()> {
+        let new_share = SavedShare::from_json(new_share.as_bytes())
+            .map_err(|e| mpc_err(e.into()))?;
+        let option_kms = kms_key.map(local_kms).transpose()?;
+        py.allow_threads(|| self.rt.block_on(self.client.rotate_commit(share_id, new_share, option_kms.as_ref().map(|kms| kms as &dyn Kms))))
+            .map_err(mpc_err)
+    }
+
+    fn identity_bind(&self, py: Python, share_id: &str, kms_key: &[u8]) -> PyResult<()> {
+        let kms = local_kms(kms_key)?;
+        py.allow_threads(|| self.rt.block_on(self.client.identity_bind(share_id, &kms)))
+            .map_err(mpc_err)
+    }
+
+    // return json of the recovered SavedShare array, they are loaded into the client
+    fn identity_recover(&self, py: Python, kms_key: &[u8]) -> PyResult<String> {
+        let kms = local_kms(kms_key)?;
+        let saved_shares = py.allow_threads(|| self.rt.block_on(self.client.identity_recover(&kms)))
+            .map_err(mpc_err)?;
+        Ok(to_json(&saved_shares))
+    }
+
+    // return the hex encoded private key, secp256k1 shares only
+    fn export(&self, py: Python, share_id: &str) -> PyResult<String> {
+        py.allow_threads(|| self.rt.block_on(self.client.export(share_id)))
+            .map_err(mpc_err)
+    }
+
+    // return json of ShareStatusMsg
+    fn share_status(&self, py: Python, share_id: &str) -> PyResult<String> {
+        let status = py.allow_threads(|| self.rt.block_on(self.client.share_status(share_id)))
+            .map_err(mpc_err)?;
+        Ok(to_json(&status))
+    }
+}
+
+fn signature_object(py: Python, sig: MpcSignature) -> PyObject {
+    match sig {
+        MpcSignature::Secp256k1(sig) => to_json(&sig).into_py(py),
+        MpcSignature::Ed25519(sig) => PyBytes::new(py, &sig).into_py(py),
+    }
+}
+
+fn local_kms(kms_key: &[u8]) -> PyResult<LocalKms> {
+    LocalKms::from_slice(kms_key).map_err(|e| mpc_err(MpcError::new(MpcErrorKind::InvalidRequest, e)))
+}
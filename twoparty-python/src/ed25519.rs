@@ -0,0 +1,176 @@
+use curv::elliptic::curves::{Ed25519, Scalar};
+use pyo3::prelude::*;
+use common::dlog::{CurveKeyPair, DLogWitness};
+use twoparty_ed25519::{keygen, rotate, sign};
+use twoparty_ed25519::generic::share::Ed25519Share;
+use twoparty_ed25519::keygen::party1::{Party1InitAssets, Party1KeygenMsg1, Party1KeygenMsg2};
+use twoparty_ed25519::keygen::party2::{Party2InitAssets, Party2KeygenMsg1, Party2KeygenMsg2};
+use twoparty_ed25519::rotate::party1::{Party1RotateMsg1, Party1RotateMsg2};
+use twoparty_ed25519::rotate::party2::{Party2RotateMsg1, Party2RotateMsg2};
+use twoparty_ed25519::sign::party1::{Party1SignMsg1, Party1SignMsg2};
+use twoparty_ed25519::sign::party2::{Party2SignMsg1, Party2SignMsg2};
+use crate::{from_json, to_json, two_party_err};
+
+// every arg & result is the json of the value of the same name in twoparty_ed25519,
+// assets, witnesses, keypairs & shares are secrets of the party that made them
+
+#[pyfunction]
+pub fn ed25519_keygen_party1_step1() -> (String, String) {
+    let (party1_msg1, assets1) = keygen::party1::party1_step1();
+    (to_json(&party1_msg1), to_json(&assets1))
+}
+
+#[pyfunction]
+pub fn ed25519_keygen_party2_step1() -> (String, String) {
+    let (party2_msg1, assets2) = keygen::party2::party2_step1();
+    (to_json(&party2_msg1), to_json(&assets2))
+}
+
+// return (party1_msg2, pending_share1), pending_share1 is completed by step3
+#[pyfunction]
+pub fn ed25519_keygen_party1_step2(party2_msg1: &str, assets1: &str) -> PyResult<(String, String)> {
+    let (party1_msg2, pending_share1) = keygen::party1::party1_step2(
+        from_json::<Party2KeygenMsg1>(party2_msg1, "party2_msg1")?,
+        from_json::<Party1InitAssets>(assets1, "assets1")?,
+    ).map_err(two_party_err)?;
+    Ok((to_json(&party1_msg2), to_json(&pending_share1)))
+}
+
+#[pyfunction]
+pub fn ed25519_keygen_party2_step2(party1_msg2: &str, party1_msg1: &str, assets2: &str) -> PyResult<(String, String)> {
+    let (party2_msg2, share2) = keygen::party2::party2_step2(
+        from_json::<Party1KeygenMsg2>(party1_msg2, "party1_msg2")?,
+        from_json::<Party1KeygenMsg1>(party1_msg1, "party1_msg1")?,
+        from_json::<Party2InitAssets>(assets2, "assets2")?,
+    ).map_err(two_party_err)?;
+    Ok((to_json(&party2_msg2), to_json(&share2)))
+}
+
+#[pyfunction]
+pub fn ed25519_keygen_party1_step3(party2_msg2: &str, pending_share1: &str) -> PyResult<String> {
+    let share1 = keygen::party1::party1_step3(
+        from_json::<Party2KeygenMsg2>(party2_msg2, "party2_msg2")?,
+        from_json::<Ed25519Share>(pending_share1, "pending_share1")?,
+    ).map_err(two_party_err)?;
+    Ok(to_json(&share1))
+}
+
+// return (party1_msg1, eph_keypair, eph_witness)
+#[pyfunction]
+pub fn ed25519_sign_party1_step1(share1: &str, message_digest: Vec<u8>) -> PyResult<(String, String, String)> {
+    let (party1_msg1, eph_keypair, eph_witness) = sign::party1::party1_step1(
+        &from_json::<Ed25519Share>(share1, "share1")?,
+        &message_digest,
+    );
+    Ok((to_json(&party1_msg1), to_json(&eph_keypair), to_json(&eph_witness)))
+}
+
+#[pyfunction]
+pub fn ed25519_sign_party2_step1(party1_msg1: &str, share2: &str) -> PyResult<(String, String)> {
+    let (party2_msg1, eph_keypair) = sign::party2::party2_step1(
+        from_json::<Party1SignMsg1>(party1_msg1, "party1_msg1")?,
+        &from_json::<Ed25519Share>(share2, "share2")?,
+    );
+    Ok((to_json(&party2_msg1), to_json(&eph_keypair)))
+}
+
+#[pyfunction]
+pub fn ed25519_sign_party1_step2(party2_msg1: &str, eph_witness: &str, message_digest: &[u8], eph_keypair: &str, share1: &str) -> PyResult<String> {
+    let party1_msg2 = sign::party1::party1_step2(
+        from_json::<Party2SignMsg1>(party2_msg1, "party2_msg1")?,
+        from_json::<DLogWitness<Ed25519>>(eph_witness, "eph_witness")?,
+        message_digest,
+        from_json::<CurveKeyPair<Ed25519>>(eph_keypair, "eph_keypair")?,
+        &from_json::<Ed25519Share>(share1, "share1")?,
+    ).map_err(two_party_err)?;
+    Ok(to_json(&party1_msg2))
+}
+
+#[pyfunction]
+pub fn ed25519_sign_party2_step2(party1_msg2: &str, party1_msg1: &str, eph_keypair: &str, share2: &str) -> PyResult<String> {
+    let party2_msg2 = sign::party2::party2_step2(
+        from_json::<Party1SignMsg2>(party1_msg2, "party1_msg2")?,
+        from_json::<Party1SignMsg1>(party1_msg1, "party1_msg1")?,
+        from_json::<CurveKeyPair<Ed25519>>(eph_keypair, "eph_keypair")?,
+        &from_json::<Ed25519Share>(share2, "share2")?,
+    ).map_err(two_party_err)?;
+    Ok(to_json(&party2_msg2))
+}
+
+// the partial signature is taken from party1_msg2, return json of EdDSASignature
+#[pyfunction]
+pub fn ed25519_sign_party1_step3(party2_msg2: &str, party1_msg2: &str, share1: &str, message_digest: Vec<u8>) -> PyResult<String> {
+    let party1_msg2 = from_json::<Party1SignMsg2>(party1_msg2, "party1_msg2")?;
+    let signature = sign::party1::party1_step3(
+        from_json::<Party2SignMsg2>(party2_msg2, "party2_msg2")?,
+        &party1_msg2.partial_sig,
+        &from_json::<Ed25519Share>(share1, "share1")?,
+        &message_digest,
+    ).map_err(two_party_err)?;
+    Ok(to_json(&signature))
+}
+
+// return (party1_msg1, delta_keypair, delta_witness)
+#[pyfunction]
+pub fn ed25519_rotate_party1_step1() -> (String, String, String) {
+    let (party1_msg1, delta_keypair, delta_witness) = rotate::party1::party1_step1();
+    (to_json(&party1_msg1), to_json(&delta_keypair), to_json(&delta_witness))
+}
+
+#[pyfunction]
+pub fn ed25519_rotate_party2_step1() -> (String, String) {
+    let (party2_msg1, delta_keypair) = rotate::party2::party2_step1();
+    (to_json(&party2_msg1), to_json(&delta_keypair))
+}
+
+// return (party1_msg2, new_x1), new_x1 is needed by step3
+#[pyfunction]
+pub fn ed25519_rotate_party1_step2(party2_msg1: &str, delta_witness: &str, delta_keypair: &str, old_share1: &str) -> PyResult<(String, String)> {
+    let (party1_msg2, new_x1) = rotate::party1::party1_step2(
+        from_json::<Party2RotateMsg1>(party2_msg1, "party2_msg1")?,
+        from_json::<DLogWitness<Ed25519>>(delta_witness, "delta_witness")?,
+        from_json::<CurveKeyPair<Ed25519>>(delta_keypair, "delta_keypair")?,
+        &from_json::<Ed25519Share>(old_share1, "old_share1")?,
+    ).map_err(two_party_err)?;
+    Ok((to_json(&party1_msg2), to_json(&new_x1)))
+}
+
+#[pyfunction]
+pub fn ed25519_rotate_party2_step2(party1_msg2: &str, party1_msg1: &str, delta_keypair: &str, old_share2: &str) -> PyResult<(String, String)> {
+    let (party2_msg2, share2) = rotate::party2::party2_step2(
+        from_json::<Party1RotateMsg2>(party1_msg2, "party1_msg2")?,
+        from_json::<Party1RotateMsg1>(party1_msg1, "party1_msg1")?,
+        from_json::<CurveKeyPair<Ed25519>>(delta_keypair, "delta_keypair")?,
+        &from_json::<Ed25519Share>(old_share2, "old_share2")?,
+    ).map_err(two_party_err)?;
+    Ok((to_json(&party2_msg2), to_json(&share2)))
+}
+
+#[pyfunction]
+pub fn ed25519_rotate_party1_step3(party2_msg2: &str, new_x1: &str, old_share1: &str) -> PyResult<String> {
+    let share1 = rotate::party1::party1_step3(
+        from_json::<Party2RotateMsg2>(party2_msg2, "party2_msg2")?,
+        from_json::<Scalar<Ed25519>>(new_x1, "new_x1")?,
+        &from_json::<Ed25519Share>(old_share1, "old_share1")?,
+    ).map_err(two_party_err)?;
+    Ok(to_json(&share1))
+}
+
+pub fn register(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(ed25519_keygen_party1_step1, m)?)?;
+    m.add_function(wrap_pyfunction!(ed25519_keygen_party2_step1, m)?)?;
+    m.add_function(wrap_pyfunction!(ed25519_keygen_party1_step2, m)?)?;
+    m.add_function(wrap_pyfunction!(ed25519_keygen_party2_step2, m)?)?;
+    m.add_function(wrap_pyfunction!(ed25519_keygen_party1_step3, m)?)?;
+    m.add_function(wrap_pyfunction!(ed25519_sign_party1_step1, m)?)?;
+    m.add_function(wrap_pyfunction!(ed25519_sign_party2_step1, m)?)?;
+    m.add_function(wrap_pyfunction!(ed25519_sign_party1_step2, m)?)?;
+    m.add_function(wrap_pyfunction!(ed25519_sign_party2_step2, m)?)?;
+    m.add_function(wrap_pyfunction!(ed25519_sign_party1_step3, m)?)?;
+    m.add_function(wrap_pyfunction!(ed25519_rotate_party1_step1, m)?)?;
+    m.add_function(wrap_pyfunction!(ed25519_rotate_party2_step1, m)?)?;
+    m.add_function(wrap_pyfunction!(ed25519_rotate_party1_step2, m)?)?;
+    m.add_function(wrap_pyfunction!(ed25519_rotate_party2_step2, m)?)?;
+    m.add_function(wrap_pyfunction!(ed25519_rotate_party1_step3, m)?)?;
+    Ok(())
+}
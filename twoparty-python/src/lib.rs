@@ -0,0 +1,62 @@
+#![allow(non_snake_case)]
+
+// python bindings: Client drives party1 against mpc-server, the step functions of secp256k1 & ed25519 take and
+// return json so a test can play both parties, or simulate party2, in python.
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use common::address::share_address;
+use common::errors::{MpcError, MpcErrorKind, TwoPartyError};
+use common::socketmsg::types::{MPC_SCOPE_ED25519EDDSA, MPC_SCOPE_SECP256K1ECDSA, SavedShare};
+
+pub mod client;
+pub mod secp256k1;
+pub mod ed25519;
+
+#[cfg(test)]
+mod test;
+
+// args are (message, code), code is one of MpcErrorKind
+create_exception!(twoparty, MpcException, PyException);
+
+fn mpc_err(e: MpcError) -> PyErr {
+    let code = e.code();
+    MpcException::new_err((e.message, code))
+}
+
+fn two_party_err(e: TwoPartyError) -> PyErr {
+    mpc_err(e.into())
+}
+
+fn from_json<T: DeserializeOwned>(json: &str, name: &str) -> PyResult<T> {
+    serde_json::from_str(json)
+        .map_err(|e| mpc_err(MpcError::new(MpcErrorKind::MalformedMessage, format!("fail to parse {}:{}", name, e))))
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+// share is json of SavedShare, the eth address of a secp256k1 share or the sui address of an ed25519 one
+#[pyfunction]
+fn address(share: &str) -> PyResult<String> {
+    let saved_share = SavedShare::from_json(share.as_bytes())
+        .map_err(|e| mpc_err(e.into()))?;
+    share_address(&saved_share)
+        .map_err(|e| mpc_err(MpcError::new(MpcErrorKind::Unsupported, e)))
+}
+
+#[pymodule]
+fn twoparty(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add("MpcException", py.get_type::<MpcException>())?;
+    m.add("SCOPE_SECP256K1ECDSA", MPC_SCOPE_SECP256K1ECDSA)?;
+    m.add("SCOPE_ED25519EDDSA", MPC_SCOPE_ED25519EDDSA)?;
+    m.add_class::<client::Client>()?;
+    m.add_function(wrap_pyfunction!(address, m)?)?;
+    secp256k1::register(m)?;
+    ed25519::register(m)?;
+    Ok(())
+}
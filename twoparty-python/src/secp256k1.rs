@@ -0,0 +1,201 @@
+use curv::elliptic::curves::{Point, Secp256k1};
+use pyo3::prelude::*;
+use common::dlog::{CurveKeyPair, DLogWitness};
+use twoparty_secp256k1::{export, keygen, rotate, sign};
+use twoparty_secp256k1::export::party1::Party1ExportMsg2;
+use twoparty_secp256k1::export::party2::{Party2ExportMsg1, Party2ExportMsg2};
+use twoparty_secp256k1::generic::share::{Party1Share, Party2Share};
+use twoparty_secp256k1::keygen::party1::{Party1KeyGenMsg1, Party1KeygenMsg2};
+use twoparty_secp256k1::keygen::party2::Party2KeyGenMsg1;
+use twoparty_secp256k1::rotate::party1::{Party1RotateMsg1, Party1RotateMsg2};
+use twoparty_secp256k1::rotate::party2::{Party2RotateMsg1, Party2RotateMsg2};
+use twoparty_secp256k1::sign::party1::{Party1SignMsg1, Party1SignMsg2};
+use twoparty_secp256k1::sign::party2::{Party2SignMsg1, Party2SignMsg2};
+use crate::{from_json, to_json, two_party_err};
+
+// every arg & result is the json of the value of the same name in twoparty_secp256k1,
+// witnesses, keypairs & shares are secrets of the party that made them
+
+#[pyfunction]
+pub fn secp256k1_keygen_party1_step1() -> (String, String, String) {
+    let (party1_msg1, witness, keypair) = keygen::party1::party1_step1();
+    (to_json(&party1_msg1), to_json(&witness), to_json(&keypair))
+}
+
+#[pyfunction]
+pub fn secp256k1_keygen_party2_step1() -> (String, String) {
+    let (party2_msg1, keypair) = keygen::party2::party2_step1();
+    (to_json(&party2_msg1), to_json(&keypair))
+}
+
+#[pyfunction]
+pub fn secp256k1_keygen_party1_step2(party2_msg1: &str, witness: &str, keypair: &str) -> PyResult<(String, String)> {
+    let (party1_msg2, share1) = keygen::party1::party1_step2(
+        from_json::<Party2KeyGenMsg1>(party2_msg1, "party2_msg1")?,
+        from_json::<DLogWitness<Secp256k1>>(witness, "witness")?,
+        from_json::<CurveKeyPair<Secp256k1>>(keypair, "keypair")?,
+    ).map_err(two_party_err)?;
+    Ok((to_json(&party1_msg2), to_json(&share1)))
+}
+
+#[pyfunction]
+pub fn secp256k1_keygen_party2_step2(party1_msg2: &str, party1_msg1: &str, keypair: &str) -> PyResult<String> {
+    let share2 = keygen::party2::party2_step2(
+        from_json::<Party1KeygenMsg2>(party1_msg2, "party1_msg2")?,
+        from_json::<Party1KeyGenMsg1>(party1_msg1, "party1_msg1")?,
+        from_json::<CurveKeyPair<Secp256k1>>(keypair, "keypair")?,
+    ).map_err(two_party_err)?;
+    Ok(to_json(&share2))
+}
+
+#[pyfunction]
+pub fn secp256k1_sign_party1_step1() -> (String, String, String) {
+    let (party1_msg1, witness, eph_keypair) = sign::party1::party1_step1();
+    (to_json(&party1_msg1), to_json(&witness), to_json(&eph_keypair))
+}
+
+#[pyfunction]
+pub fn secp256k1_sign_party2_step1() -> (String, String) {
+    let (party2_msg1, eph_keypair) = sign::party2::party2_step1();
+    (to_json(&party2_msg1), to_json(&eph_keypair))
+}
+
+// return (party1_msg2, k2_G), k2_G is needed by step3
+#[pyfunction]
+pub fn secp256k1_sign_party1_step2(party2_msg1: &str, witness: &str, message_digest: &[u8], eph_keypair: &str, share1: &str) -> PyResult<(String, String)> {
+    let (party1_msg2, k2_G) = sign::party1::party1_step2(
+        from_json::<Party2SignMsg1>(party2_msg1, "party2_msg1")?,
+        from_json::<DLogWitness<Secp256k1>>(witness, "witness")?,
+        message_digest,
+        &from_json::<CurveKeyPair<Secp256k1>>(eph_keypair, "eph_keypair")?,
+        &from_json::<Party1Share>(share1, "share1")?,
+    ).map_err(two_party_err)?;
+    Ok((to_json(&party1_msg2), to_json(&k2_G)))
+}
+
+#[pyfunction]
+pub fn secp256k1_sign_party2_step2(party1_msg2: &str, party1_msg1: &str, share2: &str, eph_keypair: &str) -> PyResult<String> {
+    let party2_msg2 = sign::party2::party2_step2(
+        from_json::<Party1SignMsg2>(party1_msg2, "party1_msg2")?,
+        from_json::<Party1SignMsg1>(party1_msg1, "party1_msg1")?,
+        &from_json::<Party2Share>(share2, "share2")?,
+        from_json::<CurveKeyPair<Secp256k1>>(eph_keypair, "eph_keypair")?,
+    ).map_err(two_party_err)?;
+    Ok(to_json(&party2_msg2))
+}
+
+// return json of ECDSASignature
+#[pyfunction]
+pub fn secp256k1_sign_party1_step3(party2_msg2: &str, share1: &str, eph_keypair: &str, message_digest: &[u8], k2_G: &str) -> PyResult<String> {
+    let signature = sign::party1::party1_step3(
+        from_json::<Party2SignMsg2>(party2_msg2, "party2_msg2")?,
+        &from_json::<Party1Share>(share1, "share1")?,
+        from_json::<CurveKeyPair<Secp256k1>>(eph_keypair, "eph_keypair")?,
+        message_digest,
+        from_json::<Point<Secp256k1>>(k2_G, "k2_G")?,
+    ).map_err(two_party_err)?;
+    Ok(to_json(&signature))
+}
+
+#[pyfunction]
+pub fn secp256k1_rotate_party1_step1() -> (String, String, String) {
+    let (party1_msg1, seed_witness, seed_keypair) = rotate::party1::party1_step1();
+    (to_json(&party1_msg1), to_json(&seed_witness), to_json(&seed_keypair))
+}
+
+#[pyfunction]
+pub fn secp256k1_rotate_party2_step1() -> (String, String) {
+    let (party2_msg1, seed_keypair) = rotate::party2::party2_step1();
+    (to_json(&party2_msg1), to_json(&seed_keypair))
+}
+
+// return (party1_msg2, pending_share1), pending_share1 is completed by step3
+#[pyfunction]
+pub fn secp256k1_rotate_party1_step2(party2_msg1: &str, seed_witness: &str, seed_keypair: &str, old_share1: &str) -> PyResult<(String, String)> {
+    let (party1_msg2, pending_share1) = rotate::party1::party1_step2(
+        from_json::<Party2RotateMsg1>(party2_msg1, "party2_msg1")?,
+        from_json::<DLogWitness<Secp256k1>>(seed_witness, "seed_witness")?,
+        from_json::<CurveKeyPair<Secp256k1>>(seed_keypair, "seed_keypair")?,
+        &from_json::<Party1Share>(old_share1, "old_share1")?,
+    ).map_err(two_party_err)?;
+    Ok((to_json(&party1_msg2), to_json(&pending_share1)))
+}
+
+#[pyfunction]
+pub fn secp256k1_rotate_party2_step2(party1_msg2: &str, party1_msg1: &str, seed_keypair: &str, old_share2: &str) -> PyResult<(String, String)> {
+    let (party2_msg2, share2) = rotate::party2::party2_step2(
+        from_json::<Party1RotateMsg2>(party1_msg2, "party1_msg2")?,
+        from_json::<Party1RotateMsg1>(party1_msg1, "party1_msg1")?,
+        from_json::<CurveKeyPair<Secp256k1>>(seed_keypair, "seed_keypair")?,
+        &from_json::<Party2Share>(old_share2, "old_share2")?,
+    ).map_err(two_party_err)?;
+    Ok((to_json(&party2_msg2), to_json(&share2)))
+}
+
+#[pyfunction]
+pub fn secp256k1_rotate_party1_step3(party2_msg2: &str, pending_share1: &str) -> PyResult<String> {
+    let share1 = rotate::party1::party1_step3(
+        from_json::<Party2RotateMsg2>(party2_msg2, "party2_msg2")?,
+        from_json::<Party1Share>(pending_share1, "pending_share1")?,
+    ).map_err(two_party_err)?;
+    Ok(to_json(&share1))
+}
+
+// export starts at party2, party1_step1 has nothing to send
+#[pyfunction]
+pub fn secp256k1_export_party2_step1() -> String {
+    to_json(&export::party2::party2_step1())
+}
+
+#[pyfunction]
+pub fn secp256k1_export_party1_step2(party2_msg1: &str, share1: &str) -> PyResult<String> {
+    let party1_msg2 = export::party1::party1_step2(
+        from_json::<Party2ExportMsg1>(party2_msg1, "party2_msg1")?,
+        &from_json::<Party1Share>(share1, "share1")?,
+    );
+    Ok(to_json(&party1_msg2))
+}
+
+// the challenge is taken from party2_msg1
+#[pyfunction]
+pub fn secp256k1_export_party2_step2(party1_msg2: &str, party2_msg1: &str, share2: &str) -> PyResult<String> {
+    let party2_msg1 = from_json::<Party2ExportMsg1>(party2_msg1, "party2_msg1")?;
+    let party2_msg2 = export::party2::party2_step2(
+        from_json::<Party1ExportMsg2>(party1_msg2, "party1_msg2")?,
+        &party2_msg1.challenge,
+        &from_json::<Party2Share>(share2, "share2")?,
+    ).map_err(two_party_err)?;
+    Ok(to_json(&party2_msg2))
+}
+
+// return json of the private key as BigInt
+#[pyfunction]
+pub fn secp256k1_export_party1_step3(party2_msg2: &str, share1: &str) -> PyResult<String> {
+    let x = export::party1::party1_step3(
+        from_json::<Party2ExportMsg2>(party2_msg2, "party2_msg2")?,
+        &from_json::<Party1Share>(share1, "share1")?,
+    ).map_err(two_party_err)?;
+    Ok(to_json(&x))
+}
+
+pub fn register(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(secp256k1_keygen_party1_step1, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_keygen_party2_step1, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_keygen_party1_step2, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_keygen_party2_step2, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_sign_party1_step1, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_sign_party2_step1, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_sign_party1_step2, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_sign_party2_step2, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_sign_party1_step3, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_rotate_party1_step1, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_rotate_party2_step1, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_rotate_party1_step2, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_rotate_party2_step2, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_rotate_party1_step3, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_export_party2_step1, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_export_party1_step2, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_export_party2_step2, m)?)?;
+    m.add_function(wrap_pyfunction!(secp256k1_export_party1_step3, m)?)?;
+    Ok(())
+}
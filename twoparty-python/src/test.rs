@@ -0,0 +1,38 @@
+use twoparty_ed25519::generic::share::Ed25519Share;
+use twoparty_ed25519::sign::EdDSASignature;
+use crate::ed25519::*;
+
+// both parties through the json functions, as a python test would
+fn json_sign(share1: &str, share2: &str, message_digest: &[u8]) -> EdDSASignature {
+    let (party1_msg1, eph_keypair1, eph_witness) = ed25519_sign_party1_step1(share1, message_digest.to_vec()).unwrap();
+    let (party2_msg1, eph_keypair2) = ed25519_sign_party2_step1(&party1_msg1, share2).unwrap();
+    let party1_msg2 = ed25519_sign_party1_step2(&party2_msg1, &eph_witness, message_digest, &eph_keypair1, share1).unwrap();
+    let party2_msg2 = ed25519_sign_party2_step2(&party1_msg2, &party1_msg1, &eph_keypair2, share2).unwrap();
+    let sig = ed25519_sign_party1_step3(&party2_msg2, &party1_msg2, share1, message_digest.to_vec()).unwrap();
+    serde_json::from_str(&sig).unwrap()
+}
+
+#[test]
+fn test_ed25519_json_steps() {
+    let (party1_msg1, assets1) = ed25519_keygen_party1_step1();
+    let (party2_msg1, assets2) = ed25519_keygen_party2_step1();
+    let (party1_msg2, pending_share1) = ed25519_keygen_party1_step2(&party2_msg1, &assets1).unwrap();
+    let (party2_msg2, share2) = ed25519_keygen_party2_step2(&party1_msg2, &party1_msg1, &assets2).unwrap();
+    let share1 = ed25519_keygen_party1_step3(&party2_msg2, &pending_share1).unwrap();
+
+    let message_digest = vec![1, 2, 3, 4];
+    let sig = json_sign(&share1, &share2, &message_digest);
+    let inner_share1 = serde_json::from_str::<Ed25519Share>(&share1).unwrap();
+    assert!(sig.verify(&message_digest, &inner_share1));
+
+    let (party1_msg1, delta_keypair1, delta_witness) = ed25519_rotate_party1_step1();
+    let (party2_msg1, delta_keypair2) = ed25519_rotate_party2_step1();
+    let (party1_msg2, new_x1) = ed25519_rotate_party1_step2(&party2_msg1, &delta_witness, &delta_keypair1, &share1).unwrap();
+    let (party2_msg2, new_share2) = ed25519_rotate_party2_step2(&party1_msg2, &party1_msg1, &delta_keypair2, &share2).unwrap();
+    let new_share1 = ed25519_rotate_party1_step3(&party2_msg2, &new_x1, &share1).unwrap();
+    let sig_after_rotate = json_sign(&new_share1, &new_share2, &message_digest);
+    assert_eq!(sig_after_rotate.to_sig_bytes(), sig.to_sig_bytes());
+
+    // a bad json is a malformed message
+    assert!(ed25519_keygen_party1_step2("{}", &assets1).is_err());
+}
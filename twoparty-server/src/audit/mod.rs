@@ -0,0 +1,301 @@
+//! Append-only audit log of keygen, sign, rotate, rotate commit & rollback, and export.
+//!
+//! every entry carries the hash of its predecessor, and its own hash over the entry with an empty hash,
+//! so editing or deleting an entry breaks the chain. truncating the tail is only detectable against a
+//! previously recorded head hash, `verify-audit` prints the head hash for that purpose.
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::sync::LazyLock;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+use tokio::task::spawn_blocking;
+use tracing::{error, warn};
+use common::errors::{MpcErrorKind, TwoPartyError};
+use common::get_tsp;
+use common::payload::DecodedPayload;
+use common::socketmsg::types::{MPC_EXPORT, MPC_KEYGEN, MPC_ROTATE, MPC_ROTATE_COMMIT, MPC_SIGN};
+use crate::config::AppConfig;
+use crate::metrics::{record_operation_failure, record_operation_success, storage_timer};
+
+pub const AUDIT_ACTION_KEYGEN: &str = "keygen";
+pub const AUDIT_ACTION_SIGN: &str = "sign";
+pub const AUDIT_ACTION_ROTATE: &str = "rotate";
+pub const AUDIT_ACTION_EXPORT: &str = "export";
+pub const AUDIT_ACTION_ROTATE_COMMIT: &str = "rotate_commit";
+// the pending share of a rotation not committed in time is discarded by party2
+pub const AUDIT_ACTION_ROTATE_ROLLBACK: &str = "rotate_rollback";
+
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AuditEntry {
+    pub index: u64,
+    // millis
+    pub timestamp: u128,
+    pub action: String,
+    pub scope: u8,
+    pub identity_id: String,
+    // the share operated on, the new share for keygen
+    pub share_id: String,
+    // rotate only, the pending share
+    pub new_share_id: String,
+    // hex encoded message_digest, sign only
+    pub digest: String,
+    // decoded sign_payload, if party1 sent it
+    pub payload: Option<DecodedPayload>,
+    pub peer: String,
+    pub success: bool,
+    pub error_msg: String,
+    pub two_party_error: Option<TwoPartyError>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    pub fn new(action: &str, scope: u8, peer: &str, identity_id: &str, share_id: &str) -> Self {
+        AuditEntry {
+            action: action.to_string(),
+            scope,
+            peer: peer.to_string(),
+            identity_id: identity_id.to_string(),
+            share_id: share_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn compute_hash(&self) -> String {
+        let mut unsealed = self.clone();
+        unsealed.hash = "".to_string();
+        let entry_bytes = serde_json::to_vec(&unsealed).unwrap();
+        hex::encode(Sha256::digest(&entry_bytes))
+    }
+}
+
+// index & hash of the last entry
+#[derive(Clone)]
+struct AuditHead {
+    next_index: u64,
+    last_hash: String,
+}
+
+impl Default for AuditHead {
+    fn default() -> Self {
+        AuditHead { next_index: 0, last_hash: GENESIS_HASH.to_string() }
+    }
+}
+
+// None until the head is loaded from the log file
+static AUDIT_HEAD: LazyLock<Mutex<Option<AuditHead>>> = LazyLock::new(|| Mutex::new(None));
+
+fn seal(mut entry: AuditEntry, head: &AuditHead) -> AuditEntry {
+    entry.index = head.next_index;
+    entry.prev_hash = head.last_hash.clone();
+    entry.hash = entry.compute_hash();
+    entry
+}
+
+fn load_head(path: &str) -> Result<AuditHead, String> {
+    let file = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(AuditHead::default()),
+        Err(e) => return Err(e.to_string()),
+    };
+    let mut head = AuditHead::default();
+    let mut reader = BufReader::new(&file);
+    let mut complete_len = 0u64;
+    let mut line = vec![];
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        if line.last() != Some(&b'\n') {
+            // an append torn by a crash, its operation never got released to party1. appending after it would
+            // join the next entry to the torn line
+            warn!("truncate a torn audit entry of {} bytes at the end of {}", read, path);
+            file.set_len(complete_len).map_err(|e| e.to_string())?;
+            file.sync_data().map_err(|e| e.to_string())?;
+            break;
+        }
+        complete_len += read as u64;
+        let entry_bytes = &line[..read - 1];
+        if entry_bytes.is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_slice::<AuditEntry>(entry_bytes).map_err(|e| e.to_string())?;
+        head = AuditHead { next_index: entry.index + 1, last_hash: entry.hash };
+    }
+    Ok(head)
+}
+
+async fn append_audit(mut entry: AuditEntry) -> Result<(), String> {
+    let path = AppConfig::get_app_config().audit_log_path.clone();
+    entry.timestamp = get_tsp();
+
+    // the lock serializes appends, so the chain never forks
+    let mut option_head = AUDIT_HEAD.lock().await;
+    if option_head.is_none() {
+        let load_path = path.clone();
+        let head = spawn_blocking(move || load_head(&load_path)).await.map_err(|e| e.to_string())??;
+        *option_head = Some(head);
+    }
+    let head = option_head.as_mut().unwrap();
+    let sealed = seal(entry, head);
+
+    let mut line = serde_json::to_vec(&sealed).unwrap();
+    line.push(b'\n');
+    // write & fsync off the runtime threads
+    spawn_blocking(move || {
+        let _timer = storage_timer("audit_append");
+        let mut file = OpenOptions::new().create(true).append(true).open(&path).map_err(|e| e.to_string())?;
+        file.write_all(&line).map_err(|e| e.to_string())?;
+        file.sync_data().map_err(|e| e.to_string())
+    }).await.map_err(|e| e.to_string())??;
+
+    head.next_index = sealed.index + 1;
+    head.last_hash = sealed.hash;
+    Ok(())
+}
+
+// the result of an operation is released to party1 only if it is audited
+pub async fn audit_success(mut entry: AuditEntry) -> Result<(), String> {
+    entry.success = true;
+    let scope = entry.scope;
+    let action = entry.action.clone();
+    let append_result = append_audit(entry).await;
+    if append_result.is_err() {
+        error!("fail to append audit entry: {}", append_result.as_ref().unwrap_err());
+        // party1 is failed with Internal, the operation counts as failed
+        record_operation_failure(scope, &action, MpcErrorKind::Internal);
+    } else {
+        record_operation_success(scope, &action);
+    }
+    append_result
+}
+
+// kind is the MpcErrorKind party1 is failed with
+pub async fn audit_failure(mut entry: AuditEntry, kind: MpcErrorKind, error_msg: &str, two_party_error: Option<TwoPartyError>) {
+    entry.success = false;
+    entry.error_msg = error_msg.to_string();
+    entry.two_party_error = two_party_error;
+    record_operation_failure(entry.scope, &entry.action, kind);
+    if let Err(e) = append_audit(entry).await {
+        error!("fail to append audit entry: {}", e);
+    }
+}
+
+// the audit action of an mpc22 command, the identity commands are not audited
+pub fn command_action(command: u8) -> Option<&'static str> {
+    match command {
+        MPC_KEYGEN => Some(AUDIT_ACTION_KEYGEN),
+        MPC_SIGN => Some(AUDIT_ACTION_SIGN),
+        MPC_ROTATE => Some(AUDIT_ACTION_ROTATE),
+        MPC_ROTATE_COMMIT => Some(AUDIT_ACTION_ROTATE_COMMIT),
+        MPC_EXPORT => Some(AUDIT_ACTION_EXPORT),
+        _ => None,
+    }
+}
+
+// check the whole chain, return (entry count, head hash)
+pub fn verify_audit_log(path: &str) -> Result<(u64, String), String> {
+    let mut file = File::open(path).map_err(|e| format!("fail to open {}: {}", path, e))?;
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    if len > 0 {
+        let mut last_byte = [0u8];
+        file.seek(SeekFrom::End(-1)).map_err(|e| e.to_string())?;
+        file.read_exact(&mut last_byte).map_err(|e| e.to_string())?;
+        if last_byte[0] != b'\n' {
+            return Err("the last entry is torn by an interrupted append, mpc-server truncates it before its next append".to_string());
+        }
+        file.rewind().map_err(|e| e.to_string())?;
+    }
+    verify_lines(BufReader::new(file).lines().map(|line| line.map_err(|e| e.to_string())))
+}
+
+fn verify_lines(lines: impl Iterator<Item=Result<String, String>>) -> Result<(u64, String), String> {
+    let mut head = AuditHead::default();
+    for (line_no, line) in lines.enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str::<AuditEntry>(&line)
+            .map_err(|e| format!("line {}: fail to parse audit entry: {}", line_no + 1, e))?;
+        if entry.index != head.next_index {
+            return Err(format!("line {}: expect index {}, got {}", line_no + 1, head.next_index, entry.index));
+        }
+        if entry.prev_hash != head.last_hash {
+            return Err(format!("line {}: prev_hash does not match entry {}", line_no + 1, head.next_index as i64 - 1));
+        }
+        if entry.compute_hash() != entry.hash {
+            return Err(format!("line {}: entry {} has been modified", line_no + 1, entry.index));
+        }
+        head = AuditHead { next_index: entry.index + 1, last_hash: entry.hash };
+    }
+    Ok((head.next_index, head.last_hash))
+}
+
+#[cfg(test)]
+mod test {
+    use common::get_uuid;
+    use crate::audit::{AUDIT_ACTION_SIGN, AuditEntry, AuditHead, load_head, seal, verify_audit_log, verify_lines};
+
+    fn chain(count: usize) -> Vec<String> {
+        let mut head = AuditHead::default();
+        let mut lines = vec![];
+        for i in 0..count {
+            let mut entry = AuditEntry::new(AUDIT_ACTION_SIGN, 1, "127.0.0.1:9000", "wangcy", "share");
+            entry.digest = format!("0{}", i);
+            let sealed = seal(entry, &head);
+            head = AuditHead { next_index: sealed.index + 1, last_hash: sealed.hash.clone() };
+            lines.push(serde_json::to_string(&sealed).unwrap());
+        }
+        lines
+    }
+
+    fn verify(lines: &[String]) -> Result<(u64, String), String> {
+        verify_lines(lines.iter().map(|l| Ok(l.clone())))
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let lines = chain(3);
+        let (count, _head_hash) = verify(&lines).unwrap();
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_detect_tamper() {
+        let lines = chain(3);
+
+        let mut edited = lines.clone();
+        edited[1] = edited[1].replace("\"digest\":\"01\"", "\"digest\":\"ff\"");
+        assert!(verify(&edited).is_err());
+
+        let mut deleted = lines.clone();
+        deleted.remove(1);
+        assert!(verify(&deleted).is_err());
+
+        let mut reordered = lines;
+        reordered.swap(0, 1);
+        assert!(verify(&reordered).is_err());
+    }
+
+    #[test]
+    fn test_truncate_torn_entry() {
+        let lines = chain(3);
+        let path = format!("audit_{}.log", get_uuid());
+        let torn = &lines[2][..lines[2].len() / 2];
+        std::fs::write(&path, format!("{}\n{}\n{}", lines[0], lines[1], torn)).unwrap();
+        assert!(verify_audit_log(&path).is_err());
+
+        let head = load_head(&path).unwrap();
+        assert_eq!(head.next_index, 2);
+        let (count, head_hash) = verify_audit_log(&path).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(head_hash, head.last_hash);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+use std::str::FromStr;
+use jsonwebtoken::{Algorithm, decode, decode_header, DecodingKey, Validation};
+use jsonwebtoken::jwk::JwkSet;
+use serde::Deserialize;
+use crate::auth::{AuthIdentity, Authenticator};
+use crate::config::AuthConfig;
+
+#[derive(Deserialize)]
+struct Claims {
+    sub: String,
+    exp: u64,
+}
+
+// verify JWT/OIDC id tokens against a locally configured JWKS
+pub struct JwtAuthenticator {
+    jwks: JwkSet,
+    issuer: String,
+    audience: String,
+    allowed_algs: Vec<Algorithm>,
+}
+
+impl JwtAuthenticator {
+    pub fn from_config(auth_config: &AuthConfig) -> Result<Self, String> {
+        let jwks_bytes_result = std::fs::read(&auth_config.jwks_path);
+        if jwks_bytes_result.is_err() {
+            return Err(format!("fail to read jwks {}: {}", auth_config.jwks_path, jwks_bytes_result.unwrap_err()));
+        }
+        let jwks_result = serde_json::from_slice::<JwkSet>(&jwks_bytes_result.unwrap());
+        if jwks_result.is_err() {
+            return Err(format!("fail to parse jwks: {}", jwks_result.err().unwrap()));
+        }
+        let mut allowed_algs = vec![];
+        for alg in &auth_config.allowed_algs {
+            let alg_result = Algorithm::from_str(alg);
+            if alg_result.is_err() {
+                return Err(format!("unknown alg {} in allowed_algs", alg));
+            }
+            allowed_algs.push(alg_result.unwrap());
+        }
+
+        Ok(JwtAuthenticator {
+            jwks: jwks_result.unwrap(),
+            issuer: auth_config.issuer.clone(),
+            audience: auth_config.audience.clone(),
+            allowed_algs,
+        })
+    }
+}
+
+impl Authenticator for JwtAuthenticator {
+    fn authenticate(&self, token: &str) -> Result<AuthIdentity, String> {
+        let header_result = decode_header(token);
+        if header_result.is_err() {
+            return Err(format!("invalid token header: {}", header_result.err().unwrap()));
+        }
+        let header = header_result.unwrap();
+
+        let kid = header.kid.ok_or("token without kid".to_string())?;
+        let jwk = self.jwks.find(&kid).ok_or(format!("unknown kid={}", kid))?;
+        let key_result = DecodingKey::from_jwk(jwk);
+        if key_result.is_err() {
+            return Err(format!("unsupported jwk: {}", key_result.err().unwrap()));
+        }
+
+        // the alg is pinned by the jwk or the config, never picked by the token
+        let option_alg = jwk.common.algorithm.or_else(|| self.allowed_algs.contains(&header.alg).then_some(header.alg));
+        if option_alg.is_none() {
+            return Err(format!("jwk kid={} declares no alg and alg {:?} is not allowed", kid, header.alg));
+        }
+        let alg = option_alg.unwrap();
+        if header.alg != alg {
+            return Err(format!("token alg {:?} does not match alg {:?} of kid={}", header.alg, alg, kid));
+        }
+        if !self.allowed_algs.is_empty() && !self.allowed_algs.contains(&alg) {
+            return Err(format!("alg {:?} is not allowed", alg));
+        }
+
+        let mut validation = Validation::new(alg);
+        if !self.issuer.is_empty() {
+            validation.set_issuer(&[&self.issuer]);
+        }
+        if !self.audience.is_empty() {
+            validation.set_audience(&[&self.audience]);
+        }
+
+        let claims_result = decode::<Claims>(token, &key_result.unwrap(), &validation);
+        if claims_result.is_err() {
+            return Err(format!("invalid token: {}", claims_result.err().unwrap()));
+        }
+        let claims = claims_result.unwrap().claims;
+        if claims.sub.is_empty() {
+            return Err("token without sub".to_string());
+        }
+
+        Ok(AuthIdentity {
+            subject: claims.sub,
+            expires_at: claims.exp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use jsonwebtoken::{Algorithm, encode, EncodingKey, Header};
+    use jsonwebtoken::jwk::JwkSet;
+    use serde::Serialize;
+    use crate::auth::Authenticator;
+    use crate::auth::jwt::JwtAuthenticator;
+
+    #[derive(Serialize)]
+    struct TestClaims {
+        sub: String,
+        exp: u64,
+    }
+
+    const SECRET: &[u8] = b"jwt-test-secret";
+
+    fn authenticator(jwk_alg: Option<&str>, allowed_algs: Vec<Algorithm>) -> JwtAuthenticator {
+        // base64url of SECRET
+        let mut jwk = serde_json::json!({"kty": "oct", "kid": "k1", "k": "and0LXRlc3Qtc2VjcmV0"});
+        if let Some(alg) = jwk_alg {
+            jwk["alg"] = serde_json::Value::from(alg);
+        }
+        let jwks = serde_json::from_value::<JwkSet>(serde_json::json!({"keys": [jwk]})).unwrap();
+        JwtAuthenticator { jwks, issuer: "".to_string(), audience: "".to_string(), allowed_algs }
+    }
+
+    fn token(alg: Algorithm) -> String {
+        let mut header = Header::new(alg);
+        header.kid = Some("k1".to_string());
+        let claims = TestClaims { sub: "wangcy".to_string(), exp: 4102444800 };
+        encode(&header, &claims, &EncodingKey::from_secret(SECRET)).unwrap()
+    }
+
+    #[test]
+    fn test_pinned_alg() {
+        let authenticator = authenticator(Some("HS256"), vec![]);
+        assert_eq!(authenticator.authenticate(&token(Algorithm::HS256)).unwrap().subject, "wangcy");
+        // the same key under another alg of the header
+        assert!(authenticator.authenticate(&token(Algorithm::HS384)).is_err());
+    }
+
+    #[test]
+    fn test_allowed_algs() {
+        // a jwk without alg needs allowed_algs
+        assert!(authenticator(None, vec![]).authenticate(&token(Algorithm::HS256)).is_err());
+        let authenticator = authenticator(None, vec![Algorithm::HS256]);
+        assert!(authenticator.authenticate(&token(Algorithm::HS256)).is_ok());
+        assert!(authenticator.authenticate(&token(Algorithm::HS512)).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+pub mod jwt;
+
+use std::sync::OnceLock;
+use common::get_tsp;
+use crate::auth::jwt::JwtAuthenticator;
+use crate::config::AppConfig;
+
+pub const AUTH_MODE_NONE: &str = "none";
+pub const AUTH_MODE_JWT: &str = "jwt";
+
+// the identity proved by the client at websocket upgrade
+#[derive(Clone, Debug)]
+pub struct AuthIdentity {
+    pub subject: String,
+    // seconds since unix epoch, 0 means never expire
+    pub expires_at: u64,
+}
+
+impl AuthIdentity {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at != 0 && (get_tsp() / 1000) as u64 >= self.expires_at
+    }
+}
+
+// verify a bearer token and extract the authenticated subject
+pub trait Authenticator: Send + Sync {
+    fn authenticate(&self, token: &str) -> Result<AuthIdentity, String>;
+}
+
+static AUTHENTICATOR_LOCK: OnceLock<Option<Box<dyn Authenticator>>> = OnceLock::new();
+
+// None if authentication is disabled by config
+pub fn get_authenticator() -> Option<&'static dyn Authenticator> {
+    let authenticator = AUTHENTICATOR_LOCK.get_or_init(|| {
+        let auth_config = &AppConfig::get_app_config().auth;
+        match auth_config.mode.as_str() {
+            AUTH_MODE_NONE => None,
+            AUTH_MODE_JWT => {
+                let jwt_authenticator = JwtAuthenticator::from_config(auth_config).expect("fail to init jwt authenticator");
+                Some(Box::new(jwt_authenticator) as Box<dyn Authenticator>)
+            }
+            other => panic!("auth mode {other} not supported"),
+        }
+    });
+    authenticator.as_deref()
+}
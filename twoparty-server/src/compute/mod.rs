@@ -0,0 +1,83 @@
+//! A bounded pool for CPU-heavy protocol steps, e.g. paillier proofs on 2048-bit numbers,
+//! so they never block the tokio workers which serve heartbeats of other connections.
+//!
+//! at most `max_threads` steps run on blocking threads, at most `max_queue` steps wait for them,
+//! further steps are rejected with MpcErrorKind::Unavailable.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::LazyLock;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use common::errors::MpcErrorKind;
+use crate::config::AppConfig;
+use crate::metrics::{set_compute_queue_depth, set_compute_running};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ComputePoolConfig {
+    pub max_threads: usize,
+    pub max_queue: usize,
+}
+
+impl Default for ComputePoolConfig {
+    fn default() -> Self {
+        ComputePoolConfig { max_threads: 8, max_queue: 64 }
+    }
+}
+
+#[derive(Debug)]
+pub struct ComputeError {
+    pub kind: MpcErrorKind,
+    pub msg: String,
+}
+
+static COMPUTE_PERMITS: LazyLock<Semaphore> = LazyLock::new(|| {
+    Semaphore::new(AppConfig::get_app_config().compute_pool.max_threads)
+});
+static QUEUED: AtomicUsize = AtomicUsize::new(0);
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+pub async fn run_blocking<T, F>(f: F) -> Result<T, ComputeError>
+    where T: Send + 'static,
+          F: FnOnce() -> T + Send + 'static {
+    let pool_config = &AppConfig::get_app_config().compute_pool;
+
+    // the semaphore is never closed
+    let permit = match COMPUTE_PERMITS.try_acquire() {
+        Ok(permit) => permit,
+        Err(_) => {
+            // take a queue slot only while there is one, concurrent steps can not overshoot max_queue
+            let queue_result = QUEUED.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                if queued < pool_config.max_queue { Some(queued + 1) } else { None }
+            });
+            if let Err(queued) = queue_result {
+                return Err(ComputeError {
+                    kind: MpcErrorKind::Unavailable,
+                    msg: format!("compute pool saturated, queue depth={}", queued),
+                });
+            }
+            let queue_slot = QueueSlot;
+            set_compute_queue_depth(queue_result.unwrap() + 1);
+            let permit = COMPUTE_PERMITS.acquire().await.unwrap();
+            drop(queue_slot);
+            permit
+        }
+    };
+    set_compute_running(RUNNING.fetch_add(1, Ordering::SeqCst) + 1);
+
+    let join_result = tokio::task::spawn_blocking(f).await;
+
+    set_compute_running(RUNNING.fetch_sub(1, Ordering::SeqCst) - 1);
+    drop(permit);
+    join_result.map_err(|e| ComputeError {
+        kind: MpcErrorKind::Internal,
+        msg: format!("compute task failed: {}", e),
+    })
+}
+
+// leaves the queue once the permit is acquired, or when the waiting step is dropped
+struct QueueSlot;
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        set_compute_queue_depth(QUEUED.fetch_sub(1, Ordering::SeqCst) - 1);
+    }
+}
@@ -2,6 +2,9 @@ use std::sync::OnceLock;
 use include_dir::{Dir, include_dir};
 use serde::{Deserialize, Serialize};
 use tracing::{Level};
+use crate::compute::ComputePoolConfig;
+use crate::policy::SignPolicyConfig;
+use crate::ratelimit::RateLimitConfig;
 
 use tracing_appender::non_blocking::{WorkerGuard};
 
@@ -18,6 +21,18 @@ pub const RESOURCES_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/src/resourc
 pub struct CliArgs {
     #[clap(long, default_value = "test")]
     pub env: String,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum Command {
+    // check the hash chain of the audit log, then exit
+    VerifyAudit {
+        // defaults to audit_log_path of the env config
+        #[clap(long)]
+        path: Option<String>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -26,6 +41,63 @@ pub struct AppConfig {
     pub env: String,
     pub ws_server_idle: u8,
     pub ws_client_interval: u8,
+    // seconds to wait for party1 committing a rotated share before rolling it back
+    pub rotate_commit_timeout: u16,
+    // seconds to keep the mpc session of a dropped connection, party1 can resume it on a new connection
+    pub session_grace_secs: u16,
+    pub auth: AuthConfig,
+    // append-only, hash-chained audit log of keygen, sign, rotate & export
+    pub audit_log_path: String,
+    #[serde(default)]
+    pub sign_policy: SignPolicyConfig,
+    #[serde(default)]
+    pub compute_pool: ComputePoolConfig,
+    #[serde(default)]
+    pub ws_limits: WsLimitsConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    // bearer token of /admin routes & /metrics, empty disables them. the ADMIN_TOKEN env overrides it
+    #[serde(default)]
+    pub admin_token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WsLimitsConfig {
+    // outbound msgs queued for a connection, a peer which does not drain them is closed
+    pub outbound_queue: usize,
+    // bytes of an inbound msg, a larger one closes the connection
+    pub max_message_size: usize,
+    // max difference between MsgWrapper.timestamp and the server time
+    #[serde(default = "default_timestamp_skew_secs")]
+    pub timestamp_skew_secs: u64,
+}
+
+fn default_timestamp_skew_secs() -> u64 {
+    30
+}
+
+impl Default for WsLimitsConfig {
+    fn default() -> Self {
+        WsLimitsConfig { outbound_queue: 32, max_message_size: 4 << 20, timestamp_skew_secs: default_timestamp_skew_secs() }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AuthConfig {
+    // none | jwt
+    pub mode: String,
+    // local JWKS file to verify jwt
+    #[serde(default)]
+    pub jwks_path: String,
+    // empty means not checked
+    #[serde(default)]
+    pub issuer: String,
+    // empty means not checked
+    #[serde(default)]
+    pub audience: String,
+    // e.g. RS256, the algs of tokens signed by a jwk without alg. empty allows only the alg declared by the jwk
+    #[serde(default)]
+    pub allowed_algs: Vec<String>,
 }
 
 // rust static vs const
@@ -40,7 +112,13 @@ impl AppConfig {
             let config_file_path = format!("config-{env}.yml");
             if let Some(env_file) = RESOURCES_DIR.get_file(config_file_path) {
                 if let Some(env_yaml) = env_file.contents_utf8() {
-                    serde_yaml::from_str::<AppConfig>(env_yaml)
+                    serde_yaml::from_str::<AppConfig>(env_yaml).map(|mut app_config| {
+                        // keep the prod token out of the yml embedded in the binary
+                        if let Ok(admin_token) = std::env::var("ADMIN_TOKEN") {
+                            app_config.admin_token = admin_token;
+                        }
+                        app_config
+                    })
                 } else {
                     panic!("env {env} config file is empty")
                 }
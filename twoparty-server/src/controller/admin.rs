@@ -0,0 +1,66 @@
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::Json;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use tracing::warn;
+use common::socketmsg::{NOTICE_POLICY_CHANGED, NOTICE_ROTATION_REQUIRED, NOTICE_SHARE_FROZEN};
+use crate::config::AppConfig;
+use crate::metrics::gather_metrics;
+use crate::websocket::publish_notice;
+
+#[derive(Deserialize)]
+pub struct PublishNoticeReq {
+    pub identity_id: String,
+    // NOTICE_ROTATION_REQUIRED | NOTICE_SHARE_FROZEN | NOTICE_POLICY_CHANGED
+    pub notice_type: u32,
+    #[serde(default)]
+    pub share_id: String,
+    pub message: String,
+}
+
+// push a notice to mpc-clients of the identity, responds with the notice_id
+pub async fn post_notice(headers: HeaderMap, Json(req): Json<PublishNoticeReq>) -> Response {
+    if !authorize_admin(&headers) {
+        warn!("reject admin request without a valid token");
+        return (StatusCode::UNAUTHORIZED, "invalid admin token").into_response();
+    }
+    if req.identity_id.is_empty() {
+        return (StatusCode::BAD_REQUEST, "identity_id is empty").into_response();
+    }
+    if ![NOTICE_ROTATION_REQUIRED, NOTICE_SHARE_FROZEN, NOTICE_POLICY_CHANGED].contains(&req.notice_type) {
+        return (StatusCode::BAD_REQUEST, "unknown notice_type").into_response();
+    }
+    let notice_id = publish_notice(&req.identity_id, req.notice_type, &req.share_id, &req.message).await;
+    notice_id.into_response()
+}
+
+// metrics tell the load & failures of identities, scraped with the admin_token as a bearer
+pub async fn metrics(headers: HeaderMap) -> Response {
+    if !authorize_admin(&headers) {
+        warn!("reject metrics request without a valid token");
+        return (StatusCode::UNAUTHORIZED, "invalid admin token").into_response();
+    }
+    ([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], gather_metrics()).into_response()
+}
+
+// admin routes are disabled while admin_token is empty
+fn authorize_admin(headers: &HeaderMap) -> bool {
+    let admin_token = &AppConfig::get_app_config().admin_token;
+    if admin_token.is_empty() {
+        return false;
+    }
+    let option_bearer = headers.get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match option_bearer {
+        Some(bearer) => constant_time_eq(bearer.as_bytes(), admin_token.as_bytes()),
+        None => false,
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -1,15 +1,19 @@
 mod ws_config;
+mod admin;
 
 use std::net::{SocketAddr};
 use axum::{Router};
-use axum::routing::{get};
+use axum::routing::{get, post};
 use tracing::info;
 use crate::config::AppConfig;
-use crate::websocket::ws_handler;
+use crate::metrics::register_metrics;
+use crate::websocket::{sweep_rotations, ws_handler};
 
 pub async fn launch_axum() {
     let app_config = AppConfig::get_app_config();
 
+    register_metrics();
+    sweep_rotations().await;
     let router = build_router();
     let addr = format!("0.0.0.0:{}", app_config.server_port);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
@@ -22,10 +26,13 @@ fn build_router() -> Router {
         .route("/health", get(health))
         .route("/ws", get(ws_handler))
         .route("/ws-config", get(ws_config::ws_config))
+        .route("/capabilities", get(ws_config::capabilities))
+        .route("/metrics", get(admin::metrics))
+        .route("/admin/notices", post(admin::post_notice))
 }
 
 async fn health() -> String {
     let app_config = AppConfig::get_app_config();
     let crate_name = env!("CARGO_PKG_NAME");
     format!("{crate_name} runs at port: {}", app_config.server_port)
-}
\ No newline at end of file
+}
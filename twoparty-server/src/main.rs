@@ -3,7 +3,8 @@
 
 use clap::Parser;
 
-use crate::config::{AppConfig, CliArgs, log_config};
+use crate::audit::verify_audit_log;
+use crate::config::{AppConfig, CliArgs, Command, log_config};
 use crate::controller::launch_axum;
 
 
@@ -11,6 +12,12 @@ pub mod config;
 pub mod websocket;
 pub mod controller;
 pub mod storage;
+pub mod auth;
+pub mod policy;
+pub mod audit;
+pub mod metrics;
+pub mod compute;
+pub mod ratelimit;
 
 // https://github.com/tokio-rs/tokio/discussions/3858
 // tokio: worker-threads= cpu_num,  blocking-threads: create-on-demand with upper limit=500
@@ -19,7 +26,20 @@ async fn main() {
     let cli_args = CliArgs::parse();
     std::env::set_var("ENV", cli_args.env);
 
-    let _app_config = AppConfig::get_app_config();
+    let app_config = AppConfig::get_app_config();
+    if let Some(Command::VerifyAudit { path }) = cli_args.command {
+        let path = path.unwrap_or(app_config.audit_log_path.clone());
+        match verify_audit_log(&path) {
+            Ok((count, head_hash)) => {
+                println!("audit log {} is intact, entries={}, head_hash={}", path, count, head_hash);
+                return;
+            }
+            Err(e) => {
+                eprintln!("audit log {} is broken: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
     let _guard = log_config();
 
     // launch http & websocket server
@@ -0,0 +1,173 @@
+//! Prometheus metrics, exported by `/metrics`.
+use std::sync::LazyLock;
+use std::time::Instant;
+use prometheus::{Encoder, HistogramTimer, HistogramVec, IntCounterVec, IntGauge, register_histogram_vec, register_int_counter_vec, register_int_gauge, TextEncoder};
+use common::errors::MpcErrorKind;
+use common::socketmsg::types::{MPC_EXPORT, MPC_IDENTITY_BIND, MPC_IDENTITY_RECOVER, MPC_KEYGEN, MPC_ROTATE, MPC_ROTATE_COMMIT, MPC_SCOPE_ED25519EDDSA, MPC_SCOPE_SECP256K1ECDSA, MPC_SHARE_STATUS, MPC_SIGN};
+
+static WS_ACTIVE_CONNECTIONS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("ws_active_connections", "active websocket connections").unwrap()
+});
+
+static MPC_STEP_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!("mpc_step_duration_seconds", "latency of a mpc22 step", &["scope", "command", "step"]).unwrap()
+});
+
+static MPC_OPERATIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("mpc_operations_total", "finished keygen, sign, rotate & export", &["scope", "action", "result", "category"]).unwrap()
+});
+
+static STEP2_COMPUTE_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!("mpc_step2_compute_seconds", "time of party2 computing step2, which verifies party1's proofs except for ed25519 sign", &["scope", "action"]).unwrap()
+});
+
+static PROOF_VERIFICATION_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!("mpc_proof_verification_seconds", "time of the party2 steps which verify party1's proofs, by whether they verified", &["scope", "action", "result"]).unwrap()
+});
+
+static COMPUTE_QUEUE_DEPTH: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("compute_queue_depth", "protocol steps waiting for the compute pool").unwrap()
+});
+
+static COMPUTE_RUNNING: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("compute_running", "protocol steps running on the compute pool").unwrap()
+});
+
+static RATE_LIMITED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("rate_limited_total", "attempts refused by rate limits", &["limit"]).unwrap()
+});
+
+static STORAGE_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!("storage_duration_seconds", "latency of storage operations", &["operation"]).unwrap()
+});
+
+// register all metrics, so they are exported before the first observation
+pub fn register_metrics() {
+    LazyLock::force(&WS_ACTIVE_CONNECTIONS);
+    LazyLock::force(&MPC_STEP_DURATION);
+    LazyLock::force(&MPC_OPERATIONS);
+    LazyLock::force(&STEP2_COMPUTE_DURATION);
+    LazyLock::force(&PROOF_VERIFICATION_DURATION);
+    LazyLock::force(&STORAGE_DURATION);
+    LazyLock::force(&COMPUTE_QUEUE_DEPTH);
+    LazyLock::force(&COMPUTE_RUNNING);
+    LazyLock::force(&RATE_LIMITED);
+}
+
+pub fn gather_metrics() -> Vec<u8> {
+    let mut buffer = vec![];
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer).unwrap();
+    buffer
+}
+
+// counts a websocket connection until dropped
+pub struct ConnectionGuard;
+
+impl ConnectionGuard {
+    pub fn track() -> Self {
+        WS_ACTIVE_CONNECTIONS.inc();
+        ConnectionGuard
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        WS_ACTIVE_CONNECTIONS.dec();
+    }
+}
+
+// observed when dropped
+pub fn step_timer(scope: u8, command: u8, step: u8) -> HistogramTimer {
+    MPC_STEP_DURATION.with_label_values(&[scope_name(scope), command_name(command), &step.to_string()]).start_timer()
+}
+
+pub fn step2_compute_timer(scope: u8, action: &str) -> HistogramTimer {
+    STEP2_COMPUTE_DURATION.with_label_values(&[scope_name(scope), action]).start_timer()
+}
+
+// started right before a party2_step* call which verifies party1's proofs
+pub fn observe_proof_verification(scope: u8, action: &str, started: Instant, verified: bool) {
+    let result = if verified { "verified" } else { "rejected" };
+    PROOF_VERIFICATION_DURATION.with_label_values(&[scope_name(scope), action, result]).observe(started.elapsed().as_secs_f64());
+}
+
+pub fn storage_timer(operation: &str) -> HistogramTimer {
+    STORAGE_DURATION.with_label_values(&[operation]).start_timer()
+}
+
+pub fn set_compute_queue_depth(depth: usize) {
+    COMPUTE_QUEUE_DEPTH.set(depth as i64);
+}
+
+pub fn set_compute_running(running: usize) {
+    COMPUTE_RUNNING.set(running as i64);
+}
+
+pub fn record_rate_limited(limit: &str) {
+    RATE_LIMITED.with_label_values(&[limit]).inc();
+}
+
+pub fn record_operation_success(scope: u8, action: &str) {
+    MPC_OPERATIONS.with_label_values(&[scope_name(scope), action, "success", ""]).inc();
+}
+
+pub fn record_operation_failure(scope: u8, action: &str, kind: MpcErrorKind) {
+    MPC_OPERATIONS.with_label_values(&[scope_name(scope), action, "failure", failure_category(kind)]).inc();
+}
+
+// one category per MpcErrorKind, reasons themselves are too many to be labels
+fn failure_category(kind: MpcErrorKind) -> &'static str {
+    match kind {
+        MpcErrorKind::Internal => "internal",
+        MpcErrorKind::MalformedMessage => "malformed_message",
+        MpcErrorKind::ProofVerificationFailed => "proof_verification",
+        MpcErrorKind::TransportTimeout => "transport_timeout",
+        MpcErrorKind::Disconnected => "disconnected",
+        MpcErrorKind::ShareNotFound => "share_not_found",
+        MpcErrorKind::PolicyDenied => "policy",
+        MpcErrorKind::VersionMismatch => "version_mismatch",
+        MpcErrorKind::Unauthorized => "unauthorized",
+        MpcErrorKind::Forbidden => "forbidden",
+        MpcErrorKind::RateLimited => "rate_limited",
+        MpcErrorKind::InvalidRequest => "invalid_request",
+        MpcErrorKind::NotFound => "not_found",
+        MpcErrorKind::Unsupported => "unsupported",
+        MpcErrorKind::Unavailable => "unavailable",
+    }
+}
+
+fn scope_name(scope: u8) -> &'static str {
+    match scope {
+        MPC_SCOPE_SECP256K1ECDSA => "secp256k1",
+        MPC_SCOPE_ED25519EDDSA => "ed25519",
+        _ => "unknown",
+    }
+}
+
+fn command_name(command: u8) -> &'static str {
+    match command {
+        MPC_KEYGEN => "keygen",
+        MPC_SIGN => "sign",
+        MPC_ROTATE => "rotate",
+        MPC_EXPORT => "export",
+        MPC_ROTATE_COMMIT => "rotate_commit",
+        MPC_SHARE_STATUS => "share_status",
+        MPC_IDENTITY_BIND => "identity_bind",
+        MPC_IDENTITY_RECOVER => "identity_recover",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::errors::MpcErrorKind;
+    use crate::metrics::failure_category;
+
+    #[test]
+    fn test_failure_category() {
+        assert_eq!(failure_category(MpcErrorKind::ProofVerificationFailed), "proof_verification");
+        assert_eq!(failure_category(MpcErrorKind::PolicyDenied), "policy");
+        assert_eq!(failure_category(MpcErrorKind::Unavailable), "unavailable");
+        assert_eq!(failure_category(MpcErrorKind::Internal), "internal");
+    }
+}
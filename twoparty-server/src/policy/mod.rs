@@ -0,0 +1,385 @@
+//! Signing policy, evaluated before party2 co-signs a message_digest.
+//!
+//! the default rule applies to every sign, identity & share rules apply additionally,
+//! a sign is allowed only if all the applicable rules pass.
+//! the default & share rules count the signs of a share, identity rules the signs of all the shares of the identity.
+//! the counts & export times are kept in memory of the process: a restart resets the daily limits & export cooldowns,
+//! and every mpc-server process behind a load balancer counts on its own.
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use serde::{Deserialize, Serialize};
+use common::get_tsp;
+use common::payload::DecodedPayload;
+use crate::config::AppConfig;
+
+const MILLIS_PER_HOUR: u128 = 3_600_000;
+const MILLIS_PER_DAY: u128 = 24 * MILLIS_PER_HOUR;
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SignPolicyConfig {
+    #[serde(default)]
+    pub default: PolicyRule,
+    // identity_id -> rule
+    #[serde(default)]
+    pub identities: HashMap<String, PolicyRule>,
+    // share_id -> rule
+    #[serde(default)]
+    pub shares: HashMap<String, PolicyRule>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct PolicyRule {
+    // max signatures per utc day, 0 means unlimited
+    #[serde(default)]
+    pub daily_sign_limit: u32,
+    // utc hours in which signing is allowed, empty means any time
+    #[serde(default)]
+    pub allowed_windows: Vec<TimeWindow>,
+    // hex encoded digests, empty allow list means any digest
+    #[serde(default)]
+    pub allowed_digests: Vec<String>,
+    #[serde(default)]
+    pub denied_digests: Vec<String>,
+    // refuse digest-only signs, party1 must send the unsigned payload
+    #[serde(default)]
+    pub require_payload: bool,
+    // recipients decoded from the signed payload, empty allow list means any destination
+    #[serde(default)]
+    pub allowed_destinations: Vec<String>,
+    #[serde(default)]
+    pub denied_destinations: Vec<String>,
+    // chain ids decoded from the signed payload, empty means any chain
+    #[serde(default)]
+    pub allowed_chain_ids: Vec<u64>,
+    #[serde(default)]
+    pub cooldown_after_rotate_secs: u64,
+    #[serde(default)]
+    pub cooldown_after_export_secs: u64,
+}
+
+// [start_hour, end_hour) in utc, wraps around midnight if start_hour > end_hour
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimeWindow {
+    pub start_hour: u8,
+    pub end_hour: u8,
+}
+
+impl TimeWindow {
+    fn contains(&self, hour: u8) -> bool {
+        if self.start_hour <= self.end_hour {
+            hour >= self.start_hour && hour < self.end_hour
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+}
+
+pub struct SignContext<'a> {
+    pub identity_id: &'a str,
+    pub share_id: &'a str,
+    pub message_digest: &'a [u8],
+    // decoded from the signed payload, None if party1 only sent the digest
+    pub payload: Option<&'a DecodedPayload>,
+    pub share_epoch: u32,
+    // millis, when the current epoch of the share started
+    pub share_rotated_at: u128,
+}
+
+#[derive(Default, Clone)]
+pub struct SignUsage {
+    // utc day of sign_count
+    pub day: u128,
+    pub sign_count: u32,
+    // allowed signs whose party2 step2 has not finished, they count against the daily limit meanwhile
+    pub signs_in_flight: u32,
+    // millis, 0 means never exported
+    pub last_export_at: u128,
+}
+
+impl SignUsage {
+    fn signs_today(&self, now: u128) -> u32 {
+        let sign_count = if self.day == now / MILLIS_PER_DAY { self.sign_count } else { 0 };
+        sign_count + self.signs_in_flight
+    }
+
+    fn finish_sign(&mut self, succeeded: bool, now: u128) {
+        self.signs_in_flight = self.signs_in_flight.saturating_sub(1);
+        if !succeeded {
+            return;
+        }
+        let today = now / MILLIS_PER_DAY;
+        if self.day != today {
+            self.day = today;
+            self.sign_count = 0;
+        }
+        self.sign_count += 1;
+    }
+}
+
+#[derive(Default)]
+struct SignUsages {
+    // share_id -> usage
+    shares: HashMap<String, SignUsage>,
+    // identity_id -> usage, so holding more shares or rotating does not reset an identity limit
+    identities: HashMap<String, SignUsage>,
+}
+
+impl SignUsages {
+    // check the applicable rules, an allowed sign is in flight until finish_sign
+    fn admit(&mut self, policy_config: &SignPolicyConfig, ctx: &SignContext, now: u128) -> Result<(), String> {
+        let share_usage = self.shares.entry(ctx.share_id.to_string()).or_default();
+        let identity_usage = self.identities.entry(ctx.identity_id.to_string()).or_default();
+        policy_config.default.check(ctx, share_usage, now)?;
+        if let Some(identity_rule) = policy_config.identities.get(ctx.identity_id) {
+            identity_rule.check(ctx, identity_usage, now)?;
+        }
+        if let Some(share_rule) = policy_config.shares.get(ctx.share_id) {
+            share_rule.check(ctx, share_usage, now)?;
+        }
+        share_usage.signs_in_flight += 1;
+        identity_usage.signs_in_flight += 1;
+        Ok(())
+    }
+
+    fn finish_sign(&mut self, identity_id: &str, share_id: &str, succeeded: bool, now: u128) {
+        if let Some(share_usage) = self.shares.get_mut(share_id) {
+            share_usage.finish_sign(succeeded, now);
+        }
+        if let Some(identity_usage) = self.identities.get_mut(identity_id) {
+            identity_usage.finish_sign(succeeded, now);
+        }
+    }
+}
+
+// per process, not persisted. a std mutex, SignPermit releases in drop
+static SIGN_USAGES: LazyLock<Mutex<SignUsages>> = LazyLock::new(|| {
+    Mutex::new(SignUsages::default())
+});
+
+// an allowed sign, counted into the daily limits by succeed, released uncounted if dropped before
+pub struct SignPermit {
+    identity_id: String,
+    share_id: String,
+    succeeded: bool,
+}
+
+impl SignPermit {
+    // the signature is audited & released to party1
+    pub fn succeed(mut self) {
+        self.succeeded = true;
+    }
+}
+
+impl Drop for SignPermit {
+    fn drop(&mut self) {
+        let mut usages = SIGN_USAGES.lock().unwrap();
+        usages.finish_sign(&self.identity_id, &self.share_id, self.succeeded, get_tsp());
+    }
+}
+
+impl PolicyRule {
+    pub fn check(&self, ctx: &SignContext, usage: &SignUsage, now: u128) -> Result<(), String> {
+        if self.daily_sign_limit > 0 && usage.signs_today(now) >= self.daily_sign_limit {
+            return Err(format!("daily sign limit {} reached", self.daily_sign_limit));
+        }
+
+        if !self.allowed_windows.is_empty() {
+            let hour = ((now % MILLIS_PER_DAY) / MILLIS_PER_HOUR) as u8;
+            if !self.allowed_windows.iter().any(|w| w.contains(hour)) {
+                return Err(format!("signing is not allowed at utc hour {}", hour));
+            }
+        }
+
+        let digest_hex = hex::encode(ctx.message_digest);
+        if contains_ignore_case(&self.denied_digests, &digest_hex) {
+            return Err("message_digest is denied".to_string());
+        }
+        if !self.allowed_digests.is_empty() && !contains_ignore_case(&self.allowed_digests, &digest_hex) {
+            return Err("message_digest is not allowed".to_string());
+        }
+
+        if self.require_payload && ctx.payload.is_none() {
+            return Err("payload is required".to_string());
+        }
+        let destinations: Vec<&str> = ctx.payload
+            .map(|p| p.outputs.iter().map(|o| o.recipient.as_str()).collect())
+            .unwrap_or_default();
+        for destination in &destinations {
+            if contains_ignore_case(&self.denied_destinations, destination) {
+                return Err(format!("destination {} is denied", destination));
+            }
+        }
+        if !self.allowed_destinations.is_empty() {
+            if destinations.is_empty() {
+                return Err("destination is required but unknown".to_string());
+            }
+            for destination in &destinations {
+                if !contains_ignore_case(&self.allowed_destinations, destination) {
+                    return Err(format!("destination {} is not allowed", destination));
+                }
+            }
+        }
+        if !self.allowed_chain_ids.is_empty() {
+            match ctx.payload.and_then(|p| p.chain_id) {
+                Some(chain_id) if self.allowed_chain_ids.contains(&chain_id) => {}
+                Some(chain_id) => return Err(format!("chain_id {} is not allowed", chain_id)),
+                None => return Err("chain_id is required but unknown".to_string()),
+            }
+        }
+
+        if self.cooldown_after_rotate_secs > 0 && ctx.share_epoch > 0 {
+            let cooldown_end = ctx.share_rotated_at + self.cooldown_after_rotate_secs as u128 * 1000;
+            if now < cooldown_end {
+                return Err(format!("share is cooling down after rotate, {}s left", (cooldown_end - now) / 1000 + 1));
+            }
+        }
+        if self.cooldown_after_export_secs > 0 && usage.last_export_at > 0 {
+            let cooldown_end = usage.last_export_at + self.cooldown_after_export_secs as u128 * 1000;
+            if now < cooldown_end {
+                return Err(format!("share is cooling down after export, {}s left", (cooldown_end - now) / 1000 + 1));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// evaluate all the applicable rules, the permit counts the sign into the daily limits once it succeeds
+pub fn evaluate_sign_policy(ctx: &SignContext<'_>) -> Result<SignPermit, String> {
+    let policy_config = &AppConfig::get_app_config().sign_policy;
+    // hold the lock, so concurrent signs of the same share or identity can not exceed the limits
+    SIGN_USAGES.lock().unwrap().admit(policy_config, ctx, get_tsp())?;
+    Ok(SignPermit {
+        identity_id: ctx.identity_id.to_string(),
+        share_id: ctx.share_id.to_string(),
+        succeeded: false,
+    })
+}
+
+pub fn record_export(identity_id: &str, share_id: &str) {
+    let now = get_tsp();
+    let mut usages = SIGN_USAGES.lock().unwrap();
+    usages.shares.entry(share_id.to_string()).or_default().last_export_at = now;
+    usages.identities.entry(identity_id.to_string()).or_default().last_export_at = now;
+}
+
+fn contains_ignore_case(list: &[String], value: &str) -> bool {
+    list.iter().any(|v| v.eq_ignore_ascii_case(value))
+}
+
+#[cfg(test)]
+mod test {
+    use common::payload::{DecodedPayload, PAYLOAD_FORMAT_ETH_TX, PayloadOutput};
+    use std::collections::HashMap;
+    use crate::policy::{MILLIS_PER_DAY, MILLIS_PER_HOUR, PolicyRule, SignContext, SignPolicyConfig, SignUsage, SignUsages, TimeWindow};
+
+    fn sign_context(message_digest: &[u8]) -> SignContext {
+        SignContext {
+            identity_id: "wangcy",
+            share_id: "share",
+            message_digest,
+            payload: None,
+            share_epoch: 0,
+            share_rotated_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_daily_limit() {
+        let rule = PolicyRule { daily_sign_limit: 2, ..Default::default() };
+        let now = 10 * MILLIS_PER_DAY;
+        let ctx = sign_context(&[1, 2]);
+        let mut usage = SignUsage { day: 10, sign_count: 1, ..Default::default() };
+        assert!(rule.check(&ctx, &usage, now).is_ok());
+        usage.sign_count = 2;
+        assert!(rule.check(&ctx, &usage, now).is_err());
+        // counts of yesterday do not apply
+        assert!(rule.check(&ctx, &usage, now + MILLIS_PER_DAY).is_ok());
+    }
+
+    #[test]
+    fn test_identity_limit_across_shares() {
+        let policy_config = SignPolicyConfig {
+            identities: HashMap::from([("wangcy".to_string(), PolicyRule { daily_sign_limit: 2, ..Default::default() })]),
+            ..Default::default()
+        };
+        let now = 10 * MILLIS_PER_DAY;
+        let mut usages = SignUsages::default();
+        let ctx = sign_context(&[1]);
+        let other_share = SignContext { share_id: "other", ..sign_context(&[1]) };
+
+        // a failed step2 is not counted
+        assert!(usages.admit(&policy_config, &ctx, now).is_ok());
+        usages.finish_sign("wangcy", "share", false, now);
+        assert!(usages.admit(&policy_config, &ctx, now).is_ok());
+        usages.finish_sign("wangcy", "share", true, now);
+        // an in flight sign is counted until it finishes
+        assert!(usages.admit(&policy_config, &other_share, now).is_ok());
+        assert!(usages.admit(&policy_config, &ctx, now).is_err());
+        usages.finish_sign("wangcy", "other", true, now);
+        assert!(usages.admit(&policy_config, &other_share, now).is_err());
+        assert!(usages.admit(&policy_config, &ctx, now + MILLIS_PER_DAY).is_ok());
+    }
+
+    #[test]
+    fn test_time_window_and_digest_lists() {
+        let rule = PolicyRule {
+            allowed_windows: vec![TimeWindow { start_hour: 22, end_hour: 2 }],
+            denied_digests: vec!["0102".to_string()],
+            ..Default::default()
+        };
+        let usage = SignUsage::default();
+        let ctx = sign_context(&[3, 4]);
+        assert!(rule.check(&ctx, &usage, 23 * MILLIS_PER_HOUR).is_ok());
+        assert!(rule.check(&ctx, &usage, MILLIS_PER_HOUR).is_ok());
+        assert!(rule.check(&ctx, &usage, 12 * MILLIS_PER_HOUR).is_err());
+        assert!(rule.check(&sign_context(&[1, 2]), &usage, 23 * MILLIS_PER_HOUR).is_err());
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let rule = PolicyRule { cooldown_after_export_secs: 60, cooldown_after_rotate_secs: 60, ..Default::default() };
+        let mut ctx = sign_context(&[1]);
+        let usage = SignUsage { last_export_at: 1_000_000, ..Default::default() };
+        assert!(rule.check(&ctx, &usage, 1_030_000).is_err());
+        assert!(rule.check(&ctx, &usage, 1_060_000).is_ok());
+
+        ctx.share_epoch = 1;
+        ctx.share_rotated_at = 1_050_000;
+        assert!(rule.check(&ctx, &usage, 1_060_000).is_err());
+    }
+
+    fn eth_payload(recipient: &str, chain_id: u64) -> DecodedPayload {
+        DecodedPayload {
+            format: PAYLOAD_FORMAT_ETH_TX,
+            digest: vec![1],
+            outputs: vec![PayloadOutput { recipient: recipient.to_string(), value: "1".to_string() }],
+            chain_id: Some(chain_id),
+        }
+    }
+
+    #[test]
+    fn test_payload_rules() {
+        let allowed = "0x3535353535353535353535353535353535353535";
+        let rule = PolicyRule {
+            require_payload: true,
+            allowed_destinations: vec![allowed.to_string()],
+            allowed_chain_ids: vec![1],
+            ..Default::default()
+        };
+        let usage = SignUsage::default();
+        assert!(rule.check(&sign_context(&[1]), &usage, 0).is_err());
+
+        let payload = eth_payload(allowed, 1);
+        let ctx = SignContext { payload: Some(&payload), ..sign_context(&[1]) };
+        assert!(rule.check(&ctx, &usage, 0).is_ok());
+
+        let payload = eth_payload(allowed, 56);
+        let ctx = SignContext { payload: Some(&payload), ..sign_context(&[1]) };
+        assert!(rule.check(&ctx, &usage, 0).is_err());
+
+        let payload = eth_payload("0x0000000000000000000000000000000000000000", 1);
+        let ctx = SignContext { payload: Some(&payload), ..sign_context(&[1]) };
+        assert!(rule.check(&ctx, &usage, 0).is_err());
+    }
+}
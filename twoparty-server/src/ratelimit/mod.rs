@@ -0,0 +1,152 @@
+//! Token-bucket rate limits of connection attempts per ip, mpc sessions per identity and signs per share.
+//!
+//! a bucket holds at most `burst` tokens and refills `per_minute` tokens a minute, every attempt takes a token,
+//! an attempt without a token is refused with the millis until the next token.
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::config::AppConfig;
+use crate::metrics::record_rate_limited;
+
+// buckets are pruned once there are more keys than this
+const MAX_BUCKETS: usize = 100_000;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RateLimitConfig {
+    pub connect_per_ip: BucketConfig,
+    pub session_per_identity: BucketConfig,
+    pub sign_per_share: BucketConfig,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            connect_per_ip: BucketConfig { burst: 20, per_minute: 60 },
+            session_per_identity: BucketConfig { burst: 20, per_minute: 30 },
+            sign_per_share: BucketConfig { burst: 10, per_minute: 10 },
+        }
+    }
+}
+
+// burst=0 or per_minute=0 means unlimited
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BucketConfig {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+impl BucketConfig {
+    fn unlimited(&self) -> bool {
+        self.burst == 0 || self.per_minute == 0
+    }
+
+    fn refill_per_ms(&self) -> f64 {
+        self.per_minute as f64 / 60_000.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitKind {
+    ConnectPerIp,
+    SessionPerIdentity,
+    SignPerShare,
+}
+
+impl LimitKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LimitKind::ConnectPerIp => "connect_per_ip",
+            LimitKind::SessionPerIdentity => "session_per_identity",
+            LimitKind::SignPerShare => "sign_per_share",
+        }
+    }
+
+    fn config(&self) -> &'static BucketConfig {
+        let rate_limit = &AppConfig::get_app_config().rate_limit;
+        match self {
+            LimitKind::ConnectPerIp => &rate_limit.connect_per_ip,
+            LimitKind::SessionPerIdentity => &rate_limit.session_per_identity,
+            LimitKind::SignPerShare => &rate_limit.sign_per_share,
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn full(config: &BucketConfig, now: Instant) -> Self {
+        TokenBucket { tokens: config.burst as f64, updated_at: now }
+    }
+
+    fn refill(&mut self, config: &BucketConfig, now: Instant) {
+        let elapsed_ms = now.saturating_duration_since(self.updated_at).as_millis() as f64;
+        self.tokens = (self.tokens + elapsed_ms * config.refill_per_ms()).min(config.burst as f64);
+        self.updated_at = now;
+    }
+
+    // take a token, or return the millis until a token is refilled
+    fn take(&mut self, config: &BucketConfig, now: Instant) -> Result<(), u64> {
+        self.refill(config, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(((1.0 - self.tokens) / config.refill_per_ms()).ceil() as u64)
+    }
+}
+
+static BUCKETS: LazyLock<Mutex<HashMap<(LimitKind, String), TokenBucket>>> = LazyLock::new(|| {
+    Mutex::new(HashMap::new())
+});
+
+// Err(retry_after_ms) if the key is out of tokens
+pub fn check_rate_limit(kind: LimitKind, key: &str) -> Result<(), u64> {
+    let config = kind.config();
+    if config.unlimited() {
+        return Ok(());
+    }
+    let now = Instant::now();
+    let mut buckets = BUCKETS.lock().unwrap();
+    if buckets.len() > MAX_BUCKETS {
+        // a refilled bucket is the same as a missing one
+        buckets.retain(|(kind, _), bucket| {
+            bucket.refill(kind.config(), now);
+            bucket.tokens < kind.config().burst as f64
+        });
+    }
+    let bucket = buckets.entry((kind, key.to_string())).or_insert_with(|| TokenBucket::full(config, now));
+    let take_result = bucket.take(config, now);
+    if take_result.is_err() {
+        record_rate_limited(kind.name());
+    }
+    take_result
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+    use crate::ratelimit::{BucketConfig, TokenBucket};
+
+    #[test]
+    fn test_token_bucket() {
+        let config = BucketConfig { burst: 2, per_minute: 60 };
+        let now = Instant::now();
+        let mut bucket = TokenBucket::full(&config, now);
+        assert!(bucket.take(&config, now).is_ok());
+        assert!(bucket.take(&config, now).is_ok());
+        // one token a second
+        assert_eq!(bucket.take(&config, now), Err(1000));
+        assert_eq!(bucket.take(&config, now + Duration::from_millis(400)), Err(600));
+        assert!(bucket.take(&config, now + Duration::from_millis(1000)).is_ok());
+        // never refilled beyond burst
+        let later = now + Duration::from_secs(3600);
+        assert!(bucket.take(&config, later).is_ok());
+        assert!(bucket.take(&config, later).is_ok());
+        assert!(bucket.take(&config, later).is_err());
+    }
+}
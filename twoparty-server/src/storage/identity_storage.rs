@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use curv::elliptic::curves::{Point, Secp256k1};
+use serde::{Deserialize, Deserializer, Serialize};
+use common::identity::EncryptedShare;
+use crate::metrics::storage_timer;
+
+// everything mpc-server keeps for an identity, the identity-secret itself is never stored
+#[derive(Serialize, Deserialize)]
+pub struct IdentityBinding {
+    pub identity_id: String,
+    // identity-secret * G
+    pub identity_public: Point<Secp256k1>,
+    pub encrypted_identity_secret: Vec<u8>,
+    // share_id -> Enc(share)
+    #[serde(deserialize_with = "keyed_shares")]
+    pub encrypted_shares: BTreeMap<String, EncryptedShare>,
+}
+
+// bindings saved before shares were keyed by share_id hold a list of one share
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredShares {
+    Keyed(BTreeMap<String, EncryptedShare>),
+    Listed(Vec<EncryptedShare>),
+}
+
+fn keyed_shares<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, EncryptedShare>, D::Error> {
+    Ok(match StoredShares::deserialize(deserializer)? {
+        StoredShares::Keyed(shares) => shares,
+        StoredShares::Listed(shares) => shares.into_iter()
+            .map(|share| (share.share_id.clone(), share))
+            .collect(),
+    })
+}
+
+pub struct FileIdentityStorage;
+
+impl FileIdentityStorage {
+    pub(crate) async fn save_binding(binding: &IdentityBinding) -> Result<(), String> {
+        let _timer = storage_timer("save_binding");
+        let binding_bytes_result = serde_json::to_vec(binding);
+        if binding_bytes_result.is_err() {
+            return Err(binding_bytes_result.unwrap_err().to_string());
+        }
+
+        let file_result = File::create(binding_path(&binding.identity_id));
+        if file_result.is_err() {
+            return Err(file_result.unwrap_err().to_string());
+        }
+        let mut file = file_result.unwrap();
+
+        let result = file.write_all(&binding_bytes_result.unwrap());
+        if result.is_err() {
+            return Err(result.unwrap_err().to_string());
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn load_binding(identity_id: &str) -> Result<IdentityBinding, String> {
+        let _timer = storage_timer("load_binding");
+        let file_result = File::open(binding_path(identity_id));
+        if file_result.is_err() {
+            return Err(file_result.unwrap_err().to_string());
+        }
+        let mut file = file_result.unwrap();
+
+        let mut binding_bytes = vec![];
+        let result = file.read_to_end(&mut binding_bytes);
+        if result.is_err() {
+            return Err(result.unwrap_err().to_string());
+        }
+
+        let binding_result = serde_json::from_slice::<IdentityBinding>(&binding_bytes);
+        if binding_result.is_err() {
+            return Err(binding_result.err().unwrap().to_string());
+        }
+
+        Ok(binding_result.unwrap())
+    }
+
+    // drop Enc(share) of a retired share, so recover never returns it. Ok if identity_id is not bound
+    pub(crate) async fn unbind_share(identity_id: &str, share_id: &str) -> Result<(), String> {
+        let binding_result = Self::load_binding(identity_id).await;
+        if binding_result.is_err() {
+            return Ok(());
+        }
+        let mut binding = binding_result.unwrap();
+        if binding.encrypted_shares.remove(share_id).is_none() {
+            return Ok(());
+        }
+        Self::save_binding(&binding).await
+    }
+}
+
+// identity_id comes from third-party identity providers, hex encode it to get a safe file name
+fn binding_path(identity_id: &str) -> String {
+    format!("identity_{}.identity", hex::encode(identity_id))
+}
@@ -1,2 +1,2 @@
 pub mod share_storage;
-
+pub mod identity_storage;
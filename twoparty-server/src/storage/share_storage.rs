@@ -1,6 +1,13 @@
-use common::socketmsg::types::SavedShare;
+use common::socketmsg::types::{SavedShare, SHARE_STATUS_ACTIVE, SHARE_STATUS_NOT_FOUND, SHARE_STATUS_PENDING, SHARE_STATUS_RETIRED, ShareStatusMsg};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read, Write};
+use std::sync::{Arc, LazyLock};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use common::get_tsp;
+use crate::metrics::storage_timer;
+use crate::storage::identity_storage::FileIdentityStorage;
 
 // `async` trait functions are not currently supported
 // pub trait ShareStorageTrait {
@@ -9,51 +16,359 @@ use std::io::{Read, Write};
 //     async fn load_share(share_id: String) -> Result<SavedShare, String>;
 // }
 
+// a rotation keeps the old share active and the new share pending,
+// until party1 commits the new share or the commit times out
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RotationRecord {
+    pub old_share_id: String,
+    pub new_share_id: String,
+    pub committed: bool,
+    pub timestamp: u128,
+}
+
+// old share_id -> lock, the pending share, commit & rollback of one rotation never interleave
+static ROTATION_LOCKS: LazyLock<Mutex<HashMap<String, Arc<Mutex<()>>>>> = LazyLock::new(|| {
+    Mutex::new(HashMap::new())
+});
+
+async fn lock_rotation(old_share_id: &str) -> OwnedMutexGuard<()> {
+    let lock = ROTATION_LOCKS.lock().await
+        .entry(old_share_id.to_string())
+        .or_default()
+        .clone();
+    lock.lock_owned().await
+}
 
 pub struct FileShareStorage;
 
 impl FileShareStorage {
     pub(crate) async fn save_share(share: SavedShare) -> Result<(), String> {
-        let share_bytes_result = serde_json::to_vec(&share);
-        if share_bytes_result.is_err() {
-            return Err(share_bytes_result.unwrap_err().to_string());
+        let _timer = storage_timer("save_share");
+        write_json(&share_path(&share.share_id), &share)
+    }
+
+    pub(crate) async fn load_share(share_id: String) -> Result<SavedShare, String> {
+        let _timer = storage_timer("load_share");
+        read_share(&share_path(&share_id))
+    }
+
+    // undo a save_share whose operation failed after it
+    pub(crate) async fn delete_share(share_id: &str) -> Result<(), String> {
+        let _timer = storage_timer("delete_share");
+        remove_file(&share_path(share_id))
+    }
+
+    // save the rotated share as pending, the old share stays active until commit
+    pub(crate) async fn save_pending_share(old_share_id: &str, share: SavedShare) -> Result<(), String> {
+        let _rotation_guard = lock_rotation(old_share_id).await;
+        let _timer = storage_timer("save_pending_share");
+        // a previous rotation which never got committed is discarded
+        if let Ok(record) = read_json::<RotationRecord>(&rotation_path(old_share_id)) {
+            if record.committed {
+                return Err(format!("share {} has already been rotated", old_share_id));
+            }
+            remove_file(&pending_path(&record.new_share_id))?;
         }
-        let share_bytes = share_bytes_result.unwrap();
 
-        let path = format!("share_{}.share", share.share_id);
-        let file_result = File::create(path);
-        if file_result.is_err() {
-            return Err(file_result.unwrap_err().to_string());
+        write_json(&pending_path(&share.share_id), &share)?;
+        let record = RotationRecord {
+            old_share_id: old_share_id.to_string(),
+            new_share_id: share.share_id.clone(),
+            committed: false,
+            timestamp: get_tsp(),
+        };
+        write_json(&rotation_path(old_share_id), &record)
+    }
+
+    // promote the pending share to active, the old share is retired.
+    // the committed record is written first, a crash before the pending share is moved is finished by a resent
+    // commit or by finish_commits at startup
+    pub(crate) async fn commit_rotation(old_share_id: &str, new_share_id: &str) -> Result<(), CommitError> {
+        let _rotation_guard = lock_rotation(old_share_id).await;
+        let _timer = storage_timer("commit_rotation");
+        let record_path = rotation_path(old_share_id);
+        if !std::path::Path::new(&record_path).exists() {
+            return Err(CommitError::NotFound(format!("no pending rotation for share {}", old_share_id)));
+        }
+        let mut record = read_json::<RotationRecord>(&record_path).map_err(CommitError::Storage)?;
+        if record.new_share_id != new_share_id {
+            return Err(CommitError::Mismatch(format!("pending rotation of share {} is not {}", old_share_id, new_share_id)));
         }
-        let mut file = file_result.unwrap();
+        // commit is idempotent, party1 may resend it after a dropped connection
+        if !record.committed {
+            record.committed = true;
+            record.timestamp = get_tsp();
+            write_json(&record_path, &record).map_err(CommitError::Storage)?;
+        }
+        finish_commit(&record).await.map_err(CommitError::Storage)
+    }
 
-        let result = file.write_all(&share_bytes);
-        if result.is_err() {
-            return Err(result.unwrap_err().to_string());
+    // discard the pending share if it is still not committed, return true if rolled back
+    pub(crate) async fn rollback_rotation(old_share_id: &str, new_share_id: &str) -> Result<bool, String> {
+        let _rotation_guard = lock_rotation(old_share_id).await;
+        let _timer = storage_timer("rollback_rotation");
+        let record_result = read_json::<RotationRecord>(&rotation_path(old_share_id));
+        if record_result.is_err() {
+            return Ok(false);
         }
+        let record = record_result.unwrap();
+        if record.committed || record.new_share_id != new_share_id {
+            return Ok(false);
+        }
+
+        remove_file(&pending_path(new_share_id))?;
+        // left by a commit of an older server, which saved the new share before the committed record
+        remove_file(&share_path(new_share_id))?;
+        remove_file(&rotation_path(old_share_id))?;
+        Ok(true)
+    }
 
+    // finish the commits a crash left between the committed record and the move of the pending share
+    pub(crate) async fn finish_commits() -> Result<(), String> {
+        let _timer = storage_timer("finish_commits");
+        for record in rotation_records().into_iter().filter(|record| record.committed) {
+            let _rotation_guard = lock_rotation(&record.old_share_id).await;
+            finish_commit(&record).await?;
+        }
         Ok(())
     }
 
-    pub(crate) async fn load_share(share_id: String) -> Result<SavedShare, String> {
-        let path = format!("share_{}.share", share_id);
-        let file_result = File::open(path);
-        if file_result.is_err() {
-            return Err(file_result.unwrap_err().to_string());
+    // the rotations not committed yet, e.g. left by a previous run of the server
+    pub(crate) async fn uncommitted_rotations() -> Vec<RotationRecord> {
+        let _timer = storage_timer("uncommitted_rotations");
+        rotation_records().into_iter()
+            .filter(|record| !record.committed)
+            .collect()
+    }
+
+    // identity_id of an active, retired or pending share
+    pub(crate) async fn share_owner(share_id: &str) -> Option<String> {
+        let _timer = storage_timer("share_owner");
+        read_share(&share_path(share_id))
+            .or_else(|_| read_share(&pending_path(share_id)))
+            .ok()
+            .map(|share| share.identity_id)
+    }
+
+    // which version of a share is active: the share itself, its rotated successor, or a pending rotation
+    pub(crate) async fn share_status(share_id: &str) -> ShareStatusMsg {
+        let _timer = storage_timer("share_status");
+        let mut status = ShareStatusMsg {
+            share_id: share_id.to_string(),
+            status: SHARE_STATUS_NOT_FOUND,
+            epoch: 0,
+            active_share_id: "".to_string(),
+            pending_share_id: "".to_string(),
+        };
+
+        if let Ok(pending_share) = read_share(&pending_path(share_id)) {
+            status.status = SHARE_STATUS_PENDING;
+            status.epoch = pending_share.epoch;
+            return status;
         }
-        let mut file = file_result.unwrap();
+        match read_share(&share_path(share_id)) {
+            Ok(share) => status.epoch = share.epoch,
+            Err(_) => return status,
+        }
+
+        // follow committed rotations to the latest share
+        let mut active_share_id = share_id.to_string();
+        loop {
+            match read_json::<RotationRecord>(&rotation_path(&active_share_id)) {
+                Ok(record) if record.committed => {
+                    active_share_id = record.new_share_id;
+                }
+                Ok(record) => {
+                    status.pending_share_id = record.new_share_id;
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+
+        status.status = if active_share_id == share_id { SHARE_STATUS_ACTIVE } else { SHARE_STATUS_RETIRED };
+        status.active_share_id = active_share_id;
+        status
+    }
+}
+
+#[derive(Debug)]
+pub enum CommitError {
+    // no rotation of the share is pending
+    NotFound(String),
+    // the pending rotation is of another new share
+    Mismatch(String),
+    Storage(String),
+}
 
-        let mut share_bytes = vec![];
-        let result = file.read_to_end(&mut share_bytes);
-        if result.is_err() {
-            return Err(result.unwrap_err().to_string());
+impl std::fmt::Display for CommitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommitError::NotFound(msg) | CommitError::Mismatch(msg) | CommitError::Storage(msg) => write!(f, "{}", msg),
         }
+    }
+}
 
-        let share_result = serde_json::from_slice::<SavedShare>(&share_bytes);
-        if share_result.is_err() {
-            return Err(share_result.err().unwrap().to_string());
+// move the pending share of a committed record to active, a step done before a crash is skipped
+async fn finish_commit(record: &RotationRecord) -> Result<(), String> {
+    let pending_share_result = read_share(&pending_path(&record.new_share_id));
+    if pending_share_result.is_err() {
+        if std::path::Path::new(&share_path(&record.new_share_id)).exists() {
+            return Ok(());
         }
+        return Err(format!("committed share {} is missing", record.new_share_id));
+    }
+    let pending_share = pending_share_result.unwrap();
+    let identity_id = pending_share.identity_id.clone();
+    FileShareStorage::save_share(pending_share).await?;
+    remove_file(&pending_path(&record.new_share_id))?;
+    // party1 binds the new share again, the server can not encrypt it
+    FileIdentityStorage::unbind_share(&identity_id, &record.old_share_id).await
+}
+
+fn rotation_records() -> Vec<RotationRecord> {
+    let read_dir_result = std::fs::read_dir(".");
+    if read_dir_result.is_err() {
+        return vec![];
+    }
+    read_dir_result.unwrap()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.starts_with("rotation_") && name.ends_with(".rotation"))
+        .filter_map(|name| read_json::<RotationRecord>(&name).ok())
+        .collect()
+}
+
+fn share_path(share_id: &str) -> String {
+    format!("share_{}.share", share_id)
+}
+
+fn pending_path(share_id: &str) -> String {
+    format!("share_{}.pending", share_id)
+}
+
+fn rotation_path(old_share_id: &str) -> String {
+    format!("rotation_{}.rotation", old_share_id)
+}
+
+// write a temp file, fsync it and rename it over path, a crash leaves either the old or the new content
+fn write_json<T: Serialize>(path: &str, value: &T) -> Result<(), String> {
+    let bytes_result = serde_json::to_vec(value);
+    if bytes_result.is_err() {
+        return Err(bytes_result.unwrap_err().to_string());
+    }
+    let bytes = bytes_result.unwrap();
+
+    let tmp_path = format!("{}.tmp", path);
+    let file_result = File::create(&tmp_path);
+    if file_result.is_err() {
+        return Err(file_result.unwrap_err().to_string());
+    }
+    let mut file = file_result.unwrap();
+
+    let result = file.write_all(&bytes).and_then(|_| file.sync_all());
+    if result.is_err() {
+        return Err(result.unwrap_err().to_string());
+    }
+    let rename_result = std::fs::rename(&tmp_path, path);
+    if rename_result.is_err() {
+        return Err(rename_result.unwrap_err().to_string());
+    }
+    // the rename itself is durable once the directory is synced
+    File::open(".").and_then(|dir| dir.sync_all()).map_err(|e| e.to_string())
+}
+
+// shares are parsed with migration of legacy schema
+fn read_share(path: &str) -> Result<SavedShare, String> {
+    let bytes_result = std::fs::read(path);
+    if bytes_result.is_err() {
+        return Err(bytes_result.unwrap_err().to_string());
+    }
+    Ok(SavedShare::from_json(&bytes_result.unwrap())?)
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &str) -> Result<T, String> {
+    let file_result = File::open(path);
+    if file_result.is_err() {
+        return Err(file_result.unwrap_err().to_string());
+    }
+    let mut file = file_result.unwrap();
+
+    let mut bytes = vec![];
+    let result = file.read_to_end(&mut bytes);
+    if result.is_err() {
+        return Err(result.unwrap_err().to_string());
+    }
+
+    let value_result = serde_json::from_slice::<T>(&bytes);
+    if value_result.is_err() {
+        return Err(value_result.err().unwrap().to_string());
+    }
+
+    Ok(value_result.unwrap())
+}
+
+fn remove_file(path: &str) -> Result<(), String> {
+    match std::fs::remove_file(path) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::get_uuid;
+    use common::socketmsg::types::{MPC_SCOPE_SECP256K1ECDSA, SAVED_SHARE_SCHEMA_VERSION, SavedShare, SHARE_STATUS_ACTIVE, SHARE_STATUS_NOT_FOUND, SHARE_STATUS_RETIRED};
+    use crate::storage::share_storage::{CommitError, FileShareStorage, pending_path, rotation_path, RotationRecord, share_path, write_json};
+
+    fn saved_share(share_id: &str) -> SavedShare {
+        SavedShare {
+            schema_version: SAVED_SHARE_SCHEMA_VERSION,
+            identity_id: "wangcy".to_string(),
+            share_id: share_id.to_string(),
+            scope: MPC_SCOPE_SECP256K1ECDSA,
+            party: 2,
+            epoch: 0,
+            created_at: 0,
+            rotated_at: 0,
+            uncompressed_pub: vec![],
+            share_detail: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_commit_interrupted() {
+        let old_share_id = get_uuid();
+        let new_share_id = get_uuid();
+        FileShareStorage::save_share(saved_share(&old_share_id)).await.unwrap();
+        FileShareStorage::save_pending_share(&old_share_id, saved_share(&new_share_id)).await.unwrap();
+        // a crash right after the committed record is written, the pending share is not moved yet
+        let mut record = std::fs::read(rotation_path(&old_share_id)).map(|bytes| serde_json::from_slice::<RotationRecord>(&bytes).unwrap()).unwrap();
+        record.committed = true;
+        write_json(&rotation_path(&old_share_id), &record).unwrap();
+
+        FileShareStorage::finish_commits().await.unwrap();
+        assert!(!std::path::Path::new(&pending_path(&new_share_id)).exists());
+        assert_eq!(FileShareStorage::share_status(&old_share_id).await.status, SHARE_STATUS_RETIRED);
+        assert_eq!(FileShareStorage::share_status(&new_share_id).await.status, SHARE_STATUS_ACTIVE);
+        // the resent commit of party1 succeeds
+        FileShareStorage::commit_rotation(&old_share_id, &new_share_id).await.unwrap();
+        assert!(matches!(FileShareStorage::commit_rotation(&old_share_id, &get_uuid()).await, Err(CommitError::Mismatch(_))));
+        assert!(matches!(FileShareStorage::commit_rotation(&get_uuid(), &new_share_id).await, Err(CommitError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_rollback_leftover_share() {
+        let old_share_id = get_uuid();
+        let new_share_id = get_uuid();
+        FileShareStorage::save_share(saved_share(&old_share_id)).await.unwrap();
+        FileShareStorage::save_pending_share(&old_share_id, saved_share(&new_share_id)).await.unwrap();
+        // an older server saved the new share before the committed record and crashed
+        write_json(&share_path(&new_share_id), &saved_share(&new_share_id)).unwrap();
 
-        Ok(share_result.unwrap())
+        assert!(FileShareStorage::rollback_rotation(&old_share_id, &new_share_id).await.unwrap());
+        assert_eq!(FileShareStorage::share_status(&old_share_id).await.status, SHARE_STATUS_ACTIVE);
+        assert_eq!(FileShareStorage::share_status(&new_share_id).await.status, SHARE_STATUS_NOT_FOUND);
     }
-}
\ No newline at end of file
+}
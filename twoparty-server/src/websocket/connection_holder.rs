@@ -1,35 +1,110 @@
 use std::collections::HashMap;
 
+use std::borrow::Cow;
 use std::sync::{Arc, LazyLock};
-use axum::extract::ws::{Message, WebSocket};
+use std::time::Duration;
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket};
 use futures_util::SinkExt;
 use futures_util::stream::SplitSink;
-use tokio::sync::{mpsc, RwLock};
-use tokio::sync::mpsc::{UnboundedSender};
-use tracing::info;
+use tokio::sync::{mpsc, RwLock, watch};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::Sender;
+use tracing::{info, warn};
+use crate::websocket::notice_holder::unsubscribe_notice;
+use common::get_tsp;
+use common::errors::DecodeError;
+use common::socketmsg::codec::decode_json;
+use serde::de::DeserializeOwned;
+use crate::config::AppConfig;
+use crate::websocket::step_cache::{put_step_cache, SessionSteps, take_step_cache, wait_steps};
 use twoparty_secp256k1::generic::share::Party2Share as Secp256k1Share;
 use twoparty_ed25519::generic::share::Ed25519Share;
 
 #[allow(clippy::type_complexity)]
-static PRODUCER_GROUP: LazyLock<Arc<RwLock<HashMap<String, UnboundedSender<Message>>>>> = LazyLock::new(|| {
+static PRODUCER_GROUP: LazyLock<Arc<RwLock<HashMap<String, WsProducer>>>> = LazyLock::new(|| {
     Arc::new(RwLock::new(HashMap::new()))
 });
 
+// the producer side of a connection, msgs are queued into a bounded channel drained by the consumer task
+#[derive(Clone)]
+pub struct WsProducer {
+    socket_id: String,
+    tx: Sender<Message>,
+    close_tx: Arc<watch::Sender<Option<CloseFrame<'static>>>>,
+}
+
+impl WsProducer {
+    pub fn send(&self, msg: Message) {
+        match self.tx.try_send(msg) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                // the peer does not read its responses
+                warn!("outbound queue full, socket_id={}", self.socket_id);
+                self.close(close_code::POLICY, "outbound queue full");
+            }
+            Err(TrySendError::Closed(_)) => {}
+        }
+    }
+
+    // wait for room in the queue instead of closing the connection, false if the connection is gone
+    pub async fn send_wait(&self, msg: Message) -> bool {
+        self.tx.send(msg).await.is_ok()
+    }
+
+    // send a close frame with the code & reason, then close the ws_sender
+    pub fn close(&self, code: u16, reason: &str) {
+        let frame = CloseFrame { code, reason: Cow::Owned(reason.to_string()) };
+        self.close_tx.send_replace(Some(frame));
+    }
+}
+
 #[derive(Clone)]
 pub struct SocketLocal {
     pub socket_id: String,
+    // chosen by party1 at step1, empty for clients which do not resume sessions
+    pub session_id: String,
+    // protocol version of step1, later steps must run the same one
+    pub protocol_version: u16,
+    // command & scope of step1, later steps must run the same ones
+    pub command: u8,
+    pub scope: u8,
     pub identity_id: String,
     pub share_id: String,
+    // epoch & created_at of the loaded share, a rotated share inherits them
+    pub share_epoch: u32,
+    pub share_created_at: u128,
+    pub share_rotated_at: u128,
     pub mpc_eph: HashMap<String, Vec<u8>>,
     pub secp256k1_share: Option<Secp256k1Share>,
     pub ed25519_share: Option<Ed25519Share>,
 }
 
+// a value kept in mpc_eph by a previous step of the session
+pub fn eph_state<T: DeserializeOwned>(mpc_eph: &HashMap<String, Vec<u8>>, key: &str) -> Result<T, DecodeError> {
+    let option_bytes = mpc_eph.get(key);
+    if option_bytes.is_none() {
+        return Err(DecodeError::Missing(format!("mpc_eph.{}", key)));
+    }
+    decode_json(option_bytes.unwrap())
+}
+
 #[allow(clippy::type_complexity)]
 static SOCKET_LOCALS: LazyLock<Arc<RwLock<HashMap<String, SocketLocal>>>> = LazyLock::new(|| {
     Arc::new(RwLock::new(HashMap::new()))
 });
 
+// the session of a dropped connection, kept for session_grace_secs
+struct ParkedSession {
+    socket_local: SocketLocal,
+    steps: Option<SessionSteps>,
+    parked_at: u128,
+}
+
+// session_id -> parked session
+static PARKED_SESSIONS: LazyLock<Arc<RwLock<HashMap<String, ParkedSession>>>> = LazyLock::new(|| {
+    Arc::new(RwLock::new(HashMap::new()))
+});
+
 
 // cannot borrow data in dereference of `tokio::sync::RwLockReadGuard<'_, HashMap<std::string::String, SocketLocal>>` as mutable
 pub async fn get_socket_local(socket_id: &str) -> Option<SocketLocal> {
@@ -52,28 +127,136 @@ pub async fn drop_producer(socket_id: &str) {
     // drop the producer will close the channel, then trigger consumer task return
     PRODUCER_GROUP.write().await.remove(socket_id);
     info!("drop producer:socket_id={}", socket_id);
-    // drop socket_locals
-    SOCKET_LOCALS.write().await.remove(socket_id);
+    unsubscribe_notice(socket_id).await;
+    // a step still computed writes its eph & rsp under this socket_id, park the session once it returns
+    wait_steps(socket_id).await;
+    // drop socket_locals, park the session so party1 can resume it on a new connection
+    let option_socket_local = SOCKET_LOCALS.write().await.remove(socket_id);
+    let steps = take_step_cache(socket_id).await;
+    if let Some(socket_local) = option_socket_local {
+        if !socket_local.session_id.is_empty() {
+            park_session(socket_local, steps).await;
+        }
+    }
+}
+
+async fn park_session(socket_local: SocketLocal, steps: Option<SessionSteps>) {
+    let session_id = socket_local.session_id.clone();
+    let parked_at = get_tsp();
+    PARKED_SESSIONS.write().await.insert(session_id.clone(), ParkedSession { socket_local, steps, parked_at });
+
+    let grace = Duration::from_secs(AppConfig::get_app_config().session_grace_secs as u64);
+    tokio::spawn(async move {
+        tokio::time::sleep(grace).await;
+        let mut parked_sessions = PARKED_SESSIONS.write().await;
+        // unless resumed & parked again meanwhile
+        if parked_sessions.get(&session_id).is_some_and(|parked| parked.parked_at == parked_at) {
+            parked_sessions.remove(&session_id);
+            info!("parked session expired: session_id={}", session_id);
+        }
+    });
+}
+
+// move the session to this connection, from a dropped connection or from one whose drop is not noticed yet.
+// Ok(false) if there is no such session or it already runs on this connection
+pub async fn resume_session(session_id: &str, socket_id: &str, auth_subject: Option<&str>) -> Result<bool, String> {
+    let authorize = |socket_local: &SocketLocal| -> Result<(), String> {
+        match auth_subject {
+            Some(subject) if subject != socket_local.identity_id => Err("session is owned by another identity".to_string()),
+            _ => Ok(()),
+        }
+    };
+
+    let (mut socket_local, option_steps) = loop {
+        let mut parked_sessions = PARKED_SESSIONS.write().await;
+        if let Some(parked) = parked_sessions.get(session_id) {
+            authorize(&parked.socket_local)?;
+            let parked = parked_sessions.remove(session_id).unwrap();
+            break (parked.socket_local, parked.steps);
+        }
+        drop(parked_sessions);
+
+        let old_socket_id = {
+            let socket_locals = SOCKET_LOCALS.read().await;
+            let option_old = socket_locals.values().find(|local| local.session_id == session_id);
+            if option_old.is_none() {
+                return Ok(false);
+            }
+            let old_socket_id = option_old.unwrap().socket_id.clone();
+            if old_socket_id == socket_id {
+                return Ok(false);
+            }
+            authorize(option_old.unwrap())?;
+            old_socket_id
+        };
+        // the resent step may still be computed on the old connection, move the session with its result
+        // rather than running the step twice
+        wait_steps(&old_socket_id).await;
+        let option_old = SOCKET_LOCALS.write().await.remove(&old_socket_id);
+        // parked by drop_producer meanwhile, look again
+        if let Some(socket_local) = option_old {
+            break (socket_local, take_step_cache(&old_socket_id).await);
+        }
+    };
+
+    socket_local.socket_id = socket_id.to_string();
+    upsert_socket_local(socket_local).await;
+    if let Some(steps) = option_steps {
+        put_step_cache(socket_id, steps).await;
+    }
+    Ok(true)
+}
+
+// a producer whose msgs are read from the receiver instead of a websocket
+#[cfg(test)]
+pub fn test_producer(socket_id: &str, outbound_queue: usize) -> (WsProducer, mpsc::Receiver<Message>) {
+    let (tx, rx) = mpsc::channel::<Message>(outbound_queue);
+    let (close_tx, _close_rx) = watch::channel::<Option<CloseFrame<'static>>>(None);
+    let producer = WsProducer {
+        socket_id: socket_id.to_string(),
+        tx,
+        close_tx: Arc::new(close_tx),
+    };
+    (producer, rx)
 }
 
-pub async fn share_ws_sender_with_channel(mut ws_sender: SplitSink<WebSocket, Message>, socket_id: String) -> UnboundedSender<Message> {
-    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
-    // cache the tx
-    PRODUCER_GROUP.write().await.insert(socket_id, tx.clone());
+pub async fn share_ws_sender_with_channel(mut ws_sender: SplitSink<WebSocket, Message>, socket_id: String, outbound_queue: usize) -> WsProducer {
+    let (tx, mut rx) = mpsc::channel::<Message>(outbound_queue);
+    let (close_tx, mut close_rx) = watch::channel::<Option<CloseFrame<'static>>>(None);
+    let producer = WsProducer {
+        socket_id: socket_id.clone(),
+        tx,
+        close_tx: Arc::new(close_tx),
+    };
+    // cache the producer
+    PRODUCER_GROUP.write().await.insert(socket_id, producer.clone());
     // spawn a task to manage the ws_sender
     tokio::spawn(async move {
         loop {
-            let option_msg = rx.recv().await;
-            if option_msg.is_none() {
-                info!("channel closed, stop the consumer task, close the ws_sender");
-                ws_sender.close().await.unwrap_or(());
-                return;
+            tokio::select! {
+                option_msg = rx.recv() => {
+                    if option_msg.is_none() {
+                        info!("channel closed, stop the consumer task, close the ws_sender");
+                        ws_sender.close().await.unwrap_or(());
+                        return;
+                    }
+                    let msg = option_msg.unwrap();
+                    ws_sender.send(msg).await.unwrap_or(());
+                }
+                changed = close_rx.changed() => {
+                    // Err means all producers are dropped without closing
+                    if changed.is_ok() {
+                        let option_frame = close_rx.borrow().clone();
+                        info!("close the ws_sender with {:?}", option_frame);
+                        ws_sender.send(Message::Close(option_frame)).await.unwrap_or(());
+                    }
+                    ws_sender.close().await.unwrap_or(());
+                    return;
+                }
             }
-            let msg = option_msg.unwrap();
-            ws_sender.send(msg).await.unwrap_or(());
         }
     });
-    tx
+    producer
 }
 
 
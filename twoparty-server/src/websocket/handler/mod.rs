@@ -1,3 +1,7 @@
 pub mod mpc22_handler;
 mod mpc22_ed25519;
 mod mpc22_secp256k1;
+pub mod mpc22_rotation;
+mod mpc22_identity;
+pub mod notice_handler;
+pub mod negotiate_handler;
@@ -1,55 +1,171 @@
 use std::collections::HashMap;
 
-use tracing::{error};
+use tracing::{error, info};
 use crate::websocket::inbound_dispatcher::InboundWithTx;
 
-use common::socketmsg::{RSP_CODE_BAD_REQUEST, RSP_CODE_NOT_FOUND};
-use common::socketmsg::types::{Mpc22Msg, MPC_KEYGEN, MPC_SIGN, MPC_ROTATE, MPC_EXPORT, MPC_SCOPE_SECP256K1ECDSA, MPC_SCOPE_ED25519EDDSA};
+use common::errors::MpcErrorKind;
+use common::payload::DecodedPayload;
+use common::socketmsg::codec::decode_json;
+use common::socketmsg::{Capabilities, REQ_CODE_MPC22, REQ_CODE_SUBSCRIBE_NOTICE, ScopeCapabilities};
+use common::socketmsg::types::{Mpc22Msg, MPC_KEYGEN, MPC_SIGN, MPC_ROTATE, MPC_EXPORT, MPC_SCOPE_SECP256K1ECDSA, MPC_SCOPE_ED25519EDDSA, MPC_ROTATE_COMMIT, MPC_SHARE_STATUS, SHARE_STATUS_ACTIVE, MPC_IDENTITY_BIND, MPC_IDENTITY_RECOVER, MPC_PROTOCOL_V1, MPC_PROTOCOL_V2};
 use twoparty_ed25519::generic::share::Ed25519Share;
 use twoparty_secp256k1::generic::share::Party2Share;
+use sha2::{Digest, Sha256};
+use crate::audit::{audit_failure, command_action, AuditEntry};
+use crate::metrics::step_timer;
+use crate::ratelimit::{check_rate_limit, LimitKind};
 use crate::storage::share_storage::FileShareStorage;
-use crate::websocket::connection_holder::{SocketLocal, get_socket_local, upsert_socket_local};
+use crate::websocket::connection_holder::{SocketLocal, get_socket_local, resume_session, upsert_socket_local};
+use crate::websocket::step_cache::{lookup_step, StepLookup};
 use crate::websocket::handler::mpc22_ed25519::{ed25519_keygen, ed25519_rotate, ed25519_sign};
+use crate::websocket::handler::mpc22_identity::{identity_bind, identity_recover};
+use crate::websocket::handler::mpc22_rotation::{rotate_commit, share_status};
 use crate::websocket::handler::mpc22_secp256k1::{secp256k1_export, secp256k1_keygen, secp256k1_rotate, secp256k1_sign};
 
-pub async fn mpc22_handler(inbound: InboundWithTx) {
+pub async fn mpc22_handler(mut inbound: InboundWithTx) {
     let req = &inbound.msg_wrapper;
 
-    let parse_result = serde_json::from_slice::<Mpc22Msg>(&req.body);
+    let parse_result = inbound.decode_detail::<Mpc22Msg>(&req.body);
     if parse_result.is_err() {
         error!("fail to parse mpc22 msg");
-        inbound.fail_rsp(RSP_CODE_BAD_REQUEST, parse_result.err().unwrap().to_string()).await;
+        inbound.fail(MpcErrorKind::MalformedMessage, parse_result.err().unwrap().to_string()).await;
         return;
     }
     let mpc22_msg = parse_result.unwrap();
     let socket_id = inbound.socket_id.clone();
+    // observed when the step returns
+    let _step_timer = step_timer(mpc22_msg.scope, mpc22_msg.command, mpc22_msg.step);
 
-    // init connection_local
+    if let Some(auth_identity) = &inbound.auth_identity {
+        if auth_identity.is_expired() {
+            inbound.fail(MpcErrorKind::Unauthorized, "token expired".to_string()).await;
+            return;
+        }
+    }
+
+    if !protocol_versions(mpc22_msg.scope).contains(&mpc22_msg.version) {
+        let err = format!("unsupported protocol version {} of scope {}", mpc22_msg.version, mpc22_msg.scope);
+        error!("{}", &err);
+        inbound.fail(MpcErrorKind::VersionMismatch, err).await;
+        return;
+    }
+
+    // single step commands, no need of socket_local
+    if mpc22_msg.command == MPC_ROTATE_COMMIT || mpc22_msg.command == MPC_SHARE_STATUS {
+        if let Some(owner) = FileShareStorage::share_owner(&mpc22_msg.share_id).await {
+            if !authorize_identity(&inbound, &owner).await {
+                return;
+            }
+        }
+    }
+    match mpc22_msg.command {
+        MPC_ROTATE_COMMIT => {
+            rotate_commit(inbound, mpc22_msg.scope, &mpc22_msg.share_id, &mpc22_msg.msg_detail).await;
+            return;
+        }
+        MPC_SHARE_STATUS => {
+            share_status(inbound, &mpc22_msg.share_id).await;
+            return;
+        }
+        _ => {}
+    }
+
+    // party1 resends a step of its session on a new connection after a disconnection
     let step = mpc22_msg.step;
+    if mpc22_msg.version >= MPC_PROTOCOL_V2 && mpc22_msg.session_id.is_empty() {
+        inbound.fail(MpcErrorKind::InvalidRequest, "session_id is required since protocol v2".to_string()).await;
+        return;
+    }
+    if step > 1 && !mpc22_msg.session_id.is_empty() {
+        let auth_subject = inbound.auth_identity.as_ref().map(|identity| identity.subject.as_str());
+        match resume_session(&mpc22_msg.session_id, &socket_id, auth_subject).await {
+            Ok(true) => {
+                info!("resume session {} on socket_id={}", mpc22_msg.session_id, socket_id);
+            }
+            Ok(false) => {}
+            Err(e) => {
+                error!("fail to resume session {}: {}", mpc22_msg.session_id, e);
+                inbound.fail(MpcErrorKind::Forbidden, e).await;
+                return;
+            }
+        }
+    }
+
+    // an exact retry of a completed step gets the same rsp, party1 may resend a step after its request timeout.
+    // a retry of a step still computed waits for it
+    let req_hash = Sha256::digest(&req.body).to_vec();
+    match lookup_step(&socket_id, step, &req_hash).await {
+        StepLookup::Cached(rsp_body) => {
+            info!("reply the cached rsp of step {}, socket_id={}", step, socket_id);
+            inbound.success_rsp(Some(rsp_body)).await;
+            return;
+        }
+        StepLookup::Conflict => {
+            let err = format!("step {} already completed with a different request", step);
+            error!("{}", &err);
+            inbound.fail(MpcErrorKind::InvalidRequest, err).await;
+            return;
+        }
+        StepLookup::Miss(step_flight) => {
+            inbound.cache_step_rsp(step, req_hash, step_flight);
+        }
+    }
+
+    // init connection_local
     if step == 1 {
         let mut socket_local = SocketLocal {
             socket_id: socket_id.clone(),
+            session_id: mpc22_msg.session_id.clone(),
+            protocol_version: mpc22_msg.version,
+            command: mpc22_msg.command,
+            scope: mpc22_msg.scope,
             identity_id: "".to_string(),
             share_id: "".to_string(),
+            share_epoch: 0,
+            share_created_at: 0,
+            share_rotated_at: 0,
             mpc_eph: HashMap::new(),
             secp256k1_share: None,
             ed25519_share: None,
         };
 
-        if &mpc22_msg.command == &MPC_KEYGEN {
-            let identity_id = &mpc22_msg.identity_id;
+        // commands bound to identity_id rather than a share
+        let by_identity = [MPC_KEYGEN, MPC_IDENTITY_BIND, MPC_IDENTITY_RECOVER].contains(&mpc22_msg.command);
+        if by_identity {
+            let mut identity_id = mpc22_msg.identity_id.clone();
+            // the share is bound to the authenticated subject
+            if let Some(auth_identity) = &inbound.auth_identity {
+                if identity_id.is_empty() {
+                    identity_id = auth_identity.subject.clone();
+                }
+                if !authorize_identity(&inbound, &identity_id).await {
+                    audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::Forbidden, &identity_id, "share is owned by another identity").await;
+                    return;
+                }
+            }
             if identity_id.is_empty() {
                 error!("identity_id is empty");
-                inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "identity_id is empty".to_string()).await;
+                inbound.fail(MpcErrorKind::InvalidRequest, "identity_id is empty".to_string()).await;
+                audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::InvalidRequest, "", "identity_id is empty").await;
                 return;
             }
-            socket_local.identity_id = identity_id.clone();
+            socket_local.identity_id = identity_id;
         } else {
             let share_id = &mpc22_msg.share_id;
             socket_local.share_id = share_id.clone();
             if share_id.is_empty() {
                 error!("share_id is empty for {}", &mpc22_msg.command);
-                inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "share_id is empty".to_string()).await;
+                inbound.fail(MpcErrorKind::InvalidRequest, "share_id is empty".to_string()).await;
+                audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::InvalidRequest, &mpc22_msg.identity_id, "share_id is empty").await;
+                return;
+            }
+            // only the active version of a share can be used
+            let status = FileShareStorage::share_status(share_id).await;
+            if status.status != SHARE_STATUS_ACTIVE {
+                let err = format!("share is not active, status={}, active_share_id={}", status.status, status.active_share_id);
+                error!("{}", &err);
+                inbound.fail(MpcErrorKind::ShareNotFound, err.clone()).await;
+                audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::ShareNotFound, &mpc22_msg.identity_id, &err).await;
                 return;
             }
             // load share
@@ -57,24 +173,82 @@ pub async fn mpc22_handler(inbound: InboundWithTx) {
             if saved_share_result.is_err() {
                 let err = format!("fail to load share:{}", saved_share_result.err().unwrap());
                 error!("{}", &err);
-                inbound.fail_rsp(RSP_CODE_BAD_REQUEST, err).await;
+                inbound.fail(MpcErrorKind::ShareNotFound, err.clone()).await;
+                audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::ShareNotFound, &mpc22_msg.identity_id, &err).await;
                 return;
             }
             let saved_share = saved_share_result.unwrap();
+            // the share is dispatched by mpc22_msg.scope
+            if saved_share.scope != mpc22_msg.scope {
+                let err = format!("scope {} does not match scope {} of share {}", mpc22_msg.scope, saved_share.scope, share_id);
+                error!("{}", &err);
+                inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+                audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::MalformedMessage, &saved_share.identity_id, &err).await;
+                return;
+            }
+            if !authorize_identity(&inbound, &saved_share.identity_id).await {
+                audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::Forbidden, &saved_share.identity_id, "share is owned by another identity").await;
+                return;
+            }
+            // both parties must hold the same epoch of the share
+            if saved_share.epoch != mpc22_msg.epoch {
+                let err = format!("share epoch not match, party1 epoch={}, party2 epoch={}", mpc22_msg.epoch, saved_share.epoch);
+                error!("{}", &err);
+                inbound.fail(MpcErrorKind::VersionMismatch, err.clone()).await;
+                audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::VersionMismatch, &saved_share.identity_id, &err).await;
+                return;
+            }
+            // decode the unsigned payload now, its digest is checked when party1 reveals message_digest
+            if let Some(sign_payload) = &mpc22_msg.sign_payload {
+                if mpc22_msg.command == MPC_SIGN {
+                    let decode_result = sign_payload.decode();
+                    if decode_result.is_err() {
+                        let err = format!("fail to decode sign_payload: {}", decode_result.unwrap_err());
+                        error!("{}", &err);
+                        inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+                        audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::MalformedMessage, &saved_share.identity_id, &err).await;
+                        return;
+                    }
+                    let decoded_payload = decode_result.unwrap();
+                    socket_local.mpc_eph.insert("sign_payload".to_string(), serde_json::to_vec(&decoded_payload).unwrap());
+                }
+            }
+            socket_local.share_epoch = saved_share.epoch;
+            socket_local.share_created_at = saved_share.created_at;
+            socket_local.share_rotated_at = saved_share.rotated_at;
             // set socket_local.identity_id
             let identity_id = &saved_share.identity_id;
             socket_local.identity_id = identity_id.clone();
             // set socket_local share TODO: cache share
-            match saved_share.scope {
-                MPC_SCOPE_SECP256K1ECDSA => {
-                    let inner_share = serde_json::from_slice::<Party2Share>(&saved_share.share_detail).unwrap();
-                    socket_local.secp256k1_share = Some(inner_share);
-                }
-                MPC_SCOPE_ED25519EDDSA => {
-                    let inner_share = serde_json::from_slice::<Ed25519Share>(&saved_share.share_detail).unwrap();
-                    socket_local.ed25519_share = Some(inner_share);
-                }
-                _ => {}
+            let load_result = match saved_share.scope {
+                MPC_SCOPE_SECP256K1ECDSA => decode_json::<Party2Share>(&saved_share.share_detail)
+                    .map(|inner_share| socket_local.secp256k1_share = Some(inner_share)),
+                MPC_SCOPE_ED25519EDDSA => decode_json::<Ed25519Share>(&saved_share.share_detail)
+                    .map(|inner_share| socket_local.ed25519_share = Some(inner_share)),
+                _ => Ok(()),
+            };
+            if load_result.is_err() {
+                let err = format!("fail to load share {}: {}", saved_share.share_id, load_result.unwrap_err());
+                error!("{}", &err);
+                inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+                audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::MalformedMessage, &saved_share.identity_id, &err).await;
+                return;
+            }
+        }
+        if let Err(retry_after_ms) = check_rate_limit(LimitKind::SessionPerIdentity, &socket_local.identity_id) {
+            let err = format!("too many mpc sessions of identity_id={}, retry after {}ms", socket_local.identity_id, retry_after_ms);
+            error!("{}", &err);
+            inbound.too_many_requests_rsp(retry_after_ms, err.clone()).await;
+            audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::RateLimited, &socket_local.identity_id, &err).await;
+            return;
+        }
+        if mpc22_msg.command == MPC_SIGN {
+            if let Err(retry_after_ms) = check_rate_limit(LimitKind::SignPerShare, &socket_local.share_id) {
+                let err = format!("too many signs of share_id={}, retry after {}ms", socket_local.share_id, retry_after_ms);
+                error!("{}", &err);
+                inbound.too_many_requests_rsp(retry_after_ms, err.clone()).await;
+                audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::RateLimited, &socket_local.identity_id, &err).await;
+                return;
             }
         }
         // insert connection_local
@@ -85,11 +259,35 @@ pub async fn mpc22_handler(inbound: InboundWithTx) {
     let option_socket_local = get_socket_local(&socket_id).await;
     if option_socket_local.is_none() {
         error!("can not find socket_local");
-        inbound.fail_rsp(RSP_CODE_NOT_FOUND, "can not find socket_local".to_string()).await;
+        inbound.fail(MpcErrorKind::NotFound, "can not find socket_local".to_string()).await;
+        audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::NotFound, &mpc22_msg.identity_id, "can not find socket_local").await;
         return;
     }
     let socket_local = option_socket_local.unwrap();
-
+    // later steps run the command, scope, version & session of step1
+    if step > 1 {
+        if socket_local.command != mpc22_msg.command || socket_local.scope != mpc22_msg.scope {
+            let err = format!("command {} of scope {} does not match command {} of scope {} of step1",
+                mpc22_msg.command, mpc22_msg.scope, socket_local.command, socket_local.scope);
+            error!("{}", &err);
+            inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+            audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::MalformedMessage, &socket_local.identity_id, &err).await;
+            return;
+        }
+        if socket_local.protocol_version != mpc22_msg.version {
+            let err = format!("protocol version {} does not match {} of step1", mpc22_msg.version, socket_local.protocol_version);
+            error!("{}", &err);
+            inbound.fail(MpcErrorKind::VersionMismatch, err.clone()).await;
+            audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::VersionMismatch, &socket_local.identity_id, &err).await;
+            return;
+        }
+        if mpc22_msg.version >= MPC_PROTOCOL_V2 && socket_local.session_id != mpc22_msg.session_id {
+            error!("session_id {} does not match the session of step1", mpc22_msg.session_id);
+            inbound.fail(MpcErrorKind::InvalidRequest, "session_id does not match the session of step1".to_string()).await;
+            audit_refusal(&inbound, &mpc22_msg, MpcErrorKind::InvalidRequest, &socket_local.identity_id, "session_id does not match the session of step1").await;
+            return;
+        }
+    }
 
     let command = &mpc22_msg.command;
     let scope = &mpc22_msg.scope;
@@ -104,7 +302,7 @@ pub async fn mpc22_handler(inbound: InboundWithTx) {
                     ed25519_keygen(inbound, socket_local.clone(), step, msg_detail).await;
                 }
                 _ => {
-                    inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "unsupported scope".to_string()).await;
+                    inbound.fail(MpcErrorKind::Unsupported, "unsupported scope".to_string()).await;
                 }
             }
         }
@@ -117,7 +315,7 @@ pub async fn mpc22_handler(inbound: InboundWithTx) {
                     ed25519_sign(inbound, socket_local.clone(), step, msg_detail).await;
                 }
                 _ => {
-                    inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "unsupported scope".to_string()).await;
+                    inbound.fail(MpcErrorKind::Unsupported, "unsupported scope".to_string()).await;
                 }
             }
         }
@@ -130,7 +328,7 @@ pub async fn mpc22_handler(inbound: InboundWithTx) {
                     ed25519_rotate(inbound, socket_local.clone(), step, msg_detail).await;
                 }
                 _ => {
-                    inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "unsupported scope".to_string()).await;
+                    inbound.fail(MpcErrorKind::Unsupported, "unsupported scope".to_string()).await;
                 }
             }
         }
@@ -140,13 +338,175 @@ pub async fn mpc22_handler(inbound: InboundWithTx) {
                     secp256k1_export(inbound, socket_local.clone(), step, msg_detail).await;
                 }
                 _ => {
-                    inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "only SECP256K1ECDSA support export".to_string()).await;
+                    inbound.fail(MpcErrorKind::Unsupported, "only SECP256K1ECDSA support export".to_string()).await;
                 }
             }
         }
+        MPC_IDENTITY_BIND => {
+            identity_bind(inbound, socket_local.clone(), step, msg_detail).await;
+        }
+        MPC_IDENTITY_RECOVER => {
+            identity_recover(inbound, socket_local.clone(), step, msg_detail).await;
+        }
         _ => {
-            inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "unsupported command".to_string()).await;
+            inbound.fail(MpcErrorKind::Unsupported, "unsupported command".to_string()).await;
+        }
+    }
+}
+
+// the protocol versions party2 runs for a scope, scope 0 is for commands not bound to a scope
+pub fn protocol_versions(scope: u8) -> Vec<u16> {
+    match scope {
+        0 | MPC_SCOPE_SECP256K1ECDSA | MPC_SCOPE_ED25519EDDSA => vec![MPC_PROTOCOL_V1, MPC_PROTOCOL_V2],
+        _ => vec![],
+    }
+}
+
+// advertised at /capabilities, keep it in line with the dispatch of mpc22_handler
+pub fn supported_capabilities() -> Capabilities {
+    let scope_independent = [MPC_ROTATE_COMMIT, MPC_SHARE_STATUS, MPC_IDENTITY_BIND, MPC_IDENTITY_RECOVER];
+    let secp256k1_commands = [MPC_KEYGEN, MPC_SIGN, MPC_ROTATE, MPC_EXPORT];
+    let ed25519_commands = [MPC_KEYGEN, MPC_SIGN, MPC_ROTATE];
+    Capabilities {
+        req_codes: vec![REQ_CODE_MPC22, REQ_CODE_SUBSCRIBE_NOTICE],
+        scopes: vec![
+            ScopeCapabilities {
+                scope: MPC_SCOPE_SECP256K1ECDSA,
+                commands: [secp256k1_commands.as_slice(), &scope_independent].concat(),
+                protocol_versions: protocol_versions(MPC_SCOPE_SECP256K1ECDSA),
+            },
+            ScopeCapabilities {
+                scope: MPC_SCOPE_ED25519EDDSA,
+                commands: [ed25519_commands.as_slice(), &scope_independent].concat(),
+                protocol_versions: protocol_versions(MPC_SCOPE_ED25519EDDSA),
+            },
+        ],
+    }
+}
+
+// reject operations on shares owned by another identity, always pass if authentication is disabled
+async fn authorize_identity(inbound: &InboundWithTx, identity_id: &str) -> bool {
+    if let Some(auth_identity) = &inbound.auth_identity {
+        if auth_identity.subject != identity_id {
+            error!("identity {} is not allowed to access shares of {}", auth_identity.subject, identity_id);
+            inbound.fail(MpcErrorKind::Forbidden, "share is owned by another identity".to_string()).await;
+            return false;
         }
     }
+    true
+}
+
+// a request refused before its curve handler runs is audited as a failure of the command
+async fn audit_refusal(inbound: &InboundWithTx, mpc22_msg: &Mpc22Msg, kind: MpcErrorKind, identity_id: &str, err: &str) {
+    if let Some(action) = command_action(mpc22_msg.command) {
+        let audit_entry = AuditEntry::new(action, mpc22_msg.scope, &inbound.peer, identity_id, &mpc22_msg.share_id);
+        audit_failure(audit_entry, kind, err, None).await;
+    }
+}
+
+// the payload decoded at sign step1 must hash to the message_digest party2 is about to co-sign
+pub fn verified_sign_payload(mpc_eph: &HashMap<String, Vec<u8>>, share_id: &str, message_digest: &[u8]) -> Result<Option<DecodedPayload>, String> {
+    let option_payload_bytes = mpc_eph.get("sign_payload");
+    if option_payload_bytes.is_none() {
+        return Ok(None);
+    }
+    let decoded_payload = decode_json::<DecodedPayload>(option_payload_bytes.unwrap())?;
+    if decoded_payload.digest != message_digest {
+        return Err("message_digest does not match the sign_payload".to_string());
+    }
+    info!("co-sign payload of share_id={}, format={}, chain_id={:?}, outputs={:?}",
+        share_id, decoded_payload.format, decoded_payload.chain_id, decoded_payload.outputs);
+    Ok(Some(decoded_payload))
 }
 
+#[cfg(test)]
+mod test {
+    use axum::extract::ws::Message;
+    use common::errors::MpcErrorKind;
+    use common::{get_tsp, get_uuid};
+    use common::socketmsg::codec::{decode_msg, encode_detail};
+    use common::socketmsg::{MSG_ACTION_REQ, MSG_VERSION_V1, MsgWrapper, REQ_CODE_MPC22, RSP_CODE_SUCCESS};
+    use common::socketmsg::types::{Mpc22Msg, MPC_EXPORT, MPC_KEYGEN, MPC_PROTOCOL_V1, MPC_SCOPE_ED25519EDDSA, MPC_SCOPE_SECP256K1ECDSA, MPC_SIGN, SAVED_SHARE_SCHEMA_VERSION, SavedShare};
+    use twoparty_ed25519::keygen;
+    use crate::storage::share_storage::FileShareStorage;
+    use crate::websocket::connection_holder::test_producer;
+    use crate::websocket::handler::mpc22_handler::mpc22_handler;
+    use crate::websocket::inbound_dispatcher::InboundWithTx;
+
+    fn mpc22_msg(command: u8, scope: u8, step: u8, share_id: &str, msg_detail: Vec<u8>) -> Mpc22Msg {
+        Mpc22Msg {
+            command,
+            scope,
+            party: 1,
+            step,
+            msg_detail,
+            identity_id: "wangcy".to_string(),
+            share_id: share_id.to_string(),
+            epoch: 0,
+            sign_payload: None,
+            session_id: "".to_string(),
+            version: MPC_PROTOCOL_V1,
+        }
+    }
+
+    // run the req through mpc22_handler, return its rsp
+    async fn send(socket_id: &str, mpc22_msg: &Mpc22Msg) -> MsgWrapper {
+        let (producer, mut rx) = test_producer(socket_id, 4);
+        let req = MsgWrapper {
+            seq: mpc22_msg.step as u32,
+            timestamp: get_tsp(),
+            action: MSG_ACTION_REQ,
+            action_code: REQ_CODE_MPC22,
+            body: serde_json::to_vec(mpc22_msg).unwrap(),
+            error_msg: "".to_string(),
+            notice_id: "".to_string(),
+            version: MSG_VERSION_V1,
+            error_code: 0,
+        };
+        let inbound = InboundWithTx::new(req, producer, socket_id.to_string(), "127.0.0.1:9000".to_string(), None);
+        mpc22_handler(inbound).await;
+        match rx.recv().await.unwrap() {
+            Message::Binary(bytes) => decode_msg(&bytes).unwrap(),
+            msg => panic!("unexpected outbound msg {:?}", msg),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_step1_scope_mismatch() {
+        let share_id = get_uuid();
+        let now = get_tsp();
+        let saved_share = SavedShare {
+            schema_version: SAVED_SHARE_SCHEMA_VERSION,
+            identity_id: "wangcy".to_string(),
+            share_id: share_id.clone(),
+            scope: MPC_SCOPE_SECP256K1ECDSA,
+            party: 2,
+            epoch: 0,
+            created_at: now,
+            rotated_at: now,
+            uncompressed_pub: vec![],
+            share_detail: b"{}".to_vec(),
+        };
+        FileShareStorage::save_share(saved_share).await.unwrap();
+
+        // an ed25519 sign naming a secp256k1 share
+        let rsp = send(&get_uuid(), &mpc22_msg(MPC_SIGN, MPC_SCOPE_ED25519EDDSA, 1, &share_id, vec![])).await;
+        std::fs::remove_file(format!("share_{}.share", share_id)).unwrap();
+        assert_eq!(rsp.error_code, MpcErrorKind::MalformedMessage.code());
+    }
+
+    #[tokio::test]
+    async fn test_step2_command_mismatch() {
+        let socket_id = get_uuid();
+        let (party1_msg1, _assets1) = keygen::party1::party1_step1();
+        let msg_detail = encode_detail(MSG_VERSION_V1, &party1_msg1).unwrap();
+        let rsp = send(&socket_id, &mpc22_msg(MPC_KEYGEN, MPC_SCOPE_ED25519EDDSA, 1, "", msg_detail)).await;
+        assert_eq!(rsp.action_code, RSP_CODE_SUCCESS);
+
+        // step2 of other commands & scopes after an ed25519 keygen step1
+        for (command, scope) in [(MPC_SIGN, MPC_SCOPE_ED25519EDDSA), (MPC_KEYGEN, MPC_SCOPE_SECP256K1ECDSA), (MPC_EXPORT, MPC_SCOPE_SECP256K1ECDSA)] {
+            let rsp = send(&socket_id, &mpc22_msg(command, scope, 2, "", vec![])).await;
+            assert_eq!(rsp.error_code, MpcErrorKind::MalformedMessage.code());
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use curv::arithmetic::Converter;
+use curv::BigInt;
+use curv::elliptic::curves::{Point, Secp256k1};
+use tracing::{error, info};
+use common::errors::MpcErrorKind;
+use common::identity::{generate_challenge, generate_identity_secret, IdentityBindMsg, IdentityRecoverMsg1, IdentityRecoverMsg2, IdentityRecoverMsg3, IdentitySecretMsg, verify_identity_proof};
+use crate::storage::identity_storage::{FileIdentityStorage, IdentityBinding};
+use crate::storage::share_storage::FileShareStorage;
+use crate::websocket::connection_holder::{eph_state, SocketLocal, upsert_socket_local};
+use crate::websocket::inbound_dispatcher::InboundWithTx;
+
+pub async fn identity_bind(inbound: InboundWithTx, mut socket_local: SocketLocal, step: u8, msg_detail: &[u8]) {
+    match step {
+        1 => {
+            info!("identity_bind step1 start");
+            let identity_secret_msg = match FileIdentityStorage::load_binding(&socket_local.identity_id).await {
+                // a bound identity proves its identity-secret to bind another share
+                Ok(binding) => {
+                    let challenge = generate_challenge();
+                    socket_local.mpc_eph.insert("challenge".to_string(), challenge.to_bytes());
+                    let recover_msg1 = IdentityRecoverMsg1 {
+                        encrypted_identity_secret: binding.encrypted_identity_secret,
+                        challenge,
+                    };
+                    IdentitySecretMsg { identity_secret: vec![], bound: Some(recover_msg1) }
+                }
+                Err(_) => {
+                    let (identity_secret, identity_public) = generate_identity_secret();
+                    socket_local.mpc_eph.insert("identity_public".to_string(), serde_json::to_vec(&identity_public).unwrap());
+                    IdentitySecretMsg { identity_secret, bound: None }
+                }
+            };
+
+            // update socket_local
+            upsert_socket_local(socket_local).await;
+
+            let identity_secret_msg_bytes = inbound.encode_detail(&identity_secret_msg);
+            inbound.success_rsp(Some(identity_secret_msg_bytes)).await;
+            info!("identity_bind step1 success");
+        }
+        2 => {
+            info!("identity_bind step2 start");
+            let bind_msg_result = inbound.decode_detail::<IdentityBindMsg>(msg_detail);
+            if bind_msg_result.is_err() {
+                inbound.fail(MpcErrorKind::MalformedMessage, "fail to parse identity_bind_msg".to_string()).await;
+                return;
+            }
+            let bind_msg = bind_msg_result.unwrap();
+
+            // only shares of the identity itself can be bound
+            let share_id = bind_msg.encrypted_share.share_id.clone();
+            let owner = FileShareStorage::share_owner(&share_id).await;
+            if owner.as_ref() != Some(&socket_local.identity_id) {
+                inbound.fail(MpcErrorKind::Forbidden, "share is not owned by identity_id".to_string()).await;
+                return;
+            }
+
+            let binding = if let Some(challenge_bytes) = socket_local.mpc_eph.get("challenge") {
+                // add or replace a share of a bound identity
+                let binding_result = FileIdentityStorage::load_binding(&socket_local.identity_id).await;
+                if binding_result.is_err() {
+                    inbound.fail(MpcErrorKind::NotFound, "identity_id not bound".to_string()).await;
+                    return;
+                }
+                let mut binding = binding_result.unwrap();
+                let challenge = BigInt::from_bytes(challenge_bytes);
+                let verified = bind_msg.secret_proof.as_ref()
+                    .is_some_and(|proof| verify_identity_proof(proof, &binding.identity_public, &challenge));
+                if !verified {
+                    let err = "fail to verify the proof of identity-secret".to_string();
+                    error!("{}, identity_id={}", &err, &socket_local.identity_id);
+                    inbound.fail(MpcErrorKind::ProofVerificationFailed, err).await;
+                    return;
+                }
+                binding.encrypted_shares.insert(share_id, bind_msg.encrypted_share);
+                binding
+            } else {
+                let identity_public_result = eph_state::<Point<Secp256k1>>(&socket_local.mpc_eph, "identity_public");
+                if identity_public_result.is_err() {
+                    inbound.fail(MpcErrorKind::InvalidRequest, "identity_bind step1 not finished".to_string()).await;
+                    return;
+                }
+                // bound by another session since step1
+                if FileIdentityStorage::load_binding(&socket_local.identity_id).await.is_ok() {
+                    inbound.fail(MpcErrorKind::Forbidden, "identity_id already bound".to_string()).await;
+                    return;
+                }
+                IdentityBinding {
+                    identity_id: socket_local.identity_id.clone(),
+                    identity_public: identity_public_result.unwrap(),
+                    encrypted_identity_secret: bind_msg.encrypted_identity_secret,
+                    encrypted_shares: BTreeMap::from([(share_id, bind_msg.encrypted_share)]),
+                }
+            };
+            let save_result = FileIdentityStorage::save_binding(&binding).await;
+            if save_result.is_err() {
+                let err = format!("save identity binding fail: {}", save_result.unwrap_err());
+                error!("{}", &err);
+                inbound.fail(MpcErrorKind::Internal, err).await;
+                return;
+            }
+
+            inbound.success_rsp(None).await;
+            info!("identity_bind step2 success");
+        }
+        _ => {
+            inbound.fail(MpcErrorKind::Unsupported, "identity_bind max step=2".to_string()).await;
+        }
+    }
+}
+
+pub async fn identity_recover(inbound: InboundWithTx, mut socket_local: SocketLocal, step: u8, msg_detail: &[u8]) {
+    match step {
+        1 => {
+            info!("identity_recover step1 start");
+            let binding_result = FileIdentityStorage::load_binding(&socket_local.identity_id).await;
+            if binding_result.is_err() {
+                inbound.fail(MpcErrorKind::NotFound, "identity_id not bound".to_string()).await;
+                return;
+            }
+            let binding = binding_result.unwrap();
+
+            let challenge = generate_challenge();
+            let mpc_eph = &mut socket_local.mpc_eph;
+            mpc_eph.insert("challenge".to_string(), challenge.to_bytes());
+
+            // update socket_local
+            upsert_socket_local(socket_local).await;
+
+            let recover_msg1 = IdentityRecoverMsg1 {
+                encrypted_identity_secret: binding.encrypted_identity_secret,
+                challenge,
+            };
+            let recover_msg1_bytes = inbound.encode_detail(&recover_msg1);
+            inbound.success_rsp(Some(recover_msg1_bytes)).await;
+            info!("identity_recover step1 success");
+        }
+        2 => {
+            info!("identity_recover step2 start");
+            let recover_msg2_result = inbound.decode_detail::<IdentityRecoverMsg2>(msg_detail);
+            if recover_msg2_result.is_err() {
+                inbound.fail(MpcErrorKind::MalformedMessage, "fail to parse identity_recover_msg2".to_string()).await;
+                return;
+            }
+            let recover_msg2 = recover_msg2_result.unwrap();
+
+            let option_challenge = socket_local.mpc_eph.get("challenge");
+            if option_challenge.is_none() {
+                inbound.fail(MpcErrorKind::InvalidRequest, "identity_recover step1 not finished".to_string()).await;
+                return;
+            }
+            let challenge = BigInt::from_bytes(option_challenge.unwrap());
+
+            let binding_result = FileIdentityStorage::load_binding(&socket_local.identity_id).await;
+            if binding_result.is_err() {
+                inbound.fail(MpcErrorKind::NotFound, "identity_id not bound".to_string()).await;
+                return;
+            }
+            let binding = binding_result.unwrap();
+
+            let flag = verify_identity_proof(&recover_msg2.secret_proof, &binding.identity_public, &challenge);
+            if !flag {
+                let err = "fail to verify the proof of identity-secret".to_string();
+                error!("{}, identity_id={}", &err, &socket_local.identity_id);
+                inbound.fail(MpcErrorKind::ProofVerificationFailed, err).await;
+                return;
+            }
+
+            let recover_msg3 = IdentityRecoverMsg3 {
+                encrypted_shares: binding.encrypted_shares.into_values().collect(),
+            };
+            let recover_msg3_bytes = inbound.encode_detail(&recover_msg3);
+            inbound.success_rsp(Some(recover_msg3_bytes)).await;
+            info!("identity_recover step2 success");
+        }
+        _ => {
+            inbound.fail(MpcErrorKind::Unsupported, "identity_recover max step=2".to_string()).await;
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::time::Duration;
+use tracing::{error, info, warn};
+use common::errors::MpcErrorKind;
+use common::get_tsp;
+use common::socketmsg::NOTICE_ROTATION_REQUIRED;
+use common::socketmsg::types::RotateCommitMsg;
+use crate::audit::{AUDIT_ACTION_ROTATE_COMMIT, AUDIT_ACTION_ROTATE_ROLLBACK, audit_failure, audit_success, AuditEntry};
+use crate::config::AppConfig;
+use crate::storage::share_storage::{CommitError, FileShareStorage};
+use crate::websocket::inbound_dispatcher::InboundWithTx;
+use crate::websocket::notice_holder::publish_notice;
+
+pub async fn rotate_commit(inbound: InboundWithTx, scope: u8, old_share_id: &str, msg_detail: &[u8]) {
+    info!("rotate_commit start");
+    let identity_id = FileShareStorage::share_owner(old_share_id).await.unwrap_or_default();
+    let mut audit_entry = AuditEntry::new(AUDIT_ACTION_ROTATE_COMMIT, scope, &inbound.peer, &identity_id, old_share_id);
+    let commit_msg_result = inbound.decode_detail::<RotateCommitMsg>(msg_detail);
+    if commit_msg_result.is_err() {
+        let err = "fail to parse rotate_commit_msg".to_string();
+        inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+        audit_failure(audit_entry, MpcErrorKind::MalformedMessage, &err, None).await;
+        return;
+    }
+    let commit_msg = commit_msg_result.unwrap();
+    audit_entry.new_share_id = commit_msg.new_share_id.clone();
+
+    let commit_result = FileShareStorage::commit_rotation(old_share_id, &commit_msg.new_share_id).await;
+    if commit_result.is_err() {
+        let commit_error = commit_result.unwrap_err();
+        let kind = match &commit_error {
+            CommitError::NotFound(_) => MpcErrorKind::NotFound,
+            CommitError::Mismatch(_) => MpcErrorKind::InvalidRequest,
+            CommitError::Storage(_) => MpcErrorKind::Internal,
+        };
+        let err = format!("fail to commit rotation: {}", commit_error);
+        error!("{}", &err);
+        inbound.fail(kind, err.clone()).await;
+        audit_failure(audit_entry, kind, &err, None).await;
+        return;
+    }
+
+    // the commit is idempotent, party1 resends it if the audit fails
+    if audit_success(audit_entry).await.is_err() {
+        inbound.fail(MpcErrorKind::Internal, "fail to audit rotate_commit".to_string()).await;
+        return;
+    }
+    inbound.success_rsp(None).await;
+    info!("rotate_commit success, old_share_id={}, new_share_id={}", old_share_id, commit_msg.new_share_id);
+}
+
+pub async fn share_status(inbound: InboundWithTx, share_id: &str) {
+    let status = FileShareStorage::share_status(share_id).await;
+    let status_bytes = inbound.encode_detail(&status);
+    inbound.success_rsp(Some(status_bytes)).await;
+}
+
+// roll back the pending share if party1 does not commit it in time
+pub fn schedule_rotation_rollback(scope: u8, old_share_id: String, new_share_id: String) {
+    let app_config = AppConfig::get_app_config();
+    let timeout = Duration::from_secs(app_config.rotate_commit_timeout as u64);
+    spawn_rollback(scope, old_share_id, new_share_id, timeout);
+}
+
+// the timers of schedule_rotation_rollback die with the process, at startup the rotations still pending are
+// rolled back once their commit timeout has passed, right away if it already has. commits a crash interrupted are finished
+pub async fn sweep_rotations() {
+    if let Err(e) = FileShareStorage::finish_commits().await {
+        error!("fail to finish committed rotations: {}", e);
+    }
+    let app_config = AppConfig::get_app_config();
+    let timeout_ms = app_config.rotate_commit_timeout as u128 * 1000;
+    let now = get_tsp();
+    for record in FileShareStorage::uncommitted_rotations().await {
+        let remaining_ms = (record.timestamp + timeout_ms).saturating_sub(now);
+        let scope = FileShareStorage::load_share(record.old_share_id.clone()).await
+            .map(|share| share.scope)
+            .unwrap_or(0);
+        info!("rotation of share {} is not committed, roll back in {}ms", record.old_share_id, remaining_ms);
+        spawn_rollback(scope, record.old_share_id, record.new_share_id, Duration::from_millis(remaining_ms as u64));
+    }
+}
+
+fn spawn_rollback(scope: u8, old_share_id: String, new_share_id: String, delay: Duration) {
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        let option_identity_id = FileShareStorage::share_owner(&old_share_id).await;
+        // party2 itself is the peer of a rollback
+        let mut audit_entry = AuditEntry::new(AUDIT_ACTION_ROTATE_ROLLBACK, scope, "", option_identity_id.as_deref().unwrap_or(""), &old_share_id);
+        audit_entry.new_share_id = new_share_id.clone();
+        match FileShareStorage::rollback_rotation(&old_share_id, &new_share_id).await {
+            Ok(true) => {
+                warn!("rotation not committed in time, rolled back: old_share_id={}, new_share_id={}", old_share_id, new_share_id);
+                audit_success(audit_entry).await.unwrap_or(());
+                if let Some(identity_id) = option_identity_id {
+                    publish_notice(&identity_id, NOTICE_ROTATION_REQUIRED, &old_share_id,
+                                   "rotation was not committed in time and has been rolled back, rotate the share again").await;
+                }
+            }
+            Ok(false) => {}
+            Err(e) => {
+                let err = format!("fail to roll back rotation of share {}: {}", old_share_id, e);
+                error!("{}", &err);
+                audit_failure(audit_entry, MpcErrorKind::Internal, &err, None).await;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use common::get_uuid;
+    use common::socketmsg::types::{MPC_SCOPE_SECP256K1ECDSA, SAVED_SHARE_SCHEMA_VERSION, SavedShare, SHARE_STATUS_ACTIVE, SHARE_STATUS_NOT_FOUND};
+    use crate::storage::share_storage::{CommitError, FileShareStorage};
+    use crate::websocket::handler::mpc22_rotation::sweep_rotations;
+
+    fn saved_share(share_id: &str) -> SavedShare {
+        SavedShare {
+            schema_version: SAVED_SHARE_SCHEMA_VERSION,
+            identity_id: "wangcy".to_string(),
+            share_id: share_id.to_string(),
+            scope: MPC_SCOPE_SECP256K1ECDSA,
+            party: 2,
+            epoch: 0,
+            created_at: 0,
+            rotated_at: 0,
+            uncompressed_pub: vec![],
+            share_detail: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sweep_stale_rotation() {
+        let old_share_id = get_uuid();
+        let new_share_id = get_uuid();
+        FileShareStorage::save_share(saved_share(&old_share_id)).await.unwrap();
+        FileShareStorage::save_pending_share(&old_share_id, saved_share(&new_share_id)).await.unwrap();
+        // the record of a rotation started long before a restart
+        let rotation_path = format!("rotation_{}.rotation", old_share_id);
+        let record = std::fs::read_to_string(&rotation_path).unwrap();
+        let mut record: serde_json::Value = serde_json::from_str(&record).unwrap();
+        record["timestamp"] = serde_json::json!(0);
+        std::fs::write(&rotation_path, record.to_string()).unwrap();
+
+        sweep_rotations().await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let status = FileShareStorage::share_status(&old_share_id).await;
+        assert_eq!(status.status, SHARE_STATUS_ACTIVE);
+        assert!(status.pending_share_id.is_empty());
+        assert_eq!(FileShareStorage::share_status(&new_share_id).await.status, SHARE_STATUS_NOT_FOUND);
+        assert!(FileShareStorage::commit_rotation(&old_share_id, &new_share_id).await.is_err());
+    }
+}
@@ -1,70 +1,112 @@
+use std::time::Instant;
 use curv::arithmetic::Converter;
 use curv::BigInt;
 use curv::elliptic::curves::Secp256k1;
 use tracing::{error, info};
+use common::errors::MpcErrorKind;
 use common::dlog::CurveKeyPair;
-use common::get_uuid;
-use common::socketmsg::{RSP_CODE_BAD_REQUEST, RSP_CODE_FORBIDDEN, RSP_CODE_INTERNAL_SERVER_ERROR};
-use common::socketmsg::types::{MPC_SCOPE_SECP256K1ECDSA, SavedShare};
-use crate::websocket::connection_holder::{SocketLocal, upsert_socket_local};
+use common::{get_tsp, get_uuid};
+use common::socketmsg::types::{MPC_SCOPE_SECP256K1ECDSA, SAVED_SHARE_SCHEMA_VERSION, SavedShare};
+use crate::websocket::connection_holder::{eph_state, SocketLocal, upsert_socket_local};
 use crate::websocket::inbound_dispatcher::InboundWithTx;
+use crate::websocket::handler::mpc22_handler::verified_sign_payload;
+use crate::websocket::handler::mpc22_rotation::schedule_rotation_rollback;
 use twoparty_secp256k1::{export, keygen, rotate, sign};
 use twoparty_secp256k1::export::party1::Party1ExportMsg2;
 use twoparty_secp256k1::rotate::party1::{Party1RotateMsg1, Party1RotateMsg2};
 use twoparty_secp256k1::sign::party1::{Party1SignMsg1, Party1SignMsg2};
+use crate::audit::{AUDIT_ACTION_EXPORT, AUDIT_ACTION_KEYGEN, AUDIT_ACTION_ROTATE, AUDIT_ACTION_SIGN, audit_failure, audit_success, AuditEntry};
+use crate::compute::run_blocking;
+use crate::metrics::{observe_proof_verification, step2_compute_timer};
+use crate::policy::{evaluate_sign_policy, record_export, SignContext};
 use crate::storage::share_storage::{FileShareStorage};
 
 pub async fn secp256k1_keygen(inbound: InboundWithTx, mut socket_local: SocketLocal, step: u8, msg_detail: &[u8]) {
     match step {
         1 => {
             info!("secp256k1_keygen step1 start");
-            let party1_keygen_msg1_result = serde_json::from_slice::<keygen::party1::Party1KeyGenMsg1>(msg_detail);
+            let party1_keygen_msg1_result = inbound.decode_detail::<keygen::party1::Party1KeyGenMsg1>(msg_detail);
             if party1_keygen_msg1_result.is_err() {
-                inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "fail to parse party1_keygen_msg1".to_string()).await;
+                let err = "fail to parse party1_keygen_msg1".to_string();
+                inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+                let audit_entry = AuditEntry::new(AUDIT_ACTION_KEYGEN, MPC_SCOPE_SECP256K1ECDSA, &inbound.peer, &socket_local.identity_id, &socket_local.share_id);
+                audit_failure(audit_entry, MpcErrorKind::MalformedMessage, &err, None).await;
                 return;
             }
 
             let (party2_keygen_msg1, party2_keypair) = keygen::party2::party2_step1();
             let mpc_eph = &mut socket_local.mpc_eph;
-            mpc_eph.insert("party1_keygen_msg1".to_string(), msg_detail.to_vec());
+            // eph keeps json whatever the msg version
+            mpc_eph.insert("party1_keygen_msg1".to_string(), serde_json::to_vec(party1_keygen_msg1_result.as_ref().unwrap()).unwrap());
             mpc_eph.insert("party2_keypair".to_string(), serde_json::to_vec(&party2_keypair).unwrap());
 
             // update socket_local
             upsert_socket_local(socket_local).await;
 
-            let party2_keygen_msg1_bytes = serde_json::to_vec(&party2_keygen_msg1).unwrap();
+            let party2_keygen_msg1_bytes = inbound.encode_detail(&party2_keygen_msg1);
             inbound.success_rsp(Some(party2_keygen_msg1_bytes)).await;
             info!("secp256k1_keygen step1 success");
         }
         2 => {
             info!("secp256k1_keygen step2 start");
-            let party1_keygen_msg2 = serde_json::from_slice::<keygen::party1::Party1KeygenMsg2>(msg_detail);
+            let mut audit_entry = AuditEntry::new(AUDIT_ACTION_KEYGEN, MPC_SCOPE_SECP256K1ECDSA, &inbound.peer, &socket_local.identity_id, &socket_local.share_id);
+            let party1_keygen_msg2 = inbound.decode_detail::<keygen::party1::Party1KeygenMsg2>(msg_detail);
             if party1_keygen_msg2.is_err() {
-                inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "fail to parse party1_keygen_msg2".to_string()).await;
+                let err = "fail to parse party1_keygen_msg2".to_string();
+                inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::MalformedMessage, &err, None).await;
                 return;
             }
             let mpc_eph = &mut socket_local.mpc_eph;
-            let party1_keygen_msg1 = serde_json::from_slice::<keygen::party1::Party1KeyGenMsg1>(mpc_eph.get("party1_keygen_msg1").unwrap()).unwrap();
-            let party2_keypair = serde_json::from_slice::<CurveKeyPair<Secp256k1>>(mpc_eph.get("party2_keypair").unwrap()).unwrap();
-            let result2 = keygen::party2::party2_step2(
-                party1_keygen_msg2.unwrap(),
-                party1_keygen_msg1,
-                party2_keypair,
-            );
+            let party1_keygen_msg1 = eph_state::<keygen::party1::Party1KeyGenMsg1>(mpc_eph, "party1_keygen_msg1");
+            let party2_keypair = eph_state::<CurveKeyPair<Secp256k1>>(mpc_eph, "party2_keypair");
+            if party1_keygen_msg1.is_err() || party2_keypair.is_err() {
+                let err = "secp256k1_keygen step1 not finished".to_string();
+                inbound.fail(MpcErrorKind::InvalidRequest, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::InvalidRequest, &err, None).await;
+                return;
+            }
+            let party1_keygen_msg1 = party1_keygen_msg1.unwrap();
+            let party2_keypair = party2_keypair.unwrap();
+            let compute_result = run_blocking(move || {
+                let compute_timer = step2_compute_timer(MPC_SCOPE_SECP256K1ECDSA, AUDIT_ACTION_KEYGEN);
+                let verify_start = Instant::now();
+                let result2 = keygen::party2::party2_step2(
+                    party1_keygen_msg2.unwrap(),
+                    party1_keygen_msg1,
+                    party2_keypair,
+                );
+                observe_proof_verification(MPC_SCOPE_SECP256K1ECDSA, AUDIT_ACTION_KEYGEN, verify_start, result2.is_ok());
+                compute_timer.observe_duration();
+                result2
+            }).await;
+            if compute_result.is_err() {
+                inbound.fail_compute(compute_result.err().unwrap(), audit_entry).await;
+                return;
+            }
+            let result2 = compute_result.unwrap();
             if result2.is_err() {
-                let err = result2.err().unwrap().to_string();
+                let two_party_error = result2.err().unwrap();
+                let err = two_party_error.to_string();
                 error!("{}", err);
-                inbound.fail_rsp(RSP_CODE_FORBIDDEN, err).await;
+                inbound.fail(MpcErrorKind::ProofVerificationFailed, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::ProofVerificationFailed, &err, Some(two_party_error)).await;
                 return;
             }
             let share_id = get_uuid();
+            audit_entry.share_id = share_id.clone();
             let share2 = result2.unwrap();
 
+            let now = get_tsp();
             let saved_share = SavedShare {
+                schema_version: SAVED_SHARE_SCHEMA_VERSION,
                 identity_id: socket_local.identity_id,
                 share_id: share_id.clone(),
                 scope: MPC_SCOPE_SECP256K1ECDSA,
                 party: 2,
+                epoch: 0,
+                created_at: now,
+                rotated_at: now,
                 uncompressed_pub: share2.public.pub_key.to_bytes(false).to_vec(),
                 share_detail: serde_json::to_vec(&share2).unwrap(),
             };
@@ -73,16 +115,24 @@ pub async fn secp256k1_keygen(inbound: InboundWithTx, mut socket_local: SocketLo
             if save_result.is_err() {
                 let err = format!("save share fail: {}", save_result.unwrap_err());
                 error!("{}",&err);
-                inbound.fail_rsp(RSP_CODE_INTERNAL_SERVER_ERROR, err).await;
+                inbound.fail(MpcErrorKind::Internal, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::Internal, &err, None).await;
+                return;
+            }
+            // audited after the save, so a failed save is the only entry. an unaudited share is deleted, party1 never gets it
+            if audit_success(audit_entry).await.is_err() {
+                FileShareStorage::delete_share(&share_id).await
+                    .unwrap_or_else(|e| error!("fail to delete unaudited share {}: {}", share_id, e));
+                inbound.fail(MpcErrorKind::Internal, "fail to audit keygen".to_string()).await;
                 return;
             }
 
-            let share_id_bytes = serde_json::to_vec(&share_id).unwrap();
+            let share_id_bytes = inbound.encode_detail(&share_id);
             inbound.success_rsp(Some(share_id_bytes)).await;
             info!("secp256k1_keygen step2 success");
         }
         _ => {
-            inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "secp256k1_keygen max step=2".to_string()).await;
+            inbound.fail(MpcErrorKind::Unsupported, "secp256k1_keygen max step=2".to_string()).await;
         }
     }
 }
@@ -91,58 +141,129 @@ pub async fn secp256k1_sign(inbound: InboundWithTx, mut socket_local: SocketLoca
     match step {
         1 => {
             info!("secp256k1_sign step1 start");
-            let party1_sign_msg1_result = serde_json::from_slice::<Party1SignMsg1>(msg_detail);
+            let party1_sign_msg1_result = inbound.decode_detail::<Party1SignMsg1>(msg_detail);
             if party1_sign_msg1_result.is_err() {
-                inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "fail to parse party1_sign_msg1".to_string()).await;
+                let err = "fail to parse party1_sign_msg1".to_string();
+                inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+                let audit_entry = AuditEntry::new(AUDIT_ACTION_SIGN, MPC_SCOPE_SECP256K1ECDSA, &inbound.peer, &socket_local.identity_id, &socket_local.share_id);
+                audit_failure(audit_entry, MpcErrorKind::MalformedMessage, &err, None).await;
                 return;
             }
 
             let (party2_sign_msg1, party2_eph_keypair) = sign::party2::party2_step1();
             let mpc_eph = &mut socket_local.mpc_eph;
-            mpc_eph.insert("party1_sign_msg1".to_string(), msg_detail.to_vec());
+            mpc_eph.insert("party1_sign_msg1".to_string(), serde_json::to_vec(party1_sign_msg1_result.as_ref().unwrap()).unwrap());
             mpc_eph.insert("party2_eph_keypair".to_string(), serde_json::to_vec(&party2_eph_keypair).unwrap());
 
             // update socket_local
             upsert_socket_local(socket_local).await;
 
-            let party2_sign_msg1_bytes = serde_json::to_vec(&party2_sign_msg1).unwrap();
+            let party2_sign_msg1_bytes = inbound.encode_detail(&party2_sign_msg1);
             inbound.success_rsp(Some(party2_sign_msg1_bytes)).await;
             info!("secp256k1_sign step1 success");
         }
         2 => {
             info!("secp256k1_sign step2 start");
-            let inner_share = socket_local.secp256k1_share.unwrap();
+            let mut audit_entry = AuditEntry::new(AUDIT_ACTION_SIGN, MPC_SCOPE_SECP256K1ECDSA, &inbound.peer, &socket_local.identity_id, &socket_local.share_id);
+            let option_share = socket_local.secp256k1_share.clone();
+            if option_share.is_none() {
+                let err = "no secp256k1 share in the session".to_string();
+                inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::MalformedMessage, &err, None).await;
+                return;
+            }
+            let inner_share = option_share.unwrap();
 
-            let party1_sign_msg2_result = serde_json::from_slice::<Party1SignMsg2>(msg_detail);
+            let party1_sign_msg2_result = inbound.decode_detail::<Party1SignMsg2>(msg_detail);
             if party1_sign_msg2_result.is_err() {
-                inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "fail to parse party1_sign_msg2".to_string()).await;
+                let err = "fail to parse party1_sign_msg2".to_string();
+                inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::MalformedMessage, &err, None).await;
                 return;
             }
             let party1_sign_msg2 = party1_sign_msg2_result.unwrap();
+            audit_entry.digest = hex::encode(&party1_sign_msg2.message_digest);
 
             let mpc_eph = &mut socket_local.mpc_eph;
-            let party1_sign_msg1 = serde_json::from_slice::<Party1SignMsg1>(mpc_eph.get("party1_sign_msg1").unwrap()).unwrap();
-            let party2_eph_keypair = serde_json::from_slice::<CurveKeyPair<Secp256k1>>(mpc_eph.get("party2_eph_keypair").unwrap()).unwrap();
-
-            let party2_result2 = sign::party2::party2_step2(
-                party1_sign_msg2,
-                party1_sign_msg1,
-                &inner_share,
-                party2_eph_keypair);
+            let party1_sign_msg1 = eph_state::<Party1SignMsg1>(mpc_eph, "party1_sign_msg1");
+            let party2_eph_keypair = eph_state::<CurveKeyPair<Secp256k1>>(mpc_eph, "party2_eph_keypair");
+            if party1_sign_msg1.is_err() || party2_eph_keypair.is_err() {
+                let err = "secp256k1_sign step1 not finished".to_string();
+                inbound.fail(MpcErrorKind::InvalidRequest, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::InvalidRequest, &err, None).await;
+                return;
+            }
+            let party1_sign_msg1 = party1_sign_msg1.unwrap();
+            let party2_eph_keypair = party2_eph_keypair.unwrap();
+
+            let payload_result = verified_sign_payload(mpc_eph, &socket_local.share_id, &party1_sign_msg2.message_digest);
+            if payload_result.is_err() {
+                let err = payload_result.unwrap_err();
+                error!("{}", &err);
+                inbound.fail(MpcErrorKind::InvalidRequest, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::InvalidRequest, &err, None).await;
+                return;
+            }
+            let decoded_payload = payload_result.unwrap();
+            audit_entry.payload = decoded_payload.clone();
+
+            let sign_ctx = SignContext {
+                identity_id: &socket_local.identity_id,
+                share_id: &socket_local.share_id,
+                message_digest: &party1_sign_msg2.message_digest,
+                payload: decoded_payload.as_ref(),
+                share_epoch: socket_local.share_epoch,
+                share_rotated_at: socket_local.share_rotated_at,
+            };
+            let policy_result = evaluate_sign_policy(&sign_ctx);
+            if policy_result.is_err() {
+                let err = format!("sign denied by policy: {}", policy_result.err().unwrap());
+                error!("{}", &err);
+                inbound.fail(MpcErrorKind::PolicyDenied, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::PolicyDenied, &err, None).await;
+                return;
+            }
+            let sign_permit = policy_result.unwrap();
+
+            let compute_result = run_blocking(move || {
+                let compute_timer = step2_compute_timer(MPC_SCOPE_SECP256K1ECDSA, AUDIT_ACTION_SIGN);
+                let verify_start = Instant::now();
+                let party2_result2 = sign::party2::party2_step2(
+                    party1_sign_msg2,
+                    party1_sign_msg1,
+                    &inner_share,
+                    party2_eph_keypair);
+                observe_proof_verification(MPC_SCOPE_SECP256K1ECDSA, AUDIT_ACTION_SIGN, verify_start, party2_result2.is_ok());
+                compute_timer.observe_duration();
+                party2_result2
+            }).await;
+            if compute_result.is_err() {
+                inbound.fail_compute(compute_result.err().unwrap(), audit_entry).await;
+                return;
+            }
+            let party2_result2 = compute_result.unwrap();
             if party2_result2.is_err() {
-                let err = party2_result2.err().unwrap().to_string();
+                let two_party_error = party2_result2.err().unwrap();
+                let err = two_party_error.to_string();
                 error!("{}", err);
-                inbound.fail_rsp(RSP_CODE_FORBIDDEN, err).await;
+                inbound.fail(MpcErrorKind::ProofVerificationFailed, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::ProofVerificationFailed, &err, Some(two_party_error)).await;
                 return;
             }
             let party2_sign_msg2 = party2_result2.unwrap();
 
-            let party2_sign_msg2_bytes = serde_json::to_vec(&party2_sign_msg2).unwrap();
+            let party2_sign_msg2_bytes = inbound.encode_detail(&party2_sign_msg2);
+            if audit_success(audit_entry).await.is_err() {
+                inbound.fail(MpcErrorKind::Internal, "fail to audit sign".to_string()).await;
+                return;
+            }
+            // party1 gets the signature only now, a sign failed by the audit is not counted
+            sign_permit.succeed();
             inbound.success_rsp(Some(party2_sign_msg2_bytes)).await;
             info!("secp256k1_sign step2 success");
         }
         _ => {
-            inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "secp256k1_sign max step=2".to_string()).await;
+            inbound.fail(MpcErrorKind::Unsupported, "secp256k1_sign max step=2".to_string()).await;
         }
     }
 }
@@ -152,76 +273,127 @@ pub async fn secp256k1_rotate(inbound: InboundWithTx, mut socket_local: SocketLo
     match step {
         1 => {
             info!("secp256k1_rotate step1 start");
-            let party1_rotate_msg1_result = serde_json::from_slice::<Party1RotateMsg1>(msg_detail);
+            let party1_rotate_msg1_result = inbound.decode_detail::<Party1RotateMsg1>(msg_detail);
             if party1_rotate_msg1_result.is_err() {
-                inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "fail to parse party1_rotate_msg1".to_string()).await;
+                let err = "fail to parse party1_rotate_msg1".to_string();
+                inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+                let audit_entry = AuditEntry::new(AUDIT_ACTION_ROTATE, MPC_SCOPE_SECP256K1ECDSA, &inbound.peer, &socket_local.identity_id, &socket_local.share_id);
+                audit_failure(audit_entry, MpcErrorKind::MalformedMessage, &err, None).await;
                 return;
             }
 
             let (party2_rotate_msg1, party2_seed_keypair) = rotate::party2::party2_step1();
             let mpc_eph = &mut socket_local.mpc_eph;
-            mpc_eph.insert("party1_rotate_msg1".to_string(), msg_detail.to_vec());
+            mpc_eph.insert("party1_rotate_msg1".to_string(), serde_json::to_vec(party1_rotate_msg1_result.as_ref().unwrap()).unwrap());
             mpc_eph.insert("party2_seed_keypair".to_string(), serde_json::to_vec(&party2_seed_keypair).unwrap());
 
             // update socket_local
             upsert_socket_local(socket_local).await;
 
-            let party2_rotate_msg1_bytes = serde_json::to_vec(&party2_rotate_msg1).unwrap();
+            let party2_rotate_msg1_bytes = inbound.encode_detail(&party2_rotate_msg1);
             inbound.success_rsp(Some(party2_rotate_msg1_bytes)).await;
             info!("secp256k1_rotate step1 success");
         }
         2 => {
             info!("secp256k1_rotate step2 start");
-            let inner_share = socket_local.secp256k1_share.unwrap();
+            let mut audit_entry = AuditEntry::new(AUDIT_ACTION_ROTATE, MPC_SCOPE_SECP256K1ECDSA, &inbound.peer, &socket_local.identity_id, &socket_local.share_id);
+            let option_share = socket_local.secp256k1_share.clone();
+            if option_share.is_none() {
+                let err = "no secp256k1 share in the session".to_string();
+                inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::MalformedMessage, &err, None).await;
+                return;
+            }
+            let inner_share = option_share.unwrap();
 
-            let party1_rotate_msg2_result = serde_json::from_slice::<Party1RotateMsg2>(msg_detail);
+            let party1_rotate_msg2_result = inbound.decode_detail::<Party1RotateMsg2>(msg_detail);
             if party1_rotate_msg2_result.is_err() {
-                inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "fail to parse party1_rotate_msg2".to_string()).await;
+                let err = "fail to parse party1_rotate_msg2".to_string();
+                inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::MalformedMessage, &err, None).await;
                 return;
             }
             let party1_rotate_msg2 = party1_rotate_msg2_result.unwrap();
 
             let mpc_eph = &mut socket_local.mpc_eph;
-            let party1_rotate_msg1 = serde_json::from_slice::<Party1RotateMsg1>(mpc_eph.get("party1_rotate_msg1").unwrap()).unwrap();
-            let party2_seed_keypair = serde_json::from_slice::<CurveKeyPair<Secp256k1>>(mpc_eph.get("party2_seed_keypair").unwrap()).unwrap();
-
-            let party2_result2 = rotate::party2::party2_step2(
-                party1_rotate_msg2,
-                party1_rotate_msg1,
-                party2_seed_keypair,
-                &inner_share,
-            );
+            let party1_rotate_msg1 = eph_state::<Party1RotateMsg1>(mpc_eph, "party1_rotate_msg1");
+            let party2_seed_keypair = eph_state::<CurveKeyPair<Secp256k1>>(mpc_eph, "party2_seed_keypair");
+            if party1_rotate_msg1.is_err() || party2_seed_keypair.is_err() {
+                let err = "secp256k1_rotate step1 not finished".to_string();
+                inbound.fail(MpcErrorKind::InvalidRequest, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::InvalidRequest, &err, None).await;
+                return;
+            }
+            let party1_rotate_msg1 = party1_rotate_msg1.unwrap();
+            let party2_seed_keypair = party2_seed_keypair.unwrap();
+
+            let compute_result = run_blocking(move || {
+                let compute_timer = step2_compute_timer(MPC_SCOPE_SECP256K1ECDSA, AUDIT_ACTION_ROTATE);
+                let verify_start = Instant::now();
+                let party2_result2 = rotate::party2::party2_step2(
+                    party1_rotate_msg2,
+                    party1_rotate_msg1,
+                    party2_seed_keypair,
+                    &inner_share,
+                );
+                observe_proof_verification(MPC_SCOPE_SECP256K1ECDSA, AUDIT_ACTION_ROTATE, verify_start, party2_result2.is_ok());
+                compute_timer.observe_duration();
+                party2_result2
+            }).await;
+            if compute_result.is_err() {
+                inbound.fail_compute(compute_result.err().unwrap(), audit_entry).await;
+                return;
+            }
+            let party2_result2 = compute_result.unwrap();
             if party2_result2.is_err() {
-                let err = party2_result2.err().unwrap().to_string();
+                let two_party_error = party2_result2.err().unwrap();
+                let err = two_party_error.to_string();
                 error!("{}", err);
-                inbound.fail_rsp(RSP_CODE_FORBIDDEN, err).await;
+                inbound.fail(MpcErrorKind::ProofVerificationFailed, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::ProofVerificationFailed, &err, Some(two_party_error)).await;
                 return;
             }
             let (party2_rotate_msg2, share22) = party2_result2.unwrap();
             let new_share_id = &party2_rotate_msg2.share_id;
+            audit_entry.new_share_id = new_share_id.clone();
+            let now = get_tsp();
             let new_saved_share = SavedShare {
+                schema_version: SAVED_SHARE_SCHEMA_VERSION,
                 identity_id: socket_local.identity_id,
                 share_id: new_share_id.to_string(),
                 scope: MPC_SCOPE_SECP256K1ECDSA,
                 party: 2,
+                epoch: socket_local.share_epoch + 1,
+                created_at: socket_local.share_created_at,
+                rotated_at: now,
                 uncompressed_pub: share22.public.pub_key.to_bytes(false).to_vec(),
                 share_detail: serde_json::to_vec(&share22).unwrap(),
             };
-            // save share22
-            let save_result = FileShareStorage::save_share(new_saved_share).await;
+            // save share22 as pending, it becomes active after party1 commits
+            let save_result = FileShareStorage::save_pending_share(&socket_local.share_id, new_saved_share).await;
             if save_result.is_err() {
                 let err = format!("save share fail: {}", save_result.unwrap_err());
                 error!("{}",&err);
-                inbound.fail_rsp(RSP_CODE_INTERNAL_SERVER_ERROR, err).await;
+                inbound.fail(MpcErrorKind::Internal, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::Internal, &err, None).await;
+                return;
+            }
+            // audited after the save as keygen, an unaudited pending share is rolled back
+            if audit_success(audit_entry).await.is_err() {
+                FileShareStorage::rollback_rotation(&socket_local.share_id, new_share_id).await
+                    .unwrap_or_else(|e| { error!("fail to roll back unaudited rotation {}: {}", new_share_id, e); false });
+                inbound.fail(MpcErrorKind::Internal, "fail to audit rotate".to_string()).await;
                 return;
             }
 
-            let party2_rotate_msg2_bytes = serde_json::to_vec(&party2_rotate_msg2).unwrap();
+            schedule_rotation_rollback(MPC_SCOPE_SECP256K1ECDSA, socket_local.share_id.clone(), new_share_id.to_string());
+
+            let party2_rotate_msg2_bytes = inbound.encode_detail(&party2_rotate_msg2);
             inbound.success_rsp(Some(party2_rotate_msg2_bytes)).await;
             info!("secp256k1_rotate step2 success");
         }
         _ => {
-            inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "secp256k1_rotate max step=2".to_string()).await;
+            inbound.fail(MpcErrorKind::Unsupported, "secp256k1_rotate max step=2".to_string()).await;
         }
     }
 }
@@ -240,41 +412,76 @@ pub async fn secp256k1_export(inbound: InboundWithTx, mut socket_local: SocketLo
             // update socket_local
             upsert_socket_local(socket_local).await;
 
-            let party2_export_msg1_bytes = serde_json::to_vec(&party2_export_msg1).unwrap();
+            let party2_export_msg1_bytes = inbound.encode_detail(&party2_export_msg1);
             inbound.success_rsp(Some(party2_export_msg1_bytes)).await;
             info!("secp256k1_export step1 success");
         }
         2 => {
             info!("secp256k1_export step2 start");
-            let inner_share = socket_local.secp256k1_share.unwrap();
+            let audit_entry = AuditEntry::new(AUDIT_ACTION_EXPORT, MPC_SCOPE_SECP256K1ECDSA, &inbound.peer, &socket_local.identity_id, &socket_local.share_id);
+            let option_share = socket_local.secp256k1_share.clone();
+            if option_share.is_none() {
+                let err = "no secp256k1 share in the session".to_string();
+                inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::MalformedMessage, &err, None).await;
+                return;
+            }
+            let inner_share = option_share.unwrap();
 
-            let party1_export_msg2_result = serde_json::from_slice::<Party1ExportMsg2>(msg_detail);
+            let party1_export_msg2_result = inbound.decode_detail::<Party1ExportMsg2>(msg_detail);
             if party1_export_msg2_result.is_err() {
-                inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "fail to parse party1_export_msg2".to_string()).await;
+                let err = "fail to parse party1_export_msg2".to_string();
+                inbound.fail(MpcErrorKind::MalformedMessage, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::MalformedMessage, &err, None).await;
                 return;
             }
             let party1_export_msg2 = party1_export_msg2_result.unwrap();
 
             let mpc_eph = &mut socket_local.mpc_eph;
-            let challenge = BigInt::from_bytes(mpc_eph.get("challenge").unwrap());
-
-            let party2_result2 = export::party2::party2_step2(
-                party1_export_msg2, &challenge, &inner_share);
+            let option_challenge = mpc_eph.get("challenge");
+            if option_challenge.is_none() {
+                let err = "secp256k1_export step1 not finished".to_string();
+                inbound.fail(MpcErrorKind::InvalidRequest, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::InvalidRequest, &err, None).await;
+                return;
+            }
+            let challenge = BigInt::from_bytes(option_challenge.unwrap());
+
+            let compute_result = run_blocking(move || {
+                let compute_timer = step2_compute_timer(MPC_SCOPE_SECP256K1ECDSA, AUDIT_ACTION_EXPORT);
+                let verify_start = Instant::now();
+                let party2_result2 = export::party2::party2_step2(
+                    party1_export_msg2, &challenge, &inner_share);
+                observe_proof_verification(MPC_SCOPE_SECP256K1ECDSA, AUDIT_ACTION_EXPORT, verify_start, party2_result2.is_ok());
+                compute_timer.observe_duration();
+                party2_result2
+            }).await;
+            if compute_result.is_err() {
+                inbound.fail_compute(compute_result.err().unwrap(), audit_entry).await;
+                return;
+            }
+            let party2_result2 = compute_result.unwrap();
             if party2_result2.is_err() {
-                let err = party2_result2.err().unwrap().to_string();
+                let two_party_error = party2_result2.err().unwrap();
+                let err = two_party_error.to_string();
                 error!("{}", err);
-                inbound.fail_rsp(RSP_CODE_FORBIDDEN, err).await;
+                inbound.fail(MpcErrorKind::ProofVerificationFailed, err.clone()).await;
+                audit_failure(audit_entry, MpcErrorKind::ProofVerificationFailed, &err, Some(two_party_error)).await;
                 return;
             }
             let party2_export_msg2 = party2_result2.unwrap();
 
-            let party2_export_msg2_bytes = serde_json::to_vec(&party2_export_msg2).unwrap();
+            let party2_export_msg2_bytes = inbound.encode_detail(&party2_export_msg2);
+            if audit_success(audit_entry).await.is_err() {
+                inbound.fail(MpcErrorKind::Internal, "fail to audit export".to_string()).await;
+                return;
+            }
             inbound.success_rsp(Some(party2_export_msg2_bytes)).await;
-            //  TODO record export
+            record_export(&socket_local.identity_id, &socket_local.share_id);
             info!("secp256k1_export step2 success");
         }
         _ => {
-            inbound.fail_rsp(RSP_CODE_BAD_REQUEST, "secp256k1_export max step=2".to_string()).await;
+            inbound.fail(MpcErrorKind::Unsupported, "secp256k1_export max step=2".to_string()).await;
         }
     }
 }
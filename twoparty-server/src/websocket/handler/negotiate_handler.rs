@@ -0,0 +1,34 @@
+use tracing::info;
+use common::errors::MpcErrorKind;
+use common::socketmsg::{highest_common_version, MSG_VERSIONS, NegotiatedMsg, NegotiateMsg, ScopeVersion};
+use crate::websocket::handler::mpc22_handler::protocol_versions;
+use crate::websocket::inbound_dispatcher::InboundWithTx;
+
+// pick the highest version both parties run, party2 keeps no state of it since every msg carries its version
+pub async fn negotiate_handler(inbound: InboundWithTx) {
+    let parse_result = inbound.decode_detail::<NegotiateMsg>(&inbound.msg_wrapper.body);
+    if parse_result.is_err() {
+        inbound.fail(MpcErrorKind::MalformedMessage, "fail to parse negotiate_msg".to_string()).await;
+        return;
+    }
+    let negotiate_msg = parse_result.unwrap();
+
+    let option_msg_version = highest_common_version(&MSG_VERSIONS, &negotiate_msg.msg_versions);
+    if option_msg_version.is_none() {
+        let err = format!("no common msg version, server runs {:?}", MSG_VERSIONS);
+        inbound.fail(MpcErrorKind::VersionMismatch, err).await;
+        return;
+    }
+    let scopes = negotiate_msg.scopes.iter()
+        .filter_map(|scope_versions| {
+            let version = highest_common_version(&protocol_versions(scope_versions.scope), &scope_versions.versions)?;
+            Some(ScopeVersion { scope: scope_versions.scope, version })
+        })
+        .collect::<Vec<ScopeVersion>>();
+    let negotiated_msg = NegotiatedMsg {
+        msg_version: option_msg_version.unwrap(),
+        scopes,
+    };
+    info!("negotiated versions: {:?}, socket_id={}", negotiated_msg, inbound.socket_id);
+    inbound.success_rsp(Some(inbound.encode_detail(&negotiated_msg))).await;
+}
@@ -0,0 +1,34 @@
+use tracing::{error, info};
+use common::errors::MpcErrorKind;
+use common::socketmsg::SubscribeNoticeMsg;
+use crate::websocket::inbound_dispatcher::InboundWithTx;
+use crate::websocket::notice_holder::subscribe_notice;
+
+pub async fn subscribe_handler(inbound: InboundWithTx) {
+    let parse_result = inbound.decode_detail::<SubscribeNoticeMsg>(&inbound.msg_wrapper.body);
+    if parse_result.is_err() {
+        inbound.fail(MpcErrorKind::MalformedMessage, "fail to parse subscribe_notice_msg".to_string()).await;
+        return;
+    }
+    let mut identity_id = parse_result.unwrap().identity_id;
+    // notices of an identity are only delivered to its authenticated subject
+    if let Some(auth_identity) = &inbound.auth_identity {
+        if identity_id.is_empty() {
+            identity_id = auth_identity.subject.clone();
+        }
+        if auth_identity.subject != identity_id {
+            error!("identity {} is not allowed to subscribe notices of {}", auth_identity.subject, identity_id);
+            inbound.fail(MpcErrorKind::Forbidden, "notices are owned by another identity".to_string()).await;
+            return;
+        }
+    }
+    if identity_id.is_empty() {
+        inbound.fail(MpcErrorKind::InvalidRequest, "identity_id is empty".to_string()).await;
+        return;
+    }
+
+    // rsp first, so the pending notices arrive after it
+    inbound.success_rsp(None).await;
+    subscribe_notice(&identity_id, &inbound.socket_id, inbound.producer()).await;
+    info!("subscribe notices: identity_id={}, socket_id={}", identity_id, inbound.socket_id);
+}
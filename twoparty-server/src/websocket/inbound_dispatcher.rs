@@ -1,53 +1,117 @@
 use axum::extract::ws::{Message};
 
-use tokio::sync::mpsc::UnboundedSender;
+use serde::{Deserialize, Serialize};
 use tracing::{error};
 
 use common::{get_tsp, socketmsg};
-use common::socketmsg::{MsgWrapper, MSG_ACTION_RSP, RSP_CODE_SUCCESS, MSG_ACTION_REQ};
+use common::errors::MpcErrorKind;
+use common::socketmsg::codec::{decode_detail, encode_detail, encode_msg};
+use common::socketmsg::{MsgWrapper, MSG_ACTION_RSP, RSP_CODE_SUCCESS, MSG_ACTION_REQ, MSG_ACTION_ACK, RSP_CODE_TOO_MANY_REQUESTS, RetryAfter, MSG_VERSIONS, MSG_VERSION_V1};
 
+use crate::audit::{audit_failure, AuditEntry};
+use crate::auth::AuthIdentity;
+use crate::compute::ComputeError;
+use crate::websocket::connection_holder::WsProducer;
+use crate::websocket::step_cache::{store_step, StepFlight};
 use crate::websocket::handler::mpc22_handler::mpc22_handler;
+use crate::websocket::handler::notice_handler::subscribe_handler;
+use crate::websocket::handler::negotiate_handler::negotiate_handler;
+use crate::websocket::notice_holder::ack_notice;
 
 
-pub async fn dispatch_inbound(msg_wrapper: MsgWrapper, tx: UnboundedSender<Message>, socket_id: String) {
-    let inbound_with_sender = InboundWithTx {
-        msg_wrapper: msg_wrapper.clone(),
-        tx,
-        socket_id,
-    };
+pub async fn dispatch_inbound(msg_wrapper: MsgWrapper, producer: WsProducer, socket_id: String, peer: String, auth_identity: Option<AuthIdentity>) {
+    let inbound_with_sender = InboundWithTx::new(msg_wrapper.clone(), producer, socket_id, peer, auth_identity);
 
     if msg_wrapper.action == MSG_ACTION_REQ {
         match msg_wrapper.action_code {
             socketmsg::REQ_CODE_MPC22 => {
                 mpc22_handler(inbound_with_sender).await;
             }
+            socketmsg::REQ_CODE_SUBSCRIBE_NOTICE => {
+                subscribe_handler(inbound_with_sender).await;
+            }
+            socketmsg::REQ_CODE_NEGOTIATE => {
+                negotiate_handler(inbound_with_sender).await;
+            }
             RSP_CODE_SUCCESS => {
                 println!("nothing")
             }
             _ => {}
         }
+    } else if msg_wrapper.action == MSG_ACTION_ACK {
+        ack_notice(&inbound_with_sender.socket_id, &msg_wrapper.notice_id).await;
     }
 }
 
 pub struct InboundWithTx {
     pub msg_wrapper: MsgWrapper,
-    tx: UnboundedSender<Message>,
+    producer: WsProducer,
     pub socket_id: String,
+    // remote address of the websocket connection
+    pub peer: String,
+    // None if authentication is disabled
+    pub auth_identity: Option<AuthIdentity>,
+    // (step, request hash) a success rsp is cached with
+    step_cache: Option<(u8, Vec<u8>)>,
+    // the step is in flight until this req is dropped, after its rsp is cached
+    step_flight: Option<StepFlight>,
 }
 
 impl InboundWithTx {
+    pub fn new(msg_wrapper: MsgWrapper, producer: WsProducer, socket_id: String, peer: String, auth_identity: Option<AuthIdentity>) -> Self {
+        InboundWithTx {
+            msg_wrapper,
+            producer,
+            socket_id,
+            peer,
+            auth_identity,
+            step_cache: None,
+            step_flight: None,
+        }
+    }
+
+    pub fn producer(&self) -> WsProducer {
+        self.producer.clone()
+    }
+
+    // cache the success rsp of the step, an exact retry will be replied with it
+    pub fn cache_step_rsp(&mut self, step: u8, req_hash: Vec<u8>, step_flight: StepFlight) {
+        self.step_cache = Some((step, req_hash));
+        self.step_flight = Some(step_flight);
+    }
+
+    // decode the body or a msg_detail of the req in the encoding of its version
+    pub fn decode_detail<'a, T: Deserialize<'a>>(&self, bytes: &'a [u8]) -> Result<T, String> {
+        decode_detail(self.msg_wrapper.version, bytes)
+    }
+
+    // encode a rsp body in the encoding of the req
+    pub fn encode_detail<T: ?Sized + Serialize>(&self, t: &T) -> Vec<u8> {
+        encode_detail(self.rsp_version(), t).unwrap()
+    }
+
     pub async fn send_async(&self, msg: MsgWrapper) {
-        let msg_bytes_r = serde_json::to_vec(&msg);
+        let msg_bytes_r = encode_msg(&msg);
         if let Err(_e) = msg_bytes_r {
             error!("InboundWithTx: failed to serialize outbound msg={:?}", msg);
             return;
         }
         let axum_message = Message::from(msg_bytes_r.unwrap());
-        self.tx.send(axum_message).unwrap_or(());
+        self.producer.send(axum_message);
+    }
+
+    // reply in the version of the req
+    fn rsp_version(&self) -> u16 {
+        let version = self.msg_wrapper.version;
+        if !MSG_VERSIONS.contains(&version) {
+            return MSG_VERSION_V1;
+        }
+        version
     }
 
     fn base_rsp(&self) -> MsgWrapper {
         let req_msg = &self.msg_wrapper;
+        let version = self.rsp_version();
         MsgWrapper {
             seq: req_msg.seq,
             timestamp: get_tsp(),
@@ -56,6 +120,8 @@ impl InboundWithTx {
             body: vec![],
             error_msg: "".to_string(),
             notice_id: "".to_string(),
+            version,
+            error_code: 0,
         }
     }
 
@@ -64,14 +130,35 @@ impl InboundWithTx {
         if let Some(body) = option_body {
             rsp.body = body;
         }
+        if let Some((step, req_hash)) = &self.step_cache {
+            store_step(&self.socket_id, *step, req_hash.clone(), rsp.body.clone()).await;
+        }
+        self.send_async(rsp).await;
+    }
+
+    // body is a RetryAfter
+    pub async fn too_many_requests_rsp(&self, retry_after_ms: u64, error_msg: String) {
+        let mut rsp = self.base_rsp();
+        rsp.action_code = RSP_CODE_TOO_MANY_REQUESTS;
+        rsp.error_code = MpcErrorKind::RateLimited.code();
+        rsp.body = self.encode_detail(&RetryAfter { retry_after_ms });
+        rsp.error_msg = error_msg;
         self.send_async(rsp).await;
     }
 
-    pub async fn fail_rsp(&self, rsp_code: u32, error_msg: String) {
+    pub async fn fail(&self, kind: MpcErrorKind, error_msg: String) {
         let mut rsp = self.base_rsp();
-        rsp.action_code = rsp_code;
+        rsp.action_code = kind.rsp_code();
+        rsp.error_code = kind.code();
         rsp.error_msg = error_msg;
         self.send_async(rsp).await;
     }
+
+    // a step the compute pool could not run, e.g. saturated, is failed & audited with the kind of the pool
+    pub async fn fail_compute(&self, compute_error: ComputeError, audit_entry: AuditEntry) {
+        error!("{}", &compute_error.msg);
+        self.fail(compute_error.kind, compute_error.msg.clone()).await;
+        audit_failure(audit_entry, compute_error.kind, &compute_error.msg, None).await;
+    }
 }
 
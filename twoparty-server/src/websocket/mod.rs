@@ -1,45 +1,105 @@
 mod handler;
 mod inbound_dispatcher;
 mod connection_holder;
+mod replay_guard;
+mod step_cache;
+mod notice_holder;
+
+pub use notice_holder::publish_notice;
+pub use handler::mpc22_handler::supported_capabilities;
+pub use handler::mpc22_rotation::sweep_rotations;
 
 use std::net::SocketAddr;
 
 use std::time::Duration;
 
-use axum::extract::{ConnectInfo, WebSocketUpgrade};
-use axum::extract::ws::{Message, WebSocket};
-use axum::response::IntoResponse;
+use axum::extract::{ConnectInfo, Query, WebSocketUpgrade};
+use axum::extract::ws::{close_code, Message, WebSocket};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 use futures_util::{StreamExt};
+use serde::Deserialize;
 
 use tokio::time;
 
 
-use tracing::{error, info};
-use common::socketmsg::MsgWrapper;
+use tracing::{error, info, warn};
+use common::errors::MpcErrorKind;
+use common::get_tsp;
+use common::socketmsg::{MSG_ACTION_REQ, MSG_VERSIONS, RetryAfter};
+use common::socketmsg::codec::decode_msg;
 use common;
+use crate::auth::{AuthIdentity, get_authenticator};
 use crate::config::AppConfig;
+use crate::metrics::ConnectionGuard;
+use crate::ratelimit::{check_rate_limit, LimitKind};
 use crate::websocket::connection_holder::{drop_producer, share_ws_sender_with_channel};
 
-use crate::websocket::inbound_dispatcher::dispatch_inbound;
+use crate::websocket::inbound_dispatcher::{dispatch_inbound, InboundWithTx};
+use crate::websocket::replay_guard::ReplayGuard;
+
 
+#[derive(Deserialize)]
+pub struct WsQuery {
+    // for clients which can not set headers on websocket upgrade, e.g. browsers
+    access_token: Option<String>,
+}
 
 // https://github.com/tokio-rs/axum/blob/main/examples/websockets/src/main.rs
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
-) -> impl IntoResponse {
+    headers: HeaderMap,
+    Query(query): Query<WsQuery>,
+) -> Response {
     info!("get ws upgrade request");
-    // upgrade stream protocol to websocket, register the socket handler
-    ws.on_upgrade(move |socket| handle_socket(socket, addr))
+    // count every attempt, including those failing authentication
+    if let Err(retry_after_ms) = check_rate_limit(LimitKind::ConnectPerIp, &addr.ip().to_string()) {
+        warn!("reject ws upgrade, too many connection attempts, peer address:{}", addr);
+        let retry_after_secs = retry_after_ms.div_ceil(1000).to_string();
+        let body = serde_json::to_string(&RetryAfter { retry_after_ms }).unwrap();
+        return (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, retry_after_secs)], body).into_response();
+    }
+    // authenticate before upgrade, None means authentication is disabled
+    let mut auth_identity = None;
+    if let Some(authenticator) = get_authenticator() {
+        let bearer = headers.get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(|v| v.to_string());
+        let option_token = bearer.or(query.access_token);
+        if option_token.is_none() {
+            warn!("reject ws upgrade without token, peer address:{}", addr);
+            return (StatusCode::UNAUTHORIZED, "missing bearer token").into_response();
+        }
+        match authenticator.authenticate(&option_token.unwrap()) {
+            Ok(identity) => {
+                auth_identity = Some(identity);
+            }
+            Err(e) => {
+                warn!("reject ws upgrade, peer address:{}, err={}", addr, e);
+                return (StatusCode::UNAUTHORIZED, e).into_response();
+            }
+        }
+    }
+    // upgrade stream protocol to websocket, register the socket handler.
+    // tungstenite refuses a msg beyond twice the limit without buffering it, a smaller oversized msg
+    // is refused in handle_socket with a close frame
+    let max_message_size = AppConfig::get_app_config().ws_limits.max_message_size;
+    ws.max_message_size(max_message_size * 2)
+        .on_upgrade(move |socket| handle_socket(socket, addr, auth_identity))
 }
 
-async fn handle_socket(socket: WebSocket, peer: SocketAddr) {
-    info!("New WebSocket connection, peer address:{}", peer);
+async fn handle_socket(socket: WebSocket, peer: SocketAddr, auth_identity: Option<AuthIdentity>) {
+    info!("New WebSocket connection, peer address:{}, identity:{:?}", peer, auth_identity.as_ref().map(|i| &i.subject));
+    let _connection_guard = ConnectionGuard::track();
     let app_config = AppConfig::get_app_config();
     let socket_id = common::get_uuid();
+    let ws_limits = &app_config.ws_limits;
 
     let (sender, mut receiver) = socket.split();
-    let tx = share_ws_sender_with_channel(sender, socket_id.clone()).await;
+    let producer = share_ws_sender_with_channel(sender, socket_id.clone(), ws_limits.outbound_queue).await;
+    let mut replay_guard = ReplayGuard::new(ws_limits.timestamp_skew_secs);
     loop {
         // tungstenite-rs implements auto pong, no need to process ping,
         // but ping msg will also be bubbling to here.
@@ -48,6 +108,7 @@ async fn handle_socket(socket: WebSocket, peer: SocketAddr) {
             Err(_elapsed) => {
                 // oops, client no heartbeat, close stream
                 info!("client no heartbeat, close stream");
+                producer.close(close_code::AWAY, "idle timeout");
                 drop_producer(&socket_id).await;
                 return;
             }
@@ -63,16 +124,44 @@ async fn handle_socket(socket: WebSocket, peer: SocketAddr) {
                     // Connection reset without closing handshake
                     let e = msg_result.err().unwrap();
                     info!("Protocol error: {}", e);
+                    producer.close(close_code::PROTOCOL, "protocol error");
                     drop_producer(&socket_id).await;
                     return;
                 }
 
                 let msg = msg_result.unwrap();
+                // refuse an oversized msg before parsing it
+                let msg_size = match &msg {
+                    Message::Binary(bytes) => bytes.len(),
+                    Message::Text(txt) => txt.len(),
+                    _ => 0,
+                };
+                if msg_size > ws_limits.max_message_size {
+                    warn!("close connection, msg size {} exceeds {}, peer address:{}", msg_size, ws_limits.max_message_size, peer);
+                    producer.close(close_code::SIZE, "message too big");
+                    drop_producer(&socket_id).await;
+                    return;
+                }
                 match msg {
                     Message::Binary(bytes) => {
-                        match serde_json::from_slice::<MsgWrapper>(&bytes) {
+                        match decode_msg(&bytes) {
                             Ok(msg_wrapper) => {
-                                dispatch_inbound(msg_wrapper, tx.clone(), socket_id.clone()).await;
+                                if !MSG_VERSIONS.contains(&msg_wrapper.version) {
+                                    warn!("refuse msg version {}, peer address:{}", msg_wrapper.version, peer);
+                                    let inbound = InboundWithTx::new(msg_wrapper, producer.clone(), socket_id.clone(), peer.to_string(), auth_identity.clone());
+                                    inbound.fail(MpcErrorKind::VersionMismatch, "unsupported msg version".to_string()).await;
+                                    continue;
+                                }
+                                // refuse stale & replayed requests before they reach protocol code
+                                if msg_wrapper.action == MSG_ACTION_REQ {
+                                    if let Err(e) = replay_guard.check(&msg_wrapper, get_tsp()) {
+                                        warn!("refuse request: {}, peer address:{}", e, peer);
+                                        let inbound = InboundWithTx::new(msg_wrapper, producer.clone(), socket_id.clone(), peer.to_string(), auth_identity.clone());
+                                        inbound.fail(MpcErrorKind::InvalidRequest, e).await;
+                                        continue;
+                                    }
+                                }
+                                dispatch_inbound(msg_wrapper, producer.clone(), socket_id.clone(), peer.to_string(), auth_identity.clone()).await;
                             }
                             Err(e) => {
                                 error!("fail to parse bytes to MsgWrapper: err={}", e);
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use axum::extract::ws::Message;
+use tokio::sync::RwLock;
+use tracing::{error, info};
+use common::{get_tsp, get_uuid};
+use common::socketmsg::{MSG_ACTION_NOTICE, MSG_VERSION_V1, MsgWrapper, Notice};
+use crate::websocket::connection_holder::WsProducer;
+
+// older notices of an identity are dropped beyond this
+const MAX_PENDING_NOTICES: usize = 100;
+
+// notices are kept in memory until acknowledged, they do not survive a restart of the server
+#[derive(Default)]
+struct NoticeHolder {
+    // identity_id -> unacknowledged notices
+    pending: HashMap<String, Vec<MsgWrapper>>,
+    // identity_id -> socket_id -> producer
+    subscribers: HashMap<String, HashMap<String, WsProducer>>,
+    // socket_id -> identity_id
+    subscriptions: HashMap<String, String>,
+}
+
+impl NoticeHolder {
+    fn unsubscribe(&mut self, socket_id: &str) {
+        if let Some(identity_id) = self.subscriptions.remove(socket_id) {
+            if let Some(producers) = self.subscribers.get_mut(&identity_id) {
+                producers.remove(socket_id);
+                if producers.is_empty() {
+                    self.subscribers.remove(&identity_id);
+                }
+            }
+        }
+    }
+}
+
+static NOTICE_HOLDER: LazyLock<Arc<RwLock<NoticeHolder>>> = LazyLock::new(|| {
+    Arc::new(RwLock::new(NoticeHolder::default()))
+});
+
+fn deliver(producer: &WsProducer, notice_msg: &MsgWrapper) {
+    match notice_msg.to_bytes() {
+        Ok(bytes) => producer.send(Message::from(bytes)),
+        Err(e) => error!("fail to encode notice {}: {}", notice_msg.notice_id, e),
+    }
+}
+
+// push a notice to every subscribed connection of the identity, return the notice_id
+pub async fn publish_notice(identity_id: &str, notice_type: u32, share_id: &str, message: &str) -> String {
+    let notice = Notice {
+        share_id: share_id.to_string(),
+        message: message.to_string(),
+        created_at: get_tsp(),
+    };
+    let notice_msg = MsgWrapper {
+        seq: 0,
+        timestamp: get_tsp(),
+        action: MSG_ACTION_NOTICE,
+        action_code: notice_type,
+        body: serde_json::to_vec(&notice).unwrap(),
+        error_msg: "".to_string(),
+        notice_id: get_uuid(),
+        // json, subscribers may run any msg version
+        version: MSG_VERSION_V1,
+        error_code: 0,
+    };
+
+    let mut holder = NOTICE_HOLDER.write().await;
+    if let Some(producers) = holder.subscribers.get(identity_id) {
+        producers.values().for_each(|producer| deliver(producer, &notice_msg));
+    }
+    let pending = holder.pending.entry(identity_id.to_string()).or_default();
+    pending.push(notice_msg.clone());
+    if pending.len() > MAX_PENDING_NOTICES {
+        pending.remove(0);
+    }
+    info!("publish notice: identity_id={}, notice_type={}, notice_id={}", identity_id, notice_type, notice_msg.notice_id);
+    notice_msg.notice_id
+}
+
+// register the connection for notices of the identity, then redeliver the unacknowledged ones.
+// the backlog may be larger than the outbound queue, it is sent by a task which waits for room,
+// so a notice published meanwhile can arrive before older ones
+pub async fn subscribe_notice(identity_id: &str, socket_id: &str, producer: WsProducer) {
+    let mut holder = NOTICE_HOLDER.write().await;
+    holder.unsubscribe(socket_id);
+    let backlog = holder.pending.get(identity_id).cloned().unwrap_or_default();
+    holder.subscribers.entry(identity_id.to_string()).or_default().insert(socket_id.to_string(), producer.clone());
+    holder.subscriptions.insert(socket_id.to_string(), identity_id.to_string());
+    drop(holder);
+
+    if backlog.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        for notice_msg in backlog {
+            match notice_msg.to_bytes() {
+                Ok(bytes) => {
+                    if !producer.send_wait(Message::from(bytes)).await {
+                        return;
+                    }
+                }
+                Err(e) => error!("fail to encode notice {}: {}", notice_msg.notice_id, e),
+            }
+        }
+    });
+}
+
+// an ack is accepted only from a connection subscribed to the identity of the notice
+pub async fn ack_notice(socket_id: &str, notice_id: &str) {
+    let mut holder = NOTICE_HOLDER.write().await;
+    let option_identity_id = holder.subscriptions.get(socket_id).cloned();
+    if let Some(identity_id) = option_identity_id {
+        if let Some(pending) = holder.pending.get_mut(&identity_id) {
+            pending.retain(|notice_msg| notice_msg.notice_id != notice_id);
+            if pending.is_empty() {
+                holder.pending.remove(&identity_id);
+            }
+        }
+    }
+}
+
+pub async fn unsubscribe_notice(socket_id: &str) {
+    NOTICE_HOLDER.write().await.unsubscribe(socket_id);
+}
+
+
+#[cfg(test)]
+mod test {
+    use common::get_uuid;
+    use crate::websocket::connection_holder::test_producer;
+    use crate::websocket::notice_holder::{MAX_PENDING_NOTICES, publish_notice, subscribe_notice, unsubscribe_notice};
+
+    #[tokio::test]
+    async fn test_redeliver_beyond_outbound_queue() {
+        let identity_id = get_uuid();
+        for i in 0..MAX_PENDING_NOTICES {
+            publish_notice(&identity_id, 1, "", &format!("notice {}", i)).await;
+        }
+
+        let socket_id = get_uuid();
+        let (producer, mut rx) = test_producer(&socket_id, 32);
+        subscribe_notice(&identity_id, &socket_id, producer).await;
+        for _ in 0..MAX_PENDING_NOTICES {
+            assert!(rx.recv().await.is_some());
+        }
+        unsubscribe_notice(&socket_id).await;
+    }
+}
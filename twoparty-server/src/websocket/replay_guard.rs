@@ -0,0 +1,66 @@
+use common::socketmsg::MsgWrapper;
+
+// refuses stale and replayed requests of a connection before they are dispatched
+pub struct ReplayGuard {
+    skew_ms: u128,
+    last_seq: Option<u32>,
+}
+
+impl ReplayGuard {
+    pub fn new(skew_secs: u64) -> Self {
+        ReplayGuard { skew_ms: skew_secs as u128 * 1000, last_seq: None }
+    }
+
+    // the timestamp must be within the skew window of now, seq must increase within the connection
+    pub fn check(&mut self, req: &MsgWrapper, now: u128) -> Result<(), String> {
+        if req.timestamp.abs_diff(now) > self.skew_ms {
+            return Err(format!("timestamp out of window, timestamp={}, server time={}", req.timestamp, now));
+        }
+        if let Some(last_seq) = self.last_seq {
+            if req.seq <= last_seq {
+                return Err(format!("seq not increasing, seq={}, last seq={}", req.seq, last_seq));
+            }
+        }
+        self.last_seq = Some(req.seq);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use common::socketmsg::{MSG_ACTION_REQ, MSG_VERSION_V1, MsgWrapper, REQ_CODE_MPC22};
+    use crate::websocket::replay_guard::ReplayGuard;
+
+    fn req(seq: u32, timestamp: u128) -> MsgWrapper {
+        MsgWrapper {
+            seq,
+            timestamp,
+            action: MSG_ACTION_REQ,
+            action_code: REQ_CODE_MPC22,
+            body: vec![],
+            error_msg: "".to_string(),
+            notice_id: "".to_string(),
+            version: MSG_VERSION_V1,
+            error_code: 0,
+        }
+    }
+
+    #[test]
+    fn test_replay_guard() {
+        let now = 1_700_000_000_000;
+        let mut guard = ReplayGuard::new(30);
+        assert!(guard.check(&req(0, now), now).is_ok());
+        assert!(guard.check(&req(1, now - 29_000), now).is_ok());
+        // duplicate & decreasing seq
+        assert!(guard.check(&req(1, now), now).is_err());
+        assert!(guard.check(&req(0, now), now).is_err());
+        // gaps are allowed
+        assert!(guard.check(&req(5, now + 29_000), now).is_ok());
+        // captured long ago or from the future
+        assert!(guard.check(&req(6, now - 31_000), now).is_err());
+        assert!(guard.check(&req(6, now + 31_000), now).is_err());
+        // a refused request does not advance seq
+        assert!(guard.check(&req(6, now), now).is_ok());
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use tokio::sync::{RwLock, watch};
+
+// successful responses of the current mpc session of a connection,
+// so a step retried by party1 gets the same response instead of fresh ephemeral values
+#[derive(Default)]
+pub struct SessionSteps {
+    // step -> (sha256 of the request body, response body)
+    steps: HashMap<u8, (Vec<u8>, Vec<u8>)>,
+    // step -> (sha256 of the request body, closed once the handler computing it returns)
+    in_flight: HashMap<u8, (Vec<u8>, watch::Receiver<()>)>,
+}
+
+// held by the handler of a step, dropping it ends the flight
+#[derive(Debug)]
+pub struct StepFlight {
+    _flight_tx: watch::Sender<()>,
+}
+
+// what lookup_step tells the handler of a step
+#[derive(Debug)]
+pub enum StepLookup {
+    // an exact retry, reply the cached response body
+    Cached(Vec<u8>),
+    // the step already completed with a different request
+    Conflict,
+    // run the step, it stays in flight until the StepFlight is dropped
+    Miss(StepFlight),
+}
+
+// a lookup under the cache lock, lookup_step waits out InFlight without holding it
+#[derive(Debug)]
+enum SessionLookup {
+    Step(StepLookup),
+    // the same request is still computed, wait for it and look again
+    InFlight(watch::Receiver<()>),
+}
+
+impl SessionSteps {
+    fn lookup(&mut self, step: u8, req_hash: &[u8]) -> SessionLookup {
+        self.in_flight.retain(|_, (_, flight_rx)| flight_rx.has_changed().is_ok());
+        if let Some((cached_hash, rsp_body)) = self.steps.get(&step) {
+            if cached_hash == req_hash {
+                return SessionLookup::Step(StepLookup::Cached(rsp_body.clone()));
+            }
+            if step != 1 {
+                return SessionLookup::Step(StepLookup::Conflict);
+            }
+        }
+        if let Some((flight_hash, flight_rx)) = self.in_flight.get(&step) {
+            if flight_hash == req_hash {
+                return SessionLookup::InFlight(flight_rx.clone());
+            }
+            if step != 1 {
+                return SessionLookup::Step(StepLookup::Conflict);
+            }
+        }
+        // a different step1 starts a new session
+        if step == 1 {
+            self.steps.clear();
+        }
+        let (flight_tx, flight_rx) = watch::channel(());
+        self.in_flight.insert(step, (req_hash.to_vec(), flight_rx));
+        SessionLookup::Step(StepLookup::Miss(StepFlight { _flight_tx: flight_tx }))
+    }
+
+    fn flight(&self) -> Option<watch::Receiver<()>> {
+        self.in_flight.values()
+            .map(|(_, flight_rx)| flight_rx)
+            .find(|flight_rx| flight_rx.has_changed().is_ok())
+            .cloned()
+    }
+}
+
+#[allow(clippy::type_complexity)]
+static STEP_CACHES: LazyLock<Arc<RwLock<HashMap<String, SessionSteps>>>> = LazyLock::new(|| {
+    Arc::new(RwLock::new(HashMap::new()))
+});
+
+// a step resent while it is computed waits for that computation
+pub async fn lookup_step(socket_id: &str, step: u8, req_hash: &[u8]) -> StepLookup {
+    loop {
+        let lookup = STEP_CACHES.write().await
+            .entry(socket_id.to_string()).or_default()
+            .lookup(step, req_hash);
+        match lookup {
+            SessionLookup::InFlight(mut flight_rx) => {
+                flight_rx.changed().await.unwrap_or(());
+            }
+            SessionLookup::Step(step_lookup) => return step_lookup,
+        }
+    }
+}
+
+pub async fn store_step(socket_id: &str, step: u8, req_hash: Vec<u8>, rsp_body: Vec<u8>) {
+    let mut caches = STEP_CACHES.write().await;
+    caches.entry(socket_id.to_string()).or_default().steps.insert(step, (req_hash, rsp_body));
+}
+
+// wait until no step of the connection is computed, its handler writes socket_local & the rsp under this socket_id
+pub async fn wait_steps(socket_id: &str) {
+    loop {
+        let option_flight = STEP_CACHES.read().await.get(socket_id).and_then(|session| session.flight());
+        match option_flight {
+            Some(mut flight_rx) => flight_rx.changed().await.unwrap_or(()),
+            None => return,
+        }
+    }
+}
+
+pub async fn take_step_cache(socket_id: &str) -> Option<SessionSteps> {
+    STEP_CACHES.write().await.remove(socket_id)
+}
+
+pub async fn put_step_cache(socket_id: &str, session_steps: SessionSteps) {
+    STEP_CACHES.write().await.insert(socket_id.to_string(), session_steps);
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use common::get_uuid;
+    use crate::websocket::step_cache::{lookup_step, SessionLookup, SessionSteps, StepLookup, store_step, wait_steps};
+
+    #[test]
+    fn test_session_steps() {
+        let mut session = SessionSteps::default();
+        assert!(matches!(session.lookup(1, b"req1"), SessionLookup::Step(StepLookup::Miss(_))));
+        session.steps.insert(1, (b"req1".to_vec(), b"rsp1".to_vec()));
+        assert!(matches!(session.lookup(2, b"req2"), SessionLookup::Step(StepLookup::Miss(_))));
+        session.steps.insert(2, (b"req2".to_vec(), b"rsp2".to_vec()));
+
+        // exact retries
+        assert!(matches!(session.lookup(1, b"req1"), SessionLookup::Step(StepLookup::Cached(rsp)) if rsp == b"rsp1"));
+        assert!(matches!(session.lookup(2, b"req2"), SessionLookup::Step(StepLookup::Cached(rsp)) if rsp == b"rsp2"));
+        // a different payload for a completed step
+        assert!(matches!(session.lookup(2, b"other"), SessionLookup::Step(StepLookup::Conflict)));
+
+        // a new session
+        assert!(matches!(session.lookup(1, b"other"), SessionLookup::Step(StepLookup::Miss(_))));
+        assert!(matches!(session.lookup(2, b"req2"), SessionLookup::Step(StepLookup::Miss(_))));
+    }
+
+    #[test]
+    fn test_step_in_flight() {
+        let mut session = SessionSteps::default();
+        let flight = session.lookup(2, b"req2");
+        assert!(matches!(flight, SessionLookup::Step(StepLookup::Miss(_))));
+        assert!(matches!(session.lookup(2, b"req2"), SessionLookup::InFlight(_)));
+        assert!(matches!(session.lookup(2, b"other"), SessionLookup::Step(StepLookup::Conflict)));
+        assert!(session.flight().is_some());
+
+        // the handler returned without a success rsp, the step can run again
+        drop(flight);
+        assert!(session.flight().is_none());
+        assert!(matches!(session.lookup(2, b"req2"), SessionLookup::Step(StepLookup::Miss(_))));
+    }
+
+    #[tokio::test]
+    async fn test_wait_steps() {
+        let socket_id = get_uuid();
+        let flight = lookup_step(&socket_id, 2, b"req2").await;
+        let waiter_socket_id = socket_id.clone();
+        let waiter = tokio::spawn(async move {
+            wait_steps(&waiter_socket_id).await;
+            lookup_step(&waiter_socket_id, 2, b"req2").await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+
+        store_step(&socket_id, 2, b"req2".to_vec(), b"rsp2".to_vec()).await;
+        drop(flight);
+        assert!(matches!(waiter.await.unwrap(), StepLookup::Cached(rsp) if rsp == b"rsp2"));
+    }
+}